@@ -0,0 +1,150 @@
+//! Contains the `Ast` arena and the IDs that index into it.
+//!
+//! Instead of each node owning its children in a `Box`, every
+//! node lives in one of the `Ast`'s vectors and refers to its
+//! children by `ExprId` or `StmtId`.  That gives every node a
+//! stable ID, so other passes can keep what they learn about a
+//! node in a `SideTable` keyed by its ID instead of having to
+//! change the tree.  It also means dropping a deeply nested tree
+//! doesn't recurse.
+
+use token::span::Span;
+
+use crate::expr::Expr;
+use crate::stmt::Stmt;
+
+
+/// The ID of an expression in an `Ast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+/// The ID of a statement in an `Ast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StmtId(u32);
+
+/// Something that identifies a node and can index a `SideTable`.
+pub trait NodeId: Copy {
+    fn index(self) -> usize;
+}
+
+impl NodeId for ExprId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl NodeId for StmtId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+
+/// The syntax tree of a whole program.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ast {
+    exprs: Vec<Expr>,
+    stmts: Vec<Stmt>,
+
+    /// The span of the source each node was parsed from, indexed the
+    /// same way as the nodes.
+    expr_spans: Vec<Span>,
+    stmt_spans: Vec<Span>,
+
+    /// The top-level statements, in order.
+    pub statements: Vec<StmtId>,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Ast::default()
+    }
+
+    /// Put an expression in the arena and get its ID.
+    pub fn add_expr(&mut self, expr: Expr, span: Span) -> ExprId {
+        self.exprs.push(expr);
+        self.expr_spans.push(span);
+        ExprId((self.exprs.len() - 1) as u32)
+    }
+
+    /// Put a statement in the arena and get its ID.
+    pub fn add_stmt(&mut self, stmt: Stmt, span: Span) -> StmtId {
+        self.stmts.push(stmt);
+        self.stmt_spans.push(span);
+        StmtId((self.stmts.len() - 1) as u32)
+    }
+
+    pub fn expr(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.index()]
+    }
+
+    pub fn stmt(&self, id: StmtId) -> &Stmt {
+        &self.stmts[id.index()]
+    }
+
+    pub fn expr_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.index()]
+    }
+
+    pub fn stmt_mut(&mut self, id: StmtId) -> &mut Stmt {
+        &mut self.stmts[id.index()]
+    }
+
+    pub fn expr_span(&self, id: ExprId) -> Span {
+        self.expr_spans[id.index()]
+    }
+
+    pub fn stmt_span(&self, id: StmtId) -> Span {
+        self.stmt_spans[id.index()]
+    }
+
+    /// How many expressions are in the arena (including any that
+    /// a `Folder` has cut out of the tree).
+    pub fn expr_count(&self) -> usize {
+        self.exprs.len()
+    }
+
+    /// How many statements are in the arena (including any that
+    /// a `Folder` has cut out of the tree).
+    pub fn stmt_count(&self) -> usize {
+        self.stmts.len()
+    }
+}
+
+
+/// Information about some of the nodes of an `Ast`, keyed by node ID.
+///
+/// This is how passes attach what they work out (like how far out
+/// a variable's binding is) without touching the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SideTable<Id: NodeId, T> {
+    entries: Vec<Option<T>>,
+    id: std::marker::PhantomData<Id>,
+}
+
+impl<Id: NodeId, T> Default for SideTable<Id, T> {
+    fn default() -> Self {
+        SideTable {
+            entries: Vec::new(),
+            id: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<Id: NodeId, T> SideTable<Id, T> {
+    pub fn new() -> Self {
+        SideTable::default()
+    }
+
+    pub fn insert(&mut self, id: Id, value: T) {
+        let index = id.index();
+        if index >= self.entries.len() {
+            self.entries.resize_with(index + 1, || None);
+        }
+        self.entries[index] = Some(value);
+    }
+
+    pub fn get(&self, id: Id) -> Option<&T> {
+        self.entries.get(id.index()).and_then(|entry| entry.as_ref())
+    }
+}
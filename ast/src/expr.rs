@@ -0,0 +1,93 @@
+//! Contains the `Expr` enum.
+
+use token::token::{Literal, Token};
+
+use crate::arena::ExprId;
+
+
+/// An expression node in the syntax tree.
+///
+/// Children are `ExprId`s into the `Ast` the node lives in.
+///
+/// Operators keep their whole `Token` (not just the `TokenType`)
+/// so that later passes can point back at the line they came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// `name ← value`
+    Assign {
+        name: Token,
+        value: ExprId,
+    },
+
+    /// `left operator right` for the arithmetic, comparison
+    /// and equality operators.
+    Binary {
+        left: ExprId,
+        operator: Token,
+        right: ExprId,
+    },
+
+    /// `callee(arguments)`
+    ///
+    /// `paren` is the closing parenthesis, which is the token
+    /// we report if the call goes wrong.
+    Call {
+        callee: ExprId,
+        paren: Token,
+        arguments: Vec<ExprId>,
+    },
+
+    /// `object.name`, reading a property.
+    Get {
+        object: ExprId,
+        name: Token,
+    },
+
+    /// `(expression)`
+    Grouping {
+        expression: ExprId,
+    },
+
+    /// A number, string, `true`, `false` or `nil`.
+    Literal {
+        value: Literal,
+    },
+
+    /// `left and right` or `left or right`.
+    ///
+    /// These are separate from `Binary` because they short-circuit.
+    Logical {
+        left: ExprId,
+        operator: Token,
+        right: ExprId,
+    },
+
+    /// `object.name ← value`, writing a field.
+    Set {
+        object: ExprId,
+        name: Token,
+        value: ExprId,
+    },
+
+    /// `super.method` inside a method of a subclass.
+    Super {
+        keyword: Token,
+        method: Token,
+    },
+
+    /// `this` inside a method.
+    This {
+        keyword: Token,
+    },
+
+    /// `¬right` or `⁻right`.
+    Unary {
+        operator: Token,
+        right: ExprId,
+    },
+
+    /// A reference to a variable.
+    Variable {
+        name: Token,
+    },
+}
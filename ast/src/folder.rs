@@ -0,0 +1,158 @@
+//! Contains the `Folder` trait.
+//!
+//! A folder is like a `Visitor`, except it can rewrite the
+//! tree, which is what passes like optimizers want.
+//!
+//! Folding a node gives back the ID of the node that should
+//! take its place, and the parent is updated to point at it.
+//! That can be the same ID (maybe after changing the node in
+//! place with `Ast::expr_mut`), a child's ID, or a brand new
+//! node added to the arena.  Nodes that get replaced are left
+//! in the arena but are no longer part of the tree.
+//!
+//! The defaults fold each node's children and keep the node,
+//! so a pass only overrides the nodes it rewrites. An override
+//! usually calls `walk_expr` or `walk_stmt` first, so that it
+//! sees children that have already been folded.
+
+use crate::arena::{Ast, ExprId, StmtId};
+use crate::expr::Expr;
+use crate::stmt::Stmt;
+
+
+/// A pass that rewrites the tree.
+pub trait Folder {
+    fn fold_stmt(&mut self, ast: &mut Ast, stmt: StmtId) -> StmtId {
+        walk_stmt(self, ast, stmt)
+    }
+
+    fn fold_expr(&mut self, ast: &mut Ast, expr: ExprId) -> ExprId {
+        walk_expr(self, ast, expr)
+    }
+}
+
+
+/// Fold every top-level statement of a program.
+pub fn fold_program<F: Folder + ?Sized>(folder: &mut F, ast: &mut Ast) {
+    let statements = std::mem::take(&mut ast.statements);
+    ast.statements = fold_statements(folder, ast, statements);
+}
+
+/// Fold every statement in a list.
+pub fn fold_statements<F: Folder + ?Sized>(folder: &mut F, ast: &mut Ast, statements: Vec<StmtId>) -> Vec<StmtId> {
+    statements.into_iter().map(|statement| folder.fold_stmt(ast, statement)).collect()
+}
+
+/// Fold the children of `stmt` and point it at the results.
+pub fn walk_stmt<F: Folder + ?Sized>(folder: &mut F, ast: &mut Ast, stmt: StmtId) -> StmtId {
+    // work on a copy, since folding the children needs the arena too
+    let mut node = ast.stmt(stmt).clone();
+    match &mut node {
+        Stmt::Block { statements } => *statements = fold_statements(folder, ast, std::mem::take(statements)),
+        Stmt::Expression { expression } | Stmt::Print { expression, .. } => *expression = folder.fold_expr(ast, *expression),
+        Stmt::Class(declaration) => {
+            if let Some(superclass) = &mut declaration.superclass {
+                *superclass = folder.fold_expr(ast, *superclass);
+            }
+            for method in declaration.methods.iter_mut() {
+                method.body = fold_statements(folder, ast, std::mem::take(&mut method.body));
+            }
+        }
+        Stmt::Function(declaration) => {
+            declaration.body = fold_statements(folder, ast, std::mem::take(&mut declaration.body));
+        }
+        Stmt::If { condition, then_branch, else_branch } => {
+            *condition = folder.fold_expr(ast, *condition);
+            *then_branch = folder.fold_stmt(ast, *then_branch);
+            if let Some(else_branch) = else_branch {
+                *else_branch = folder.fold_stmt(ast, *else_branch);
+            }
+        }
+        Stmt::Return { value: Some(expr), .. } | Stmt::Var { initializer: Some(expr), .. } => {
+            *expr = folder.fold_expr(ast, *expr);
+        }
+        Stmt::Return { value: None, .. } | Stmt::Var { initializer: None, .. } => {}
+        Stmt::While { condition, body } => {
+            *condition = folder.fold_expr(ast, *condition);
+            *body = folder.fold_stmt(ast, *body);
+        }
+    }
+    *ast.stmt_mut(stmt) = node;
+    stmt
+}
+
+/// Fold the children of `expr` and point it at the results.
+pub fn walk_expr<F: Folder + ?Sized>(folder: &mut F, ast: &mut Ast, expr: ExprId) -> ExprId {
+    // work on a copy, since folding the children needs the arena too
+    let mut node = ast.expr(expr).clone();
+    match &mut node {
+        Expr::Assign { value: child, .. }
+        | Expr::Get { object: child, .. }
+        | Expr::Grouping { expression: child }
+        | Expr::Unary { right: child, .. } => *child = folder.fold_expr(ast, *child),
+        Expr::Set { object, value, .. } => {
+            *object = folder.fold_expr(ast, *object);
+            *value = folder.fold_expr(ast, *value);
+        }
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            *left = folder.fold_expr(ast, *left);
+            *right = folder.fold_expr(ast, *right);
+        }
+        Expr::Call { callee, arguments, .. } => {
+            *callee = folder.fold_expr(ast, *callee);
+            for argument in arguments.iter_mut() {
+                *argument = folder.fold_expr(ast, *argument);
+            }
+        }
+        Expr::Literal { .. } | Expr::Super { .. } | Expr::This { .. } | Expr::Variable { .. } => return expr,
+    }
+    *ast.expr_mut(expr) = node;
+    expr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sexpr_printer::print_expr;
+    use token::span::Span;
+    use token::token::{Literal, Token};
+    use token::token_type::TokenType;
+
+    /// Takes out every grouping, since the tree already
+    /// says what the precedence is.
+    struct RemoveGroupings;
+
+    impl Folder for RemoveGroupings {
+        fn fold_expr(&mut self, ast: &mut Ast, expr: ExprId) -> ExprId {
+            let expr = walk_expr(self, ast, expr);
+            match ast.expr(expr) {
+                Expr::Grouping { expression } => *expression,
+                _ => expr,
+            }
+        }
+    }
+
+    fn number(ast: &mut Ast, value: f64) -> ExprId {
+        ast.add_expr(Expr::Literal { value: Literal::Number(value) }, Span::default())
+    }
+
+    fn group(ast: &mut Ast, expression: ExprId) -> ExprId {
+        ast.add_expr(Expr::Grouping { expression }, Span::default())
+    }
+
+    #[test]
+    fn test_fold_removes_nested_groupings() {
+        let mut ast = Ast::new();
+        let plus = Token::new(TokenType::Plus, String::from("+"), Literal::NoLiteral, 1, Span::default());
+        let one = number(&mut ast, 1.0);
+        let left = group(&mut ast, one);
+        let right = number(&mut ast, 2.0);
+        let sum = ast.add_expr(Expr::Binary { left, operator: plus, right }, Span::default());
+        let inner = group(&mut ast, sum);
+        let expr = group(&mut ast, inner);
+
+        let folded = RemoveGroupings.fold_expr(&mut ast, expr);
+
+        assert_eq!(print_expr(&ast, folded), "(+ 1 2)");
+    }
+}
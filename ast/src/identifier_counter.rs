@@ -0,0 +1,192 @@
+//! Contains the `IdentifierCounter` pass.
+//!
+//! This is mostly an example of a `Visitor`: it only overrides
+//! the methods for nodes that open a scope or mention a name,
+//! and leaves the rest of the walking to the defaults.
+
+use std::collections::BTreeMap;
+
+use token::token::Token;
+
+use crate::arena::{Ast, ExprId, StmtId};
+use crate::stmt::{ClassDecl, FunctionDecl};
+use crate::visitor::{walk_block, walk_function, walk_program, Visitor};
+
+
+/// How many times each identifier is mentioned directly in one scope.
+///
+/// Mentions inside a nested scope count towards that scope instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeCount {
+    /// `global`, `block`, `class <name>` for a class's methods,
+    /// or `fun <name>` for a function body.
+    pub scope: String,
+
+    /// How many scopes this one is nested in (globals are 0).
+    pub depth: usize,
+
+    /// Identifier to how many times it's mentioned. Declarations,
+    /// parameters, uses and assignments all count.
+    pub identifiers: BTreeMap<String, usize>,
+}
+
+
+/// Count the identifiers in each scope of a program.
+///
+/// The scopes come back in the order they open in the source.
+pub fn count_identifiers(ast: &Ast) -> Vec<ScopeCount> {
+    let mut counter = IdentifierCounter::default();
+    counter.enter_scope(String::from("global"));
+    walk_program(&mut counter, ast);
+    counter.scopes
+}
+
+
+/// The visitor behind `count_identifiers`.
+#[derive(Debug, Default)]
+pub struct IdentifierCounter {
+    /// Every scope seen so far, in the order they opened.
+    pub scopes: Vec<ScopeCount>,
+
+    /// Indexes into `scopes` of the scopes we're currently inside.
+    open_scopes: Vec<usize>,
+}
+
+impl IdentifierCounter {
+    fn enter_scope(&mut self, scope: String) {
+        self.scopes.push(ScopeCount {
+            scope,
+            depth: self.open_scopes.len(),
+            identifiers: BTreeMap::new(),
+        });
+        self.open_scopes.push(self.scopes.len() - 1);
+    }
+
+    fn exit_scope(&mut self) {
+        self.open_scopes.pop();
+    }
+
+    fn count(&mut self, name: &Token) {
+        let current = *self.open_scopes.last().expect("there is always a global scope");
+        *self.scopes[current].identifiers.entry(name.lexeme.clone()).or_insert(0) += 1;
+    }
+}
+
+impl Visitor for IdentifierCounter {
+    fn visit_block(&mut self, ast: &Ast, _stmt: StmtId, statements: &[StmtId]) {
+        self.enter_scope(String::from("block"));
+        walk_block(self, ast, statements);
+        self.exit_scope();
+    }
+
+    fn visit_class(&mut self, ast: &Ast, stmt: StmtId, declaration: &ClassDecl) {
+        // the methods' names belong to the class's own scope
+        self.count(&declaration.name);
+        if let Some(superclass) = declaration.superclass {
+            self.visit_expr(ast, superclass);
+        }
+        self.enter_scope(format!("class {}", declaration.name.lexeme));
+        for method in &declaration.methods {
+            self.visit_function(ast, stmt, method);
+        }
+        self.exit_scope();
+    }
+
+    fn visit_function(&mut self, ast: &Ast, _stmt: StmtId, declaration: &FunctionDecl) {
+        // the name belongs to the scope the function is declared in,
+        // the parameters to the function's own scope
+        self.count(&declaration.name);
+        self.enter_scope(format!("fun {}", declaration.name.lexeme));
+        for param in &declaration.params {
+            self.count(param);
+        }
+        walk_function(self, ast, declaration);
+        self.exit_scope();
+    }
+
+    fn visit_var(&mut self, ast: &Ast, _stmt: StmtId, name: &Token, initializer: Option<ExprId>) {
+        self.count(name);
+        if let Some(initializer) = initializer {
+            self.visit_expr(ast, initializer);
+        }
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _expr: ExprId, name: &Token, value: ExprId) {
+        self.count(name);
+        self.visit_expr(ast, value);
+    }
+
+    fn visit_variable(&mut self, _ast: &Ast, _expr: ExprId, name: &Token) {
+        self.count(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Expr;
+    use crate::stmt::Stmt;
+    use token::span::Span;
+    use token::token::Literal;
+    use token::token_type::TokenType;
+
+    fn token(token_type: TokenType, lexeme: &str) -> Token {
+        Token::new(token_type, lexeme.to_string(), Literal::NoLiteral, 1, Span::default())
+    }
+
+    fn expr(ast: &mut Ast, expr: Expr) -> ExprId {
+        ast.add_expr(expr, Span::default())
+    }
+
+    fn stmt(ast: &mut Ast, stmt: Stmt) -> StmtId {
+        ast.add_stmt(stmt, Span::default())
+    }
+
+    fn variable(ast: &mut Ast, name: &str) -> ExprId {
+        expr(ast, Expr::Variable { name: token(TokenType::Identifier, name) })
+    }
+
+    fn plus(ast: &mut Ast, left: ExprId, right: ExprId) -> ExprId {
+        expr(ast, Expr::Binary { left, operator: token(TokenType::Plus, "+"), right })
+    }
+
+    fn counts(pairs: &[(&str, usize)]) -> BTreeMap<String, usize> {
+        pairs.iter().map(|(name, count)| (name.to_string(), *count)).collect()
+    }
+
+    #[test]
+    fn test_counts_each_scope_separately() {
+        let mut ast = Ast::new();
+
+        // var a ← 1;
+        let one = expr(&mut ast, Expr::Literal { value: Literal::Number(1.0) });
+        let declare_a = stmt(&mut ast, Stmt::Var { name: token(TokenType::Identifier, "a"), initializer: Some(one) });
+
+        // fun f(b) { print a + b; }
+        let (a, b) = (variable(&mut ast, "a"), variable(&mut ast, "b"));
+        let sum = plus(&mut ast, a, b);
+        let print = stmt(&mut ast, Stmt::Print { keyword: token(TokenType::Print, "print"), expression: sum });
+        let declare_f = stmt(&mut ast, Stmt::Function(FunctionDecl {
+            name: token(TokenType::Identifier, "f"),
+            params: vec![token(TokenType::Identifier, "b")],
+            body: vec![print],
+        }));
+
+        // { var c ← a; c ← c + 1; }
+        let a = variable(&mut ast, "a");
+        let declare_c = stmt(&mut ast, Stmt::Var { name: token(TokenType::Identifier, "c"), initializer: Some(a) });
+        let (c, one) = (variable(&mut ast, "c"), expr(&mut ast, Expr::Literal { value: Literal::Number(1.0) }));
+        let sum = plus(&mut ast, c, one);
+        let assign = expr(&mut ast, Expr::Assign { name: token(TokenType::Identifier, "c"), value: sum });
+        let assign = stmt(&mut ast, Stmt::Expression { expression: assign });
+        let block = stmt(&mut ast, Stmt::Block { statements: vec![declare_c, assign] });
+
+        ast.statements = vec![declare_a, declare_f, block];
+
+        assert_eq!(count_identifiers(&ast), vec![
+            ScopeCount { scope: String::from("global"), depth: 0, identifiers: counts(&[("a", 1), ("f", 1)]) },
+            ScopeCount { scope: String::from("fun f"), depth: 1, identifiers: counts(&[("a", 1), ("b", 2)]) },
+            ScopeCount { scope: String::from("block"), depth: 1, identifiers: counts(&[("a", 1), ("c", 3)]) },
+        ]);
+    }
+}
@@ -0,0 +1,21 @@
+//! Contains the abstract syntax tree that the parser produces.
+//!
+//! - `arena` has the `Ast` that all the nodes of a program live in,
+//!   and the `ExprId`s and `StmtId`s that point at them.
+//! - `expr` has the `Expr` enum for expressions.
+//! - `stmt` has the `Stmt` enum for statements.
+//! - `sexpr_printer`, `rpn_printer` and `source_printer` print
+//!   the tree back out, which is handy for debugging the parser.
+//! - `visitor` and `folder` have the traits that passes over the
+//!   tree are built on, and `identifier_counter` is an example.
+
+
+pub mod arena;
+pub mod expr;
+pub mod stmt;
+pub mod sexpr_printer;
+pub mod rpn_printer;
+pub mod source_printer;
+pub mod visitor;
+pub mod folder;
+pub mod identifier_counter;
@@ -0,0 +1,126 @@
+//! Contains the reverse Polish notation printer.
+//!
+//! Operands come before their operator and groupings
+//! disappear, so `(1 + 2) * (4 - 3)` prints as
+//! `1 2 + 4 3 - *`.  This is the order a stack machine
+//! would evaluate things in.
+
+use crate::arena::{Ast, ExprId, StmtId};
+use crate::expr::Expr;
+use crate::sexpr_printer::print_literal;
+use crate::stmt::{FunctionDecl, Stmt};
+
+
+/// Print a whole program, one top-level statement per line.
+pub fn print_program(ast: &Ast) -> String {
+    ast.statements.iter().map(|statement| print_stmt(ast, *statement)).collect::<Vec<String>>().join("\n")
+}
+
+/// Print a statement with its keyword after its operands.
+///
+/// Statements that contain other statements keep them in braces,
+/// since control flow doesn't have a natural postfix form.
+pub fn print_stmt(ast: &Ast, stmt: StmtId) -> String {
+    match ast.stmt(stmt) {
+        Stmt::Block { statements } => print_block(ast, statements),
+        Stmt::Class(declaration) => {
+            let methods = declaration.methods.iter().map(|method| print_function(ast, method)).collect::<Vec<String>>();
+            let superclass = match declaration.superclass {
+                Some(superclass) => format!(" < {}", print_expr(ast, superclass)),
+                None => String::new(),
+            };
+            format!("class {}{} {{ {} }}", declaration.name.lexeme, superclass, methods.join("; "))
+        }
+        Stmt::Expression { expression } => print_expr(ast, *expression),
+        Stmt::Function(declaration) => print_function(ast, declaration),
+        Stmt::If { condition, then_branch, else_branch } => match else_branch {
+            Some(else_branch) => format!(
+                "{} if {} else {}",
+                print_expr(ast, *condition),
+                print_stmt(ast, *then_branch),
+                print_stmt(ast, *else_branch),
+            ),
+            None => format!("{} if {}", print_expr(ast, *condition), print_stmt(ast, *then_branch)),
+        },
+        Stmt::Print { expression, .. } => format!("{} print", print_expr(ast, *expression)),
+        Stmt::Return { value, .. } => match value {
+            Some(value) => format!("{} return", print_expr(ast, *value)),
+            None => String::from("return"),
+        },
+        Stmt::Var { name, initializer } => match initializer {
+            Some(initializer) => format!("{} var {}", print_expr(ast, *initializer), name.lexeme),
+            None => format!("var {}", name.lexeme),
+        },
+        Stmt::While { condition, body } => format!("{} while {}", print_expr(ast, *condition), print_stmt(ast, *body)),
+    }
+}
+
+fn print_function(ast: &Ast, declaration: &FunctionDecl) -> String {
+    let params = declaration.params.iter().map(|param| param.lexeme.as_str()).collect::<Vec<&str>>().join(", ");
+    format!("fun {}({}) {}", declaration.name.lexeme, params, print_block(ast, &declaration.body))
+}
+
+fn print_block(ast: &Ast, statements: &[StmtId]) -> String {
+    format!(
+        "{{ {} }}",
+        statements.iter().map(|statement| print_stmt(ast, *statement)).collect::<Vec<String>>().join("; "),
+    )
+}
+
+/// Print an expression in reverse Polish notation.
+///
+/// Calls print the callee and the arguments, then `call/N`
+/// where N is the number of arguments.
+pub fn print_expr(ast: &Ast, expr: ExprId) -> String {
+    match ast.expr(expr) {
+        Expr::Assign { name, value } => format!("{} {} ←", print_expr(ast, *value), name.lexeme),
+        Expr::Binary { left, operator, right } | Expr::Logical { left, operator, right } => {
+            format!("{} {} {}", print_expr(ast, *left), print_expr(ast, *right), operator.lexeme)
+        }
+        Expr::Call { callee, arguments, .. } => {
+            let mut parts = vec![print_expr(ast, *callee)];
+            parts.extend(arguments.iter().map(|argument| print_expr(ast, *argument)));
+            parts.push(format!("call/{}", arguments.len()));
+            parts.join(" ")
+        }
+        Expr::Get { object, name } => format!("{} .{}", print_expr(ast, *object), name.lexeme),
+        Expr::Grouping { expression } => print_expr(ast, *expression),
+        Expr::Literal { value } => print_literal(value),
+        Expr::Set { object, name, value } => {
+            format!("{} {} .{} ←", print_expr(ast, *value), print_expr(ast, *object), name.lexeme)
+        }
+        Expr::Super { method, .. } => format!("super .{}", method.lexeme),
+        Expr::This { .. } => String::from("this"),
+        Expr::Unary { operator, right } => format!("{} {}", print_expr(ast, *right), operator.lexeme),
+        Expr::Variable { name } => name.lexeme.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::span::Span;
+    use token::token::{Literal, Token};
+    use token::token_type::TokenType;
+
+    fn token(token_type: TokenType, lexeme: &str) -> Token {
+        Token::new(token_type, lexeme.to_string(), Literal::NoLiteral, 1, Span::default())
+    }
+
+    fn binary(ast: &mut Ast, left: f64, operator: Token, right: f64) -> ExprId {
+        let left = ast.add_expr(Expr::Literal { value: Literal::Number(left) }, Span::default());
+        let right = ast.add_expr(Expr::Literal { value: Literal::Number(right) }, Span::default());
+        let expression = ast.add_expr(Expr::Binary { left, operator, right }, Span::default());
+        ast.add_expr(Expr::Grouping { expression }, Span::default())
+    }
+
+    #[test]
+    fn test_print_expr() {
+        let mut ast = Ast::new();
+        let left = binary(&mut ast, 1.0, token(TokenType::Plus, "+"), 2.0);
+        let right = binary(&mut ast, 4.0, token(TokenType::Minus, "-"), 3.0);
+        let expr = ast.add_expr(Expr::Binary { left, operator: token(TokenType::Star, "*"), right }, Span::default());
+
+        assert_eq!(print_expr(&ast, expr), "1 2 + 4 3 - *");
+    }
+}
@@ -0,0 +1,130 @@
+//! Contains the S-expression printer.
+//!
+//! Every node is printed fully parenthesized with its operator
+//! first, so `⁻123 * (45.67)` prints as
+//! `(* (⁻ 123) (group 45.67))`.  This makes precedence and
+//! associativity easy to see.
+
+use token::token::Literal;
+
+use crate::arena::{Ast, ExprId, StmtId};
+use crate::expr::Expr;
+use crate::stmt::{ClassDecl, FunctionDecl, Stmt};
+
+
+/// Print a whole program, one top-level statement per line.
+pub fn print_program(ast: &Ast) -> String {
+    ast.statements.iter().map(|statement| print_stmt(ast, *statement)).collect::<Vec<String>>().join("\n")
+}
+
+/// Print a statement as an S-expression.
+pub fn print_stmt(ast: &Ast, stmt: StmtId) -> String {
+    match ast.stmt(stmt) {
+        Stmt::Block { statements } => parenthesize_stmts(ast, "block", statements),
+        Stmt::Class(declaration) => print_class(ast, declaration),
+        Stmt::Expression { expression } => format!("(; {})", print_expr(ast, *expression)),
+        Stmt::Function(declaration) => print_function(ast, declaration),
+        Stmt::If { condition, then_branch, else_branch } => match else_branch {
+            Some(else_branch) => format!(
+                "(if {} {} {})",
+                print_expr(ast, *condition),
+                print_stmt(ast, *then_branch),
+                print_stmt(ast, *else_branch),
+            ),
+            None => format!("(if {} {})", print_expr(ast, *condition), print_stmt(ast, *then_branch)),
+        },
+        Stmt::Print { expression, .. } => format!("(print {})", print_expr(ast, *expression)),
+        Stmt::Return { value, .. } => match value {
+            Some(value) => format!("(return {})", print_expr(ast, *value)),
+            None => String::from("(return)"),
+        },
+        Stmt::Var { name, initializer } => match initializer {
+            Some(initializer) => format!("(var {} {})", name.lexeme, print_expr(ast, *initializer)),
+            None => format!("(var {})", name.lexeme),
+        },
+        Stmt::While { condition, body } => format!("(while {} {})", print_expr(ast, *condition), print_stmt(ast, *body)),
+    }
+}
+
+fn print_class(ast: &Ast, declaration: &ClassDecl) -> String {
+    let mut parts = vec![format!("class {}", declaration.name.lexeme)];
+    if let Some(superclass) = declaration.superclass {
+        parts.push(format!("< {}", print_expr(ast, superclass)));
+    }
+    parts.extend(declaration.methods.iter().map(|method| print_function(ast, method)));
+    format!("({})", parts.join(" "))
+}
+
+fn print_function(ast: &Ast, declaration: &FunctionDecl) -> String {
+    let params = declaration.params.iter().map(|param| param.lexeme.as_str()).collect::<Vec<&str>>().join(" ");
+    let mut parts = vec![format!("fun {} ({})", declaration.name.lexeme, params)];
+    parts.extend(declaration.body.iter().map(|statement| print_stmt(ast, *statement)));
+    format!("({})", parts.join(" "))
+}
+
+fn parenthesize_stmts(ast: &Ast, name: &str, statements: &[StmtId]) -> String {
+    let mut parts = vec![name.to_string()];
+    parts.extend(statements.iter().map(|statement| print_stmt(ast, *statement)));
+    format!("({})", parts.join(" "))
+}
+
+/// Print an expression as an S-expression.
+pub fn print_expr(ast: &Ast, expr: ExprId) -> String {
+    match ast.expr(expr) {
+        Expr::Assign { name, value } => format!("(← {} {})", name.lexeme, print_expr(ast, *value)),
+        Expr::Binary { left, operator, right } | Expr::Logical { left, operator, right } => {
+            format!("({} {} {})", operator.lexeme, print_expr(ast, *left), print_expr(ast, *right))
+        }
+        Expr::Call { callee, arguments, .. } => {
+            let mut parts = vec![String::from("call"), print_expr(ast, *callee)];
+            parts.extend(arguments.iter().map(|argument| print_expr(ast, *argument)));
+            format!("({})", parts.join(" "))
+        }
+        Expr::Get { object, name } => format!("(. {} {})", print_expr(ast, *object), name.lexeme),
+        Expr::Grouping { expression } => format!("(group {})", print_expr(ast, *expression)),
+        Expr::Literal { value } => print_literal(value),
+        Expr::Set { object, name, value } => {
+            format!("(← (. {} {}) {})", print_expr(ast, *object), name.lexeme, print_expr(ast, *value))
+        }
+        Expr::Super { method, .. } => format!("(super {})", method.lexeme),
+        Expr::This { .. } => String::from("this"),
+        Expr::Unary { operator, right } => format!("({} {})", operator.lexeme, print_expr(ast, *right)),
+        Expr::Variable { name } => name.lexeme.clone(),
+    }
+}
+
+/// Print a literal the way it would be written in the source.
+///
+/// Shared by all the printers.
+pub fn print_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Number(number) => number.to_string(),
+        Literal::String(string) => format!("«{}»", string),
+        Literal::Bool(boolean) => boolean.to_string(),
+        Literal::Nil | Literal::NoLiteral => String::from("nil"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::span::Span;
+    use token::token::Token;
+    use token::token_type::TokenType;
+
+    fn token(token_type: TokenType, lexeme: &str) -> Token {
+        Token::new(token_type, lexeme.to_string(), Literal::NoLiteral, 1, Span::default())
+    }
+
+    #[test]
+    fn test_print_expr() {
+        let mut ast = Ast::new();
+        let number = ast.add_expr(Expr::Literal { value: Literal::Number(123.0) }, Span::default());
+        let left = ast.add_expr(Expr::Unary { operator: token(TokenType::Negative, "⁻"), right: number }, Span::default());
+        let number = ast.add_expr(Expr::Literal { value: Literal::Number(45.67) }, Span::default());
+        let right = ast.add_expr(Expr::Grouping { expression: number }, Span::default());
+        let expr = ast.add_expr(Expr::Binary { left, operator: token(TokenType::Star, "*"), right }, Span::default());
+
+        assert_eq!(print_expr(&ast, expr), "(* (⁻ 123) (group 45.67))");
+    }
+}
@@ -0,0 +1,139 @@
+//! Contains the source printer.
+//!
+//! This prints the tree back out as Lox code in one
+//! canonical layout: four-space indents, one statement
+//! per line, spaces around binary operators and the
+//! Unicode glyphs for the operators (`←`, `≟`, `≠`, `≤`,
+//! `≥`, `¬`, `⁻`).
+//!
+//! `for` loops come back out as the `while` loops the
+//! parser desugared them into.
+
+use crate::arena::{Ast, ExprId, StmtId};
+use crate::expr::Expr;
+use crate::sexpr_printer::print_literal;
+use crate::stmt::{FunctionDecl, Stmt};
+
+
+const INDENT: &str = "    ";
+
+
+/// Print a whole program as Lox source.
+pub fn print_program(ast: &Ast) -> String {
+    let mut lines = Vec::new();
+    for statement in &ast.statements {
+        print_stmt(ast, *statement, 0, &mut lines);
+    }
+    lines.join("\n")
+}
+
+/// Print a statement at `depth` levels of indentation,
+/// pushing the lines onto `lines`.
+pub fn print_stmt(ast: &Ast, stmt: StmtId, depth: usize, lines: &mut Vec<String>) {
+    let indent = INDENT.repeat(depth);
+    match ast.stmt(stmt) {
+        Stmt::Block { statements } => {
+            lines.push(format!("{}{{", indent));
+            for statement in statements {
+                print_stmt(ast, *statement, depth + 1, lines);
+            }
+            lines.push(format!("{}}}", indent));
+        }
+        Stmt::Class(declaration) => {
+            let superclass = match declaration.superclass {
+                Some(superclass) => format!(" < {}", print_expr(ast, superclass)),
+                None => String::new(),
+            };
+            lines.push(format!("{}class {}{} {{", indent, declaration.name.lexeme, superclass));
+            for method in &declaration.methods {
+                print_function(ast, "", method, depth + 1, lines);
+            }
+            lines.push(format!("{}}}", indent));
+        }
+        Stmt::Expression { expression } => lines.push(format!("{}{};", indent, print_expr(ast, *expression))),
+        Stmt::Function(declaration) => print_function(ast, "fun ", declaration, depth, lines),
+        Stmt::If { condition, then_branch, else_branch } => {
+            print_with_body(ast, format!("if ({})", print_expr(ast, *condition)), *then_branch, depth, lines);
+            if let Some(else_branch) = else_branch {
+                // hug the closing brace of the then branch: `} else {`
+                let header = match ast.stmt(*then_branch) {
+                    Stmt::Block { .. } => {
+                        lines.pop();
+                        String::from("} else")
+                    }
+                    _ => String::from("else"),
+                };
+                print_with_body(ast, header, *else_branch, depth, lines);
+            }
+        }
+        Stmt::Print { expression, .. } => lines.push(format!("{}print {};", indent, print_expr(ast, *expression))),
+        Stmt::Return { value, .. } => match value {
+            Some(value) => lines.push(format!("{}return {};", indent, print_expr(ast, *value))),
+            None => lines.push(format!("{}return;", indent)),
+        },
+        Stmt::Var { name, initializer } => match initializer {
+            Some(initializer) => lines.push(format!("{}var {} ← {};", indent, name.lexeme, print_expr(ast, *initializer))),
+            None => lines.push(format!("{}var {};", indent, name.lexeme)),
+        },
+        Stmt::While { condition, body } => {
+            print_with_body(ast, format!("while ({})", print_expr(ast, *condition)), *body, depth, lines);
+        }
+    }
+}
+
+/// Print a function, or a method if `keyword` is empty.
+fn print_function(ast: &Ast, keyword: &str, declaration: &FunctionDecl, depth: usize, lines: &mut Vec<String>) {
+    let indent = INDENT.repeat(depth);
+    let params = declaration.params.iter().map(|param| param.lexeme.as_str()).collect::<Vec<&str>>().join(", ");
+    lines.push(format!("{}{}{}({}) {{", indent, keyword, declaration.name.lexeme, params));
+    for statement in &declaration.body {
+        print_stmt(ast, *statement, depth + 1, lines);
+    }
+    lines.push(format!("{}}}", indent));
+}
+
+/// Print `header` followed by the body of an `if`, `else` or `while`.
+///
+/// A block body opens its brace on the header line, anything
+/// else goes on the next line, indented one more level.
+fn print_with_body(ast: &Ast, header: String, body: StmtId, depth: usize, lines: &mut Vec<String>) {
+    let indent = INDENT.repeat(depth);
+    match ast.stmt(body) {
+        Stmt::Block { statements } => {
+            lines.push(format!("{}{} {{", indent, header));
+            for statement in statements {
+                print_stmt(ast, *statement, depth + 1, lines);
+            }
+            lines.push(format!("{}}}", indent));
+        }
+        _ => {
+            lines.push(format!("{}{}", indent, header));
+            print_stmt(ast, body, depth + 1, lines);
+        }
+    }
+}
+
+/// Print an expression as Lox source.
+pub fn print_expr(ast: &Ast, expr: ExprId) -> String {
+    match ast.expr(expr) {
+        Expr::Assign { name, value } => format!("{} ← {}", name.lexeme, print_expr(ast, *value)),
+        Expr::Binary { left, operator, right } | Expr::Logical { left, operator, right } => {
+            format!("{} {} {}", print_expr(ast, *left), operator.lexeme, print_expr(ast, *right))
+        }
+        Expr::Call { callee, arguments, .. } => format!(
+            "{}({})",
+            print_expr(ast, *callee),
+            arguments.iter().map(|argument| print_expr(ast, *argument)).collect::<Vec<String>>().join(", "),
+        ),
+        Expr::Get { object, name } => format!("{}.{}", print_expr(ast, *object), name.lexeme),
+        Expr::Grouping { expression } => format!("({})", print_expr(ast, *expression)),
+        Expr::Literal { value } => print_literal(value),
+        Expr::Set { object, name, value } => {
+            format!("{}.{} ← {}", print_expr(ast, *object), name.lexeme, print_expr(ast, *value))
+        }
+        Expr::Super { method, .. } => format!("super.{}", method.lexeme),
+        Expr::This { .. } => String::from("this"),
+        Expr::Unary { operator, right } => format!("{}{}", operator.lexeme, print_expr(ast, *right)),
+        Expr::Variable { name } => name.lexeme.clone(),
+    }
+}
@@ -0,0 +1,98 @@
+//! Contains the `Stmt` enum.
+
+use token::token::Token;
+
+use crate::arena::{ExprId, StmtId};
+
+
+/// A statement node in the syntax tree.
+///
+/// Children are `ExprId`s and `StmtId`s into the `Ast` the
+/// node lives in, and a whole program is the `Ast`'s list of
+/// top-level `statements`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    /// `{ statements }`
+    Block {
+        statements: Vec<StmtId>,
+    },
+
+    /// `class name < superclass { methods }`
+    Class(ClassDecl),
+
+    /// An expression followed by a `;`, run for its side effects.
+    Expression {
+        expression: ExprId,
+    },
+
+    /// `fun name(params) { body }`
+    Function(FunctionDecl),
+
+    /// `if (condition) then_branch else else_branch`
+    If {
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    },
+
+    /// `print expression;`
+    ///
+    /// `keyword` is the `print` token itself, kept for error reporting.
+    Print {
+        keyword: Token,
+        expression: ExprId,
+    },
+
+    /// `return value;`
+    ///
+    /// `keyword` is the `return` token itself, kept for error reporting.
+    Return {
+        keyword: Token,
+        value: Option<ExprId>,
+    },
+
+    /// `var name ← initializer;`
+    Var {
+        name: Token,
+        initializer: Option<ExprId>,
+    },
+
+    /// `while (condition) body`
+    ///
+    /// `for` loops don't have their own node -- the parser
+    /// desugars them into a `while` inside a block.
+    While {
+        condition: ExprId,
+        body: StmtId,
+    },
+}
+
+
+/// A function declaration.
+///
+/// This is its own struct (rather than fields on `Stmt::Function`)
+/// so that it can be handed around on its own, for example to
+/// build a function object at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<StmtId>,
+}
+
+
+/// A class declaration.
+///
+/// The methods are written like functions, just without `fun`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDecl {
+    pub name: Token,
+
+    /// The class after the `<`, if there is one.  This is always
+    /// an `Expr::Variable`, but keeping it as an expression lets
+    /// the resolver and the interpreter look it up like any other
+    /// variable.
+    pub superclass: Option<ExprId>,
+
+    pub methods: Vec<FunctionDecl>,
+}
@@ -0,0 +1,298 @@
+//! Contains the `Visitor` trait.
+//!
+//! A visitor walks the tree without changing it. There is one
+//! `visit_*` method per kind of node, and each one has a default
+//! that just walks into the node's children, so a pass only has
+//! to override the methods for the nodes it cares about.
+//!
+//! An override that still wants the children walked can call
+//! the matching `walk_*` function in this module, which is what
+//! the default does.
+//!
+//! Every method gets the `Ast` and the ID of the node, so that
+//! passes can record what they find in a `SideTable`.
+
+use token::token::{Literal, Token};
+
+use crate::arena::{Ast, ExprId, StmtId};
+use crate::expr::Expr;
+use crate::stmt::{ClassDecl, FunctionDecl, Stmt};
+
+
+/// A pass that reads the tree and produces an `R` per node.
+///
+/// The defaults throw away whatever the children return and give
+/// back `R::default()`, which is why `R` has to be `Default`.
+/// Passes that combine the results of the children should
+/// override the methods where that matters.
+pub trait Visitor<R: Default = ()> {
+    fn visit_stmt(&mut self, ast: &Ast, stmt: StmtId) -> R {
+        walk_stmt(self, ast, stmt)
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expr: ExprId) -> R {
+        walk_expr(self, ast, expr)
+    }
+
+    fn visit_block(&mut self, ast: &Ast, _stmt: StmtId, statements: &[StmtId]) -> R {
+        walk_block(self, ast, statements)
+    }
+
+    fn visit_class(&mut self, ast: &Ast, stmt: StmtId, declaration: &ClassDecl) -> R {
+        walk_class(self, ast, stmt, declaration)
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, _stmt: StmtId, expression: ExprId) -> R {
+        self.visit_expr(ast, expression)
+    }
+
+    fn visit_function(&mut self, ast: &Ast, _stmt: StmtId, declaration: &FunctionDecl) -> R {
+        walk_function(self, ast, declaration)
+    }
+
+    fn visit_if(
+        &mut self,
+        ast: &Ast,
+        _stmt: StmtId,
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    ) -> R {
+        self.visit_expr(ast, condition);
+        self.visit_stmt(ast, then_branch);
+        if let Some(else_branch) = else_branch {
+            self.visit_stmt(ast, else_branch);
+        }
+        R::default()
+    }
+
+    fn visit_print(&mut self, ast: &Ast, _stmt: StmtId, expression: ExprId) -> R {
+        self.visit_expr(ast, expression)
+    }
+
+    fn visit_return(&mut self, ast: &Ast, _stmt: StmtId, _keyword: &Token, value: Option<ExprId>) -> R {
+        match value {
+            Some(value) => self.visit_expr(ast, value),
+            None => R::default(),
+        }
+    }
+
+    fn visit_var(&mut self, ast: &Ast, _stmt: StmtId, _name: &Token, initializer: Option<ExprId>) -> R {
+        match initializer {
+            Some(initializer) => self.visit_expr(ast, initializer),
+            None => R::default(),
+        }
+    }
+
+    fn visit_while(&mut self, ast: &Ast, _stmt: StmtId, condition: ExprId, body: StmtId) -> R {
+        self.visit_expr(ast, condition);
+        self.visit_stmt(ast, body)
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _expr: ExprId, _name: &Token, value: ExprId) -> R {
+        self.visit_expr(ast, value)
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _expr: ExprId, left: ExprId, _operator: &Token, right: ExprId) -> R {
+        self.visit_expr(ast, left);
+        self.visit_expr(ast, right)
+    }
+
+    fn visit_call(&mut self, ast: &Ast, _expr: ExprId, callee: ExprId, _paren: &Token, arguments: &[ExprId]) -> R {
+        self.visit_expr(ast, callee);
+        for argument in arguments {
+            self.visit_expr(ast, *argument);
+        }
+        R::default()
+    }
+
+    fn visit_get(&mut self, ast: &Ast, _expr: ExprId, object: ExprId, _name: &Token) -> R {
+        self.visit_expr(ast, object)
+    }
+
+    fn visit_grouping(&mut self, ast: &Ast, _expr: ExprId, expression: ExprId) -> R {
+        self.visit_expr(ast, expression)
+    }
+
+    fn visit_literal(&mut self, _ast: &Ast, _expr: ExprId, _value: &Literal) -> R {
+        R::default()
+    }
+
+    fn visit_logical(&mut self, ast: &Ast, _expr: ExprId, left: ExprId, _operator: &Token, right: ExprId) -> R {
+        self.visit_expr(ast, left);
+        self.visit_expr(ast, right)
+    }
+
+    fn visit_set(&mut self, ast: &Ast, _expr: ExprId, object: ExprId, _name: &Token, value: ExprId) -> R {
+        self.visit_expr(ast, object);
+        self.visit_expr(ast, value)
+    }
+
+    fn visit_super(&mut self, _ast: &Ast, _expr: ExprId, _keyword: &Token, _method: &Token) -> R {
+        R::default()
+    }
+
+    fn visit_this(&mut self, _ast: &Ast, _expr: ExprId, _keyword: &Token) -> R {
+        R::default()
+    }
+
+    fn visit_unary(&mut self, ast: &Ast, _expr: ExprId, _operator: &Token, right: ExprId) -> R {
+        self.visit_expr(ast, right)
+    }
+
+    fn visit_variable(&mut self, _ast: &Ast, _expr: ExprId, _name: &Token) -> R {
+        R::default()
+    }
+}
+
+
+/// Visit every top-level statement of a program.
+pub fn walk_program<R: Default, V: Visitor<R> + ?Sized>(visitor: &mut V, ast: &Ast) -> R {
+    walk_block(visitor, ast, &ast.statements)
+}
+
+/// Send `stmt` to the `visit_*` method for its kind.
+pub fn walk_stmt<R: Default, V: Visitor<R> + ?Sized>(visitor: &mut V, ast: &Ast, stmt: StmtId) -> R {
+    match ast.stmt(stmt) {
+        Stmt::Block { statements } => visitor.visit_block(ast, stmt, statements),
+        Stmt::Class(declaration) => visitor.visit_class(ast, stmt, declaration),
+        Stmt::Expression { expression } => visitor.visit_expression_stmt(ast, stmt, *expression),
+        Stmt::Function(declaration) => visitor.visit_function(ast, stmt, declaration),
+        Stmt::If { condition, then_branch, else_branch } => {
+            visitor.visit_if(ast, stmt, *condition, *then_branch, *else_branch)
+        }
+        Stmt::Print { expression, .. } => visitor.visit_print(ast, stmt, *expression),
+        Stmt::Return { keyword, value } => visitor.visit_return(ast, stmt, keyword, *value),
+        Stmt::Var { name, initializer } => visitor.visit_var(ast, stmt, name, *initializer),
+        Stmt::While { condition, body } => visitor.visit_while(ast, stmt, *condition, *body),
+    }
+}
+
+/// Send `expr` to the `visit_*` method for its kind.
+pub fn walk_expr<R: Default, V: Visitor<R> + ?Sized>(visitor: &mut V, ast: &Ast, expr: ExprId) -> R {
+    match ast.expr(expr) {
+        Expr::Assign { name, value } => visitor.visit_assign(ast, expr, name, *value),
+        Expr::Binary { left, operator, right } => visitor.visit_binary(ast, expr, *left, operator, *right),
+        Expr::Call { callee, paren, arguments } => visitor.visit_call(ast, expr, *callee, paren, arguments),
+        Expr::Get { object, name } => visitor.visit_get(ast, expr, *object, name),
+        Expr::Grouping { expression } => visitor.visit_grouping(ast, expr, *expression),
+        Expr::Literal { value } => visitor.visit_literal(ast, expr, value),
+        Expr::Set { object, name, value } => visitor.visit_set(ast, expr, *object, name, *value),
+        Expr::Super { keyword, method } => visitor.visit_super(ast, expr, keyword, method),
+        Expr::This { keyword } => visitor.visit_this(ast, expr, keyword),
+        Expr::Logical { left, operator, right } => visitor.visit_logical(ast, expr, *left, operator, *right),
+        Expr::Unary { operator, right } => visitor.visit_unary(ast, expr, operator, *right),
+        Expr::Variable { name } => visitor.visit_variable(ast, expr, name),
+    }
+}
+
+/// Visit each statement in a block.
+pub fn walk_block<R: Default, V: Visitor<R> + ?Sized>(visitor: &mut V, ast: &Ast, statements: &[StmtId]) -> R {
+    for statement in statements {
+        visitor.visit_stmt(ast, *statement);
+    }
+    R::default()
+}
+
+/// Visit each statement in a function's body.
+///
+/// The name and the parameters are tokens, not nodes, so
+/// there is nothing to visit for them.
+pub fn walk_function<R: Default, V: Visitor<R> + ?Sized>(visitor: &mut V, ast: &Ast, declaration: &FunctionDecl) -> R {
+    walk_block(visitor, ast, &declaration.body)
+}
+
+/// Visit the superclass and each method of a class.
+///
+/// Methods aren't statements of their own, so each one is visited
+/// as a function with the ID of the class statement `stmt`.
+pub fn walk_class<R: Default, V: Visitor<R> + ?Sized>(
+    visitor: &mut V,
+    ast: &Ast,
+    stmt: StmtId,
+    declaration: &ClassDecl,
+) -> R {
+    if let Some(superclass) = declaration.superclass {
+        visitor.visit_expr(ast, superclass);
+    }
+    for method in &declaration.methods {
+        visitor.visit_function(ast, stmt, method);
+    }
+    R::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::span::Span;
+    use token::token_type::TokenType;
+
+    fn token(token_type: TokenType, lexeme: &str) -> Token {
+        Token::new(token_type, lexeme.to_string(), Literal::NoLiteral, 1, Span::default())
+    }
+
+    fn function(name: &str, body: Vec<StmtId>) -> FunctionDecl {
+        FunctionDecl { name: token(TokenType::Identifier, name), params: Vec::new(), body }
+    }
+
+    /// Counts the functions it's shown, leaving the walking to the
+    /// defaults.
+    #[derive(Default)]
+    struct FunctionCounter {
+        functions: usize,
+    }
+
+    impl Visitor for FunctionCounter {
+        fn visit_function(&mut self, ast: &Ast, _stmt: StmtId, declaration: &FunctionDecl) {
+            self.functions += 1;
+            walk_function(self, ast, declaration);
+        }
+    }
+
+    #[test]
+    fn test_methods_are_visited_as_functions() {
+        let mut ast = Ast::new();
+
+        // class A { get() { fun helper() {} } init() {} }  fun f() {}
+        let helper = ast.add_stmt(Stmt::Function(function("helper", Vec::new())), Span::default());
+        let class = ast.add_stmt(Stmt::Class(ClassDecl {
+            name: token(TokenType::Identifier, "A"),
+            superclass: None,
+            methods: vec![function("get", vec![helper]), function("init", Vec::new())],
+        }), Span::default());
+        let f = ast.add_stmt(Stmt::Function(function("f", Vec::new())), Span::default());
+        ast.statements = vec![class, f];
+
+        let mut counter = FunctionCounter::default();
+        walk_program(&mut counter, &ast);
+
+        assert_eq!(counter.functions, 4);
+    }
+
+    /// Writes down the variables it's shown, in order.
+    #[derive(Default)]
+    struct VariableRecorder {
+        names: Vec<String>,
+    }
+
+    impl Visitor for VariableRecorder {
+        fn visit_variable(&mut self, _ast: &Ast, _expr: ExprId, name: &Token) {
+            self.names.push(name.lexeme.clone());
+        }
+    }
+
+    #[test]
+    fn test_set_visits_object_before_value() {
+        let mut ast = Ast::new();
+
+        // a.field ← b
+        let a = ast.add_expr(Expr::Variable { name: token(TokenType::Identifier, "a") }, Span::default());
+        let b = ast.add_expr(Expr::Variable { name: token(TokenType::Identifier, "b") }, Span::default());
+        let set = ast.add_expr(Expr::Set { object: a, name: token(TokenType::Identifier, "field"), value: b }, Span::default());
+
+        let mut recorder = VariableRecorder::default();
+        recorder.visit_expr(&ast, set);
+
+        assert_eq!(recorder.names, vec!["a", "b"]);
+    }
+}
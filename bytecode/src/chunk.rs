@@ -0,0 +1,283 @@
+//! Contains the `Chunk` struct.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use token::span::Span;
+
+use crate::constant::Constant;
+use crate::function::Function;
+use crate::op_code::OpCode;
+
+
+/// The most constants a chunk can have, since `OP_CONSTANT_LONG`
+/// has three bytes for the index.
+pub const MAX_CONSTANTS: usize = 1 << 24;
+
+
+/// A run of bytes in a chunk's code that all came from one line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRun {
+    pub line: usize,
+    /// How many bytes in a row came from `line`.
+    pub count: usize,
+}
+
+
+/// A compiled piece of code: the instructions, the constants they
+/// use, and which line of the source each byte came from.
+///
+/// Lines are kept run-length encoded, since the instructions for
+/// one line are next to each other: a line's worth of bytes takes
+/// one entry instead of one per byte.
+///
+/// A chunk compiled from source also knows the part of it each
+/// instruction came from, so a runtime error can point at it.  A
+/// saved chunk goes without, since the source doesn't go with it.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    constants: Vec<Constant>,
+    lines: Vec<LineRun>,
+
+    /// The span each instruction came from, as the offset it starts
+    /// at and the span, in order.  See `mark_span`.
+    spans: Vec<(usize, Span)>,
+
+    /// Where each constant is in `constants`, so `add_constant`
+    /// finds one that's already there without a scan.  The pool
+    /// only changes through `add_constant` and `from_parts`, which
+    /// both keep this up to date.
+    index: HashMap<ConstantKey, usize>,
+}
+
+/// Two chunks are the same if their code, constants and lines are;
+/// the index is only a cache, and the spans don't survive saving.
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code && self.constants == other.constants && self.lines == other.lines
+    }
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        //! Create a new empty `Chunk`.
+        Chunk::default()
+    }
+
+    pub fn from_parts(code: Vec<u8>, constants: Vec<Constant>, lines: Vec<LineRun>) -> Self {
+        //! Put a `Chunk` back together from what `code`,
+        //! `constants` and `lines` give, as when loading a saved one.
+        let mut index = HashMap::new();
+        // the first of any duplicates wins, as with a scan from the start
+        for (slot, constant) in constants.iter().enumerate().rev() {
+            index.insert(ConstantKey::of(constant), slot);
+        }
+        Chunk { code, constants, lines, spans: Vec::new(), index }
+    }
+
+    /// Add a byte that came from `line`.
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        match self.lines.last_mut() {
+            Some(run) if run.line == line => run.count += 1,
+            _ => self.lines.push(LineRun { line, count: 1 }),
+        }
+    }
+
+    /// Add an instruction that came from `line`.
+    pub fn write_op(&mut self, op_code: OpCode, line: usize) {
+        self.write(op_code as u8, line);
+    }
+
+    /// Add `constant` to the constant pool and give back its index.
+    ///
+    /// A constant that's already there is reused, so a name used
+    /// all over a chunk only takes one slot.
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        let key = ConstantKey::of(&constant);
+        if let Some(&index) = self.index.get(&key) {
+            return index;
+        }
+        self.constants.push(constant);
+        self.index.insert(key, self.constants.len() - 1);
+        self.constants.len() - 1
+    }
+
+    /// The constant pool.
+    pub fn constants(&self) -> &[Constant] {
+        &self.constants
+    }
+
+    /// Add an instruction that pushes `constant`, using the long
+    /// form if the pool has grown past what one byte can index.
+    ///
+    /// Gives back `None` if the pool is completely full.
+    pub fn write_constant(&mut self, constant: Constant, line: usize) -> Option<usize> {
+        let index = self.add_constant(constant);
+        if let Ok(byte) = u8::try_from(index) {
+            self.write_op(OpCode::Constant, line);
+            self.write(byte, line);
+        } else if index < MAX_CONSTANTS {
+            self.write_op(OpCode::ConstantLong, line);
+            for byte in &(index as u32).to_be_bytes()[1..] {
+                self.write(*byte, line);
+            }
+        } else {
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Add `op_code` with the constant `index` as its first
+    /// operand.  An index past what one byte holds gets an
+    /// `OP_WIDE` in front with the rest of it.
+    ///
+    /// Gives back `None` if the index is past the end of a full
+    /// pool.
+    pub fn write_with_constant(&mut self, op_code: OpCode, index: usize, line: usize) -> Option<()> {
+        if index >= MAX_CONSTANTS {
+            return None;
+        }
+        let [_, high, middle, low] = (index as u32).to_be_bytes();
+        if index > u8::MAX as usize {
+            self.write_op(OpCode::Wide, line);
+            self.write(high, line);
+            self.write(middle, line);
+        }
+        self.write_op(op_code, line);
+        self.write(low, line);
+        Some(())
+    }
+
+    /// Say that the instruction about to be written came from
+    /// `span` of the source.  Its operands, and any instructions
+    /// after it that aren't marked, count as coming from there too.
+    pub fn mark_span(&mut self, span: Span) {
+        match self.spans.last() {
+            Some(&(_, last)) if last == span => {}
+            _ => self.spans.push((self.code.len(), span)),
+        }
+    }
+
+    /// The span the instruction with the byte at `offset` came
+    /// from, if the chunk knows.
+    pub fn span_at(&self, offset: usize) -> Option<Span> {
+        let marks = self.spans.partition_point(|&(start, _)| start <= offset);
+        marks.checked_sub(1).map(|mark| self.spans[mark].1)
+    }
+
+    /// The line the byte at `offset` came from.
+    pub fn line_at(&self, offset: usize) -> usize {
+        let mut start = 0;
+        for run in &self.lines {
+            start += run.count;
+            if offset < start {
+                return run.line;
+            }
+        }
+        self.lines.last().map_or(0, |run| run.line)
+    }
+
+    /// The run-length encoded line table.
+    pub fn lines(&self) -> &[LineRun] {
+        &self.lines
+    }
+
+    /// The two-byte operand at `offset`.
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    /// The three-byte operand at `offset`.
+    pub fn read_u24(&self, offset: usize) -> usize {
+        u32::from_be_bytes([0, self.code[offset], self.code[offset + 1], self.code[offset + 2]]) as usize
+    }
+}
+
+
+/// What a constant is looked up by in a chunk's index: two
+/// constants have the same key exactly when they can share a slot.
+/// Unlike `==`, that tells `0` and `-0` apart and treats `NaN` as
+/// itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    String(String),
+    Function(*const Function),
+}
+
+impl ConstantKey {
+    fn of(constant: &Constant) -> Self {
+        match constant {
+            Constant::Number(number) => ConstantKey::Number(number.to_bits()),
+            Constant::String(string) => ConstantKey::String(string.clone()),
+            Constant::Function(function) => ConstantKey::Function(Rc::as_ptr(function)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_cover_the_instructions_after_them() {
+        let mut chunk = Chunk::new();
+        assert_eq!(chunk.span_at(0), None);
+        chunk.mark_span(Span::new(4, 5));
+        chunk.write_constant(Constant::Number(1.0), 1);
+        chunk.write_op(OpCode::Negate, 1);
+        chunk.mark_span(Span::new(0, 1));
+        chunk.write_op(OpCode::Print, 1);
+
+        let spans: Vec<Option<Span>> = (0..chunk.code.len()).map(|offset| chunk.span_at(offset)).collect();
+        let (number, print) = (Some(Span::new(4, 5)), Some(Span::new(0, 1)));
+        assert_eq!(spans, vec![number, number, number, print]);
+    }
+
+    #[test]
+    fn test_lines_are_run_length_encoded() {
+        let mut chunk = Chunk::new();
+        for line in [1, 1, 1, 2, 4, 4] {
+            chunk.write_op(OpCode::Nil, line);
+        }
+
+        assert_eq!(chunk.lines().len(), 3);
+        let lines: Vec<usize> = (0..chunk.code.len()).map(|offset| chunk.line_at(offset)).collect();
+        assert_eq!(lines, vec![1, 1, 1, 2, 4, 4]);
+    }
+
+    #[test]
+    fn test_constants_past_256_use_the_long_form() {
+        let mut chunk = Chunk::new();
+        for number in 0..300 {
+            chunk.write_constant(Constant::Number(number as f64), 1);
+        }
+        chunk.write_constant(Constant::Number(7.0), 1);
+
+        assert_eq!(chunk.constants().len(), 300);
+        assert_eq!(chunk.code[..2], [OpCode::Constant as u8, 0]);
+        assert_eq!(chunk.code[2 * 256], OpCode::ConstantLong as u8);
+        assert_eq!(chunk.read_u24(2 * 256 + 1), 256);
+        assert_eq!(chunk.code[chunk.code.len() - 2..], [OpCode::Constant as u8, 7]);
+    }
+
+    #[test]
+    fn test_constants_are_shared() {
+        let mut chunk = Chunk::new();
+        assert_eq!(chunk.add_constant(Constant::Number(0.0)), 0);
+        assert_eq!(chunk.add_constant(Constant::Number(-0.0)), 1);
+        assert_eq!(chunk.add_constant(Constant::String(String::from("a"))), 2);
+        assert_eq!(chunk.add_constant(Constant::Number(0.0)), 0);
+        assert_eq!(chunk.add_constant(Constant::Number(f64::NAN)), 3);
+        assert_eq!(chunk.add_constant(Constant::Number(f64::NAN)), 3);
+
+        // as when the optimizer drops constants, or a file is loaded
+        let mut chunk = Chunk::from_parts(Vec::new(), vec![Constant::String(String::from("a")), Constant::Number(1.0)], Vec::new());
+        assert_eq!(chunk.add_constant(Constant::Number(1.0)), 1);
+        assert_eq!(chunk.add_constant(Constant::String(String::from("a"))), 0);
+        assert_eq!(chunk.add_constant(Constant::Number(2.0)), 2);
+    }
+}
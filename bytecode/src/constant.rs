@@ -0,0 +1,31 @@
+//! Contains the `Constant` enum.
+
+use std::rc::Rc;
+
+use crate::function::Function;
+
+
+/// A value known when the code is compiled, kept in a chunk's
+/// constant pool.
+///
+/// These are plain data rather than runtime values, so a chunk
+/// doesn't depend on any one VM and can be printed or saved by
+/// itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Number(f64),
+    String(String),
+    /// A function declared inside the chunk's code.
+    Function(Rc<Function>),
+}
+
+/// How the disassembler shows a constant.
+impl std::fmt::Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Number(number) => write!(f, "{}", number),
+            Constant::String(string) => write!(f, "{}", string),
+            Constant::Function(function) => write!(f, "{}", function),
+        }
+    }
+}
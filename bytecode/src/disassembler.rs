@@ -0,0 +1,276 @@
+//! Contains the `disassemble` function, which shows a chunk the
+//! way clox's disassembler does.
+//!
+//! ```text
+//! == <script> ==
+//! 0000    1 OP_CONSTANT         0 '1.2'
+//! 0002    | OP_NEGATE
+//! 0003    2 OP_JUMP_IF_FALSE    3 -> 12
+//! ```
+//!
+//! Each instruction gets its offset, the line it came from (or
+//! `|` if that's the same as the instruction before), its name and
+//! its operands.  Constants are shown after their index, and jumps
+//! with where they land.  An `OP_WIDE` is shown as part of the
+//! instruction it widens, with the whole constant index.
+
+use std::fmt::Write;
+
+use crate::chunk::Chunk;
+use crate::constant::Constant;
+use crate::function::Function;
+use crate::op_code::OpCode;
+
+
+/// Every instruction in `chunk`, under a header with its `name`.
+///
+/// The functions declared in the chunk follow it, each under a
+/// header of its own.
+pub fn disassemble(chunk: &Chunk, name: &str) -> String {
+    let mut out = format!("== {} ==\n", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, &mut out);
+    }
+
+    for constant in chunk.constants() {
+        if let Constant::Function(function) = constant {
+            out.push('\n');
+            out.push_str(&disassemble_function(function));
+        }
+    }
+    out
+}
+
+
+/// Every instruction in `function`, and the functions inside it.
+pub fn disassemble_function(function: &Function) -> String {
+    disassemble(&function.chunk, &function.to_string())
+}
+
+
+/// Show the instruction at `offset` as one line of `out`, and give
+/// back the offset of the next one.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let line = chunk.line_at(offset);
+    if offset > 0 && line == chunk.line_at(offset - 1) {
+        let _ = write!(out, "{:04}    | ", offset);
+    } else {
+        let _ = write!(out, "{:04} {:4} ", offset, line);
+    }
+
+    let op_code = match OpCode::try_from(chunk.code[offset]) {
+        Ok(op_code) => op_code,
+        Err(byte) => {
+            let _ = writeln!(out, "Unknown opcode {}", byte);
+            return offset + 1;
+        }
+    };
+    // a chunk cut off in the middle of an instruction shouldn't
+    // take the disassembler down with it
+    let size = instruction_size(chunk, offset);
+    if offset + size > chunk.code.len() {
+        let _ = writeln!(out, "{} (truncated)", op_code.name());
+        return chunk.code.len();
+    }
+
+    // `at` is where the instruction proper starts, after any
+    // `OP_WIDE`, and `wide` the high bytes of its constant index
+    let (at, wide, op_code) = match op_code {
+        OpCode::Wide if size > 3 => {
+            let widened = OpCode::try_from(chunk.code[offset + 3]).expect("the size says it's an instruction");
+            (offset + 3, (chunk.read_u16(offset + 1) as usize) << 8, widened)
+        }
+        _ => (offset, 0, op_code),
+    };
+    let name = op_code.name();
+    match op_code {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::Class
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::Method
+        | OpCode::GetSuper => {
+            constant_instruction(chunk, name, wide | chunk.code[at + 1] as usize, out);
+        }
+        OpCode::ConstantLong => constant_instruction(chunk, name, chunk.read_u24(at + 1), out),
+        OpCode::PopN | OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue | OpCode::SetUpvalue | OpCode::Call => {
+            let _ = writeln!(out, "{:<16} {:4}", name, chunk.code[at + 1]);
+        }
+        OpCode::Invoke | OpCode::SuperInvoke => {
+            let index = wide | chunk.code[at + 1] as usize;
+            let argument_count = chunk.code[at + 2];
+            let _ = match chunk.constants().get(index) {
+                Some(constant) => writeln!(out, "{:<16} ({} args) {:4} '{}'", name, argument_count, index, constant),
+                None => writeln!(out, "{:<16} ({} args) {:4} (no such constant)", name, argument_count, index),
+            };
+        }
+        OpCode::Closure => {
+            constant_instruction(chunk, name, wide | chunk.code[at + 1] as usize, out);
+            // one line for each variable the closure captures
+            for pair in (at + 2..offset + size).step_by(2) {
+                let kind = if chunk.code[pair] == 1 { "local" } else { "upvalue" };
+                let _ = writeln!(out, "{:04}    |                     {} {}", pair, kind, chunk.code[pair + 1]);
+            }
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(at + 1) as usize;
+            let target = match op_code {
+                OpCode::Loop => (at + 3).checked_sub(jump),
+                _ => Some(at + 3 + jump),
+            };
+            let _ = match target {
+                Some(target) => writeln!(out, "{:<16} {:4} -> {}", name, offset, target),
+                None => writeln!(out, "{:<16} {:4} -> before the start", name, offset),
+            };
+        }
+        _ => {
+            let _ = writeln!(out, "{}", name);
+        }
+    }
+    offset + size
+}
+
+
+/// How many bytes the instruction at `offset` takes, its operands
+/// included.
+///
+/// This is the same for every instruction with the same opcode,
+/// except `OP_CLOSURE`, which is followed by two bytes for each
+/// upvalue of its function.  An `OP_WIDE` counts as part of the
+/// instruction after it.
+pub fn instruction_size(chunk: &Chunk, offset: usize) -> usize {
+    let Ok(op_code) = OpCode::try_from(chunk.code[offset]) else {
+        return 1;
+    };
+    if op_code != OpCode::Wide {
+        return widened_size(chunk, offset, 0);
+    }
+    let widened = chunk.code.get(offset + 3).map(|byte| OpCode::try_from(*byte));
+    match (widened, chunk.code.get(offset + 2)) {
+        (Some(Ok(widened)), Some(_)) if widened != OpCode::Wide => {
+            3 + widened_size(chunk, offset + 3, (chunk.read_u16(offset + 1) as usize) << 8)
+        }
+        _ => 3,
+    }
+}
+
+/// The size of the instruction at `offset`, not an `OP_WIDE`,
+/// whose constant index has the high bytes `wide`.
+fn widened_size(chunk: &Chunk, offset: usize, wide: usize) -> usize {
+    let Ok(op_code) = OpCode::try_from(chunk.code[offset]) else {
+        return 1;
+    };
+    match op_code {
+        OpCode::Constant
+        | OpCode::PopN
+        | OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::Call
+        | OpCode::Class
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::Method
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::GetSuper => 2,
+        OpCode::Closure => {
+            let function = chunk.code.get(offset + 1).and_then(|index| chunk.constants().get(wide | *index as usize));
+            match function {
+                Some(Constant::Function(function)) => 2 + 2 * function.upvalue_count,
+                _ => 2,
+            }
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop | OpCode::Invoke | OpCode::SuperInvoke => 3,
+        OpCode::ConstantLong => 4,
+        OpCode::Wide => 3,
+        _ => 1,
+    }
+}
+
+
+fn constant_instruction(chunk: &Chunk, name: &str, index: usize, out: &mut String) {
+    let _ = match chunk.constants().get(index) {
+        Some(constant) => writeln!(out, "{:<16} {:4} '{}'", name, index, constant),
+        None => writeln!(out, "{:<16} {:4} (no such constant)", name, index),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassembles_in_the_clox_style() {
+        let mut chunk = Chunk::new();
+        chunk.write_constant(Constant::Number(1.2), 1);
+        chunk.write_op(OpCode::Negate, 1);
+        chunk.write_op(OpCode::JumpIfFalse, 2);
+        chunk.write(0, 2);
+        chunk.write(2, 2);
+        chunk.write_op(OpCode::GetLocal, 2);
+        chunk.write(1, 2);
+        chunk.write_op(OpCode::Loop, 3);
+        chunk.write(0, 3);
+        chunk.write(11, 3);
+        chunk.write_op(OpCode::Return, 3);
+
+        assert_eq!(
+            disassemble(&chunk, "<script>"),
+            "\
+== <script> ==
+0000    1 OP_CONSTANT         0 '1.2'
+0002    | OP_NEGATE
+0003    2 OP_JUMP_IF_FALSE    3 -> 8
+0006    | OP_GET_LOCAL        1
+0008    3 OP_LOOP             8 -> 0
+0011    | OP_RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_closures_show_what_they_capture() {
+        let mut function = Function::new("inner");
+        function.upvalue_count = 2;
+        let mut chunk = Chunk::new();
+        chunk.add_constant(Constant::Function(std::rc::Rc::new(function)));
+        chunk.write_op(OpCode::Closure, 1);
+        chunk.write(0, 1);
+        chunk.write(1, 1);
+        chunk.write(3, 1);
+        chunk.write(0, 1);
+        chunk.write(0, 1);
+        chunk.write_op(OpCode::GetUpvalue, 2);
+        chunk.write(1, 2);
+        chunk.write_op(OpCode::CloseUpvalue, 2);
+
+        assert_eq!(
+            disassemble(&chunk, "<script>").lines().take(6).collect::<Vec<&str>>(),
+            [
+                "== <script> ==",
+                "0000    1 OP_CLOSURE          0 '<fn inner>'",
+                "0002    |                     local 3",
+                "0004    |                     upvalue 0",
+                "0006    2 OP_GET_UPVALUE      1",
+                "0008    | OP_CLOSE_UPVALUE",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bad_code_is_shown_not_a_panic() {
+        let chunk = Chunk::from_parts(vec![255, OpCode::Constant as u8, 9], vec![], vec![]);
+
+        assert_eq!(
+            disassemble(&chunk, "bad"),
+            "== bad ==\n0000    0 Unknown opcode 255\n0001    | OP_CONSTANT         9 (no such constant)\n"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Contains the `Function` struct.
+
+use crate::chunk::Chunk;
+
+
+/// How deep functions can be declared inside each other, counting
+/// the script as depth 0.
+///
+/// Compiling, verifying and loading a function all recurse into the
+/// functions declared in it, so the compiler won't compile anything
+/// deeper and a `.loxc` file with anything deeper won't load.
+pub const MAX_FUNCTION_NESTING: usize = 200;
+
+/// A compiled function: its code and what's needed to call it.
+///
+/// The top-level code of a script compiles to a function too, one
+/// with no name and no parameters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Function {
+    /// The function's name, or empty for a script.
+    pub name: String,
+    pub arity: usize,
+    /// How many variables of the functions around it the function
+    /// captures.
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl Function {
+    pub fn new(name: &str) -> Self {
+        //! Create a new `Function` with no parameters and no code.
+        Function {
+            name: name.to_string(),
+            ..Function::default()
+        }
+    }
+}
+
+/// Shows a function the way Lox prints one.
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
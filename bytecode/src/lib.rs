@@ -0,0 +1,26 @@
+//! Contains the bytecode that Lox compiles to, for the faster
+//! path alongside the tree-walking interpreter.
+//!
+//! - `op_code` has the `OpCode` enum, one per instruction.
+//! - `chunk` has the `Chunk` struct, a compiled piece of code with
+//!   its constants and line numbers.
+//! - `constant` has the `Constant` enum for what goes in a chunk's
+//!   constant pool.
+//! - `function` has the `Function` struct, what a function (or a
+//!   whole script) compiles to.
+//! - `disassembler` turns a chunk back into something readable.
+//! - `loxc` saves a compiled script to the bytes of a `.loxc` file
+//!   and loads it back.
+//! - `load_error` has the `LoadError` struct, for a file that
+//!   can't be loaded.
+//! - `verifier` checks that code is safe for the VM to run.
+
+
+pub mod op_code;
+pub mod chunk;
+pub mod constant;
+pub mod function;
+pub mod disassembler;
+pub mod loxc;
+pub mod load_error;
+pub mod verifier;
@@ -0,0 +1,25 @@
+//! Contains the `LoadError` struct.
+
+
+/// Why a `.loxc` file couldn't be loaded: it isn't one, it's from
+/// another version, it's been damaged, or its code isn't safe to
+/// run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub message: String,
+}
+
+impl LoadError {
+    pub fn new(message: &str) -> Self {
+        //! Create a new `LoadError` saying what's wrong.
+        LoadError { message: message.to_string() }
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for LoadError {}
@@ -0,0 +1,310 @@
+//! Contains the `save` and `load` functions, which write a compiled
+//! script to the bytes of a `.loxc` file and read it back.
+//!
+//! Everything is big-endian, like the operands in the code:
+//!
+//! ```text
+//! file:      "LOXC"  version: u16  function  checksum: u32
+//! function:  name: string  arity: u32  upvalue_count: u32
+//!            code: u32 length, then the bytes
+//!            lines: u32 count, then (line: u32, count: u32) each
+//!            constants: u32 count, then (tag: u8, value) each
+//!              0 = number: the f64's bits as a u64
+//!              1 = string: string
+//!              2 = function: function
+//! string:    u32 length, then UTF-8
+//! ```
+//!
+//! The checksum is the CRC-32 of everything before it.
+
+use std::rc::Rc;
+
+use crate::chunk::{Chunk, LineRun, MAX_CONSTANTS};
+use crate::constant::Constant;
+use crate::function::{Function, MAX_FUNCTION_NESTING};
+use crate::load_error::LoadError;
+
+
+/// What every `.loxc` file starts with.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+/// The version of the format `save` writes and `load` reads.  It
+/// goes up whenever the format or the instructions change, so an
+/// old file is turned away instead of being misread.
+pub const VERSION: u16 = 3;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+
+/// The bytes of a `.loxc` file for the compiled script `function`.
+pub fn save(function: &Function) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_be_bytes());
+    write_function(&mut bytes, function);
+    let checksum = crc32(&bytes);
+    bytes.extend(checksum.to_be_bytes());
+    bytes
+}
+
+fn write_function(bytes: &mut Vec<u8>, function: &Function) {
+    write_string(bytes, &function.name);
+    write_u32(bytes, function.arity);
+    write_u32(bytes, function.upvalue_count);
+
+    write_u32(bytes, function.chunk.code.len());
+    bytes.extend(&function.chunk.code);
+
+    write_u32(bytes, function.chunk.lines().len());
+    for run in function.chunk.lines() {
+        write_u32(bytes, run.line);
+        write_u32(bytes, run.count);
+    }
+
+    write_u32(bytes, function.chunk.constants().len());
+    for constant in function.chunk.constants() {
+        match constant {
+            Constant::Number(number) => {
+                bytes.push(TAG_NUMBER);
+                bytes.extend(number.to_bits().to_be_bytes());
+            }
+            Constant::String(string) => {
+                bytes.push(TAG_STRING);
+                write_string(bytes, string);
+            }
+            Constant::Function(function) => {
+                bytes.push(TAG_FUNCTION);
+                write_function(bytes, function);
+            }
+        }
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, string: &str) {
+    write_u32(bytes, string.len());
+    bytes.extend(string.as_bytes());
+}
+
+/// Everything the compiler makes fits in a `u32` many times over.
+fn write_u32(bytes: &mut Vec<u8>, number: usize) {
+    bytes.extend((number as u32).to_be_bytes());
+}
+
+
+/// Read back a script saved by `save`.
+///
+/// The bytes may come from anywhere, so nothing about them is
+/// trusted: a file that isn't a `.loxc` file, is from another
+/// version, has been cut short or changed, or has code that could
+/// make the VM misbehave is an error rather than a panic.
+pub fn load(bytes: &[u8]) -> Result<Function, LoadError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::new("This isn't a compiled Lox file."));
+    }
+    if bytes.len() < MAGIC.len() + 2 + 4 {
+        return Err(LoadError::new("The file is cut short."));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    let mut reader = Reader { bytes: body, position: MAGIC.len() };
+    let version = u16::from_be_bytes([reader.byte()?, reader.byte()?]);
+    if version != VERSION {
+        return Err(LoadError::new(&format!(
+            "The file is from version {} of the format, but this is version {}.  Compile it again.",
+            version, VERSION
+        )));
+    }
+    if checksum != crc32(body).to_be_bytes() {
+        return Err(LoadError::new("The file is damaged: its checksum doesn't match."));
+    }
+
+    let function = reader.function(0)?;
+    if reader.position != body.len() {
+        return Err(LoadError::new("The file has bytes left over after the script."));
+    }
+    if function.arity != 0 || function.upvalue_count != 0 {
+        return Err(LoadError::new("The file's top level isn't a script."));
+    }
+    crate::verifier::verify(&function)?;
+    Ok(function)
+}
+
+
+/// Reads the parts of a file in order, checking there's enough
+/// left for each.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], LoadError> {
+        if count > self.bytes.len() - self.position {
+            return Err(LoadError::new("The file is cut short."));
+        }
+        self.position += count;
+        Ok(&self.bytes[self.position - count..self.position])
+    }
+
+    fn byte(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, LoadError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    /// A count of things that each take at least `size` bytes,
+    /// which can't be more than would fit in what's left.  Checking
+    /// up front keeps a bad count from asking for a huge `Vec`.
+    fn count(&mut self, size: usize) -> Result<usize, LoadError> {
+        let count = self.u32()?;
+        if count > (self.bytes.len() - self.position) / size {
+            return Err(LoadError::new("The file is cut short."));
+        }
+        Ok(count)
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let length = self.u32()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| LoadError::new("The file has a string that isn't UTF-8."))
+    }
+
+    fn function(&mut self, nesting: usize) -> Result<Function, LoadError> {
+        if nesting > MAX_FUNCTION_NESTING {
+            return Err(LoadError::new("The file's functions are nested too deeply."));
+        }
+        let name = self.string()?;
+        let arity = self.u32()?;
+        let upvalue_count = self.u32()?;
+
+        let length = self.u32()?;
+        let code = self.take(length)?.to_vec();
+
+        let count = self.count(8)?;
+        let mut lines = Vec::with_capacity(count);
+        for _ in 0..count {
+            lines.push(LineRun { line: self.u32()?, count: self.u32()? });
+        }
+        if lines.iter().map(|run| run.count).sum::<usize>() != code.len() {
+            return Err(LoadError::new("The file's line table doesn't match its code."));
+        }
+
+        let count = self.count(1)?;
+        if count > MAX_CONSTANTS {
+            return Err(LoadError::new("The file has a function with too many constants."));
+        }
+        let mut constants = Vec::with_capacity(count);
+        for _ in 0..count {
+            let constant = match self.byte()? {
+                TAG_NUMBER => {
+                    let bytes = self.take(8)?;
+                    let bits = u64::from_be_bytes([
+                        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+                    ]);
+                    Constant::Number(f64::from_bits(bits))
+                }
+                TAG_STRING => Constant::String(self.string()?),
+                TAG_FUNCTION => Constant::Function(Rc::new(self.function(nesting + 1)?)),
+                tag => return Err(LoadError::new(&format!("The file has a constant of unknown kind {}.", tag))),
+            };
+            constants.push(constant);
+        }
+
+        Ok(Function { name, arity, upvalue_count, chunk: Chunk::from_parts(code, constants, lines) })
+    }
+}
+
+
+/// The CRC-32 (the one zip and PNG use) of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op_code::OpCode;
+
+    /// `fun add(a) { return a + 1.5; } print add(«x»);`, more or
+    /// less, put together by hand.
+    fn script() -> Function {
+        let mut add = Function::new("add");
+        add.arity = 1;
+        add.chunk.write_op(OpCode::GetLocal, 1);
+        add.chunk.write(1, 1);
+        add.chunk.write_constant(Constant::Number(1.5), 1);
+        add.chunk.write_op(OpCode::Add, 1);
+        add.chunk.write_op(OpCode::Return, 1);
+
+        let mut script = Function::new("");
+        let add = script.chunk.add_constant(Constant::Function(Rc::new(add)));
+        let name = script.chunk.add_constant(Constant::String(String::from("add")));
+        for (byte, line) in [
+            (OpCode::Closure as u8, 1),
+            (add as u8, 1),
+            (OpCode::DefineGlobal as u8, 1),
+            (name as u8, 1),
+            (OpCode::GetGlobal as u8, 2),
+            (name as u8, 2),
+        ] {
+            script.chunk.write(byte, line);
+        }
+        script.chunk.write_constant(Constant::String(String::from("«x»")), 2);
+        for op_code in [OpCode::Call as u8, 1, OpCode::Print as u8, OpCode::Nil as u8, OpCode::Return as u8] {
+            script.chunk.write(op_code, 2);
+        }
+        script
+    }
+
+    #[test]
+    fn test_loading_gives_back_what_was_saved() {
+        let script = script();
+        let bytes = save(&script);
+
+        assert_eq!(&bytes[..4], b"LOXC");
+        assert_eq!(load(&bytes), Ok(script));
+    }
+
+    #[test]
+    fn test_checksum_is_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_bad_files_are_errors_not_panics() {
+        let bytes = save(&script());
+
+        assert_eq!(load(b"#!/usr/bin/env lox").unwrap_err().message, "This isn't a compiled Lox file.");
+        let mut other_version = bytes.clone();
+        other_version[5] += 1;
+        assert!(load(&other_version).unwrap_err().message.starts_with("The file is from version 4"));
+        let mut changed = bytes.clone();
+        changed[20] ^= 1;
+        assert_eq!(load(&changed).unwrap_err().message, "The file is damaged: its checksum doesn't match.");
+
+        // Every way of cutting the file short, and every single
+        // byte changed with the checksum fixed up to match.
+        for end in 0..bytes.len() {
+            assert!(load(&bytes[..end]).is_err());
+        }
+        for index in 6..bytes.len() - 4 {
+            for bit in 0..8 {
+                let mut changed = bytes[..bytes.len() - 4].to_vec();
+                changed[index] ^= 1 << bit;
+                let checksum = crc32(&changed);
+                changed.extend(checksum.to_be_bytes());
+                let _ = load(&changed);
+            }
+        }
+    }
+}
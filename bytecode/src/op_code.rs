@@ -0,0 +1,222 @@
+//! Contains the `OpCode` enum.
+
+
+/// One bytecode instruction.
+///
+/// Each is a single byte in a chunk's code, followed by its
+/// operands (if it has any).  The comment on each says what the
+/// operands are; "constant" is a one-byte index into the chunk's
+/// constant pool, which an `OP_WIDE` in front can make longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    /// constant -- push it
+    Constant,
+    /// three-byte constant index -- push it, for when a chunk has
+    /// more than 256 constants
+    ConstantLong,
+    /// two bytes -- the high bytes of the constant index of the
+    /// instruction after it, for names and functions past the first
+    /// 256 constants
+    Wide,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// count -- pop that many values, for when several go at once
+    PopN,
+    /// stack slot -- push the local in it
+    GetLocal,
+    /// stack slot -- store the top of the stack in it
+    SetLocal,
+    /// constant naming the global -- push its value
+    GetGlobal,
+    /// constant naming the global -- pop a value and declare it
+    DefineGlobal,
+    /// constant naming the global -- store the top of the stack
+    SetGlobal,
+    Equal,
+    Greater,
+    /// its own instruction rather than `Less` then `Not`, which
+    /// would get comparisons with `NaN` wrong
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    /// two-byte offset -- jump forwards
+    Jump,
+    /// two-byte offset -- jump forwards if the top of the stack is
+    /// falsey, leaving it there
+    JumpIfFalse,
+    /// two-byte offset -- jump backwards
+    Loop,
+    /// argument count -- call the value below the arguments
+    Call,
+    Return,
+    /// constant naming the class -- push a new class with no methods
+    Class,
+    /// constant naming the property -- replace the instance on top
+    /// of the stack with its property
+    GetProperty,
+    /// constant naming the field -- pop a value and an instance,
+    /// set the field, and push the value back
+    SetProperty,
+    /// constant naming the method -- pop a function and add it as
+    /// a method of the class below it
+    Method,
+    /// constant naming the method, argument count -- call a method
+    /// of the instance below the arguments, without making a bound
+    /// method first
+    Invoke,
+    /// copy the methods of the superclass below the top of the
+    /// stack into the class on top, and pop the class
+    Inherit,
+    /// constant holding the function, then a pair of bytes for each
+    /// of its upvalues: 1 and a stack slot to capture a local of the
+    /// function running, or 0 and the index of one of its own
+    /// upvalues -- push a new closure
+    Closure,
+    /// upvalue index -- push the variable it refers to
+    GetUpvalue,
+    /// upvalue index -- store the top of the stack in the variable
+    /// it refers to
+    SetUpvalue,
+    /// move the local on top of the stack into the upvalues that
+    /// captured it, and pop it
+    CloseUpvalue,
+    /// constant naming the method -- pop the superclass and replace
+    /// the instance below it with its superclass's method, bound
+    GetSuper,
+    /// constant naming the method, argument count -- pop the
+    /// superclass and call its method on the instance below the
+    /// arguments
+    SuperInvoke,
+}
+
+impl OpCode {
+    /// The name the disassembler shows, like `OP_CONSTANT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpCode::Constant => "OP_CONSTANT",
+            OpCode::ConstantLong => "OP_CONSTANT_LONG",
+            OpCode::Wide => "OP_WIDE",
+            OpCode::Nil => "OP_NIL",
+            OpCode::True => "OP_TRUE",
+            OpCode::False => "OP_FALSE",
+            OpCode::Pop => "OP_POP",
+            OpCode::PopN => "OP_POP_N",
+            OpCode::GetLocal => "OP_GET_LOCAL",
+            OpCode::SetLocal => "OP_SET_LOCAL",
+            OpCode::GetGlobal => "OP_GET_GLOBAL",
+            OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+            OpCode::SetGlobal => "OP_SET_GLOBAL",
+            OpCode::Equal => "OP_EQUAL",
+            OpCode::Greater => "OP_GREATER",
+            OpCode::GreaterEqual => "OP_GREATER_EQUAL",
+            OpCode::Less => "OP_LESS",
+            OpCode::LessEqual => "OP_LESS_EQUAL",
+            OpCode::Add => "OP_ADD",
+            OpCode::Subtract => "OP_SUBTRACT",
+            OpCode::Multiply => "OP_MULTIPLY",
+            OpCode::Divide => "OP_DIVIDE",
+            OpCode::Not => "OP_NOT",
+            OpCode::Negate => "OP_NEGATE",
+            OpCode::Print => "OP_PRINT",
+            OpCode::Jump => "OP_JUMP",
+            OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+            OpCode::Loop => "OP_LOOP",
+            OpCode::Call => "OP_CALL",
+            OpCode::Return => "OP_RETURN",
+            OpCode::Class => "OP_CLASS",
+            OpCode::GetProperty => "OP_GET_PROPERTY",
+            OpCode::SetProperty => "OP_SET_PROPERTY",
+            OpCode::Method => "OP_METHOD",
+            OpCode::Invoke => "OP_INVOKE",
+            OpCode::Inherit => "OP_INHERIT",
+            OpCode::Closure => "OP_CLOSURE",
+            OpCode::GetUpvalue => "OP_GET_UPVALUE",
+            OpCode::SetUpvalue => "OP_SET_UPVALUE",
+            OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+            OpCode::GetSuper => "OP_GET_SUPER",
+            OpCode::SuperInvoke => "OP_SUPER_INVOKE",
+        }
+    }
+}
+
+/// Decode a byte of a chunk's code, which fails if it isn't an
+/// instruction.
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        // A `match` rather than a table, so that the compiler sees
+        // each byte decodes to itself, and the VM's `match` on the
+        // result can jump straight from the byte.
+        match byte {
+            0 => Ok(OpCode::Constant),
+            1 => Ok(OpCode::ConstantLong),
+            2 => Ok(OpCode::Wide),
+            3 => Ok(OpCode::Nil),
+            4 => Ok(OpCode::True),
+            5 => Ok(OpCode::False),
+            6 => Ok(OpCode::Pop),
+            7 => Ok(OpCode::PopN),
+            8 => Ok(OpCode::GetLocal),
+            9 => Ok(OpCode::SetLocal),
+            10 => Ok(OpCode::GetGlobal),
+            11 => Ok(OpCode::DefineGlobal),
+            12 => Ok(OpCode::SetGlobal),
+            13 => Ok(OpCode::Equal),
+            14 => Ok(OpCode::Greater),
+            15 => Ok(OpCode::GreaterEqual),
+            16 => Ok(OpCode::Less),
+            17 => Ok(OpCode::LessEqual),
+            18 => Ok(OpCode::Add),
+            19 => Ok(OpCode::Subtract),
+            20 => Ok(OpCode::Multiply),
+            21 => Ok(OpCode::Divide),
+            22 => Ok(OpCode::Not),
+            23 => Ok(OpCode::Negate),
+            24 => Ok(OpCode::Print),
+            25 => Ok(OpCode::Jump),
+            26 => Ok(OpCode::JumpIfFalse),
+            27 => Ok(OpCode::Loop),
+            28 => Ok(OpCode::Call),
+            29 => Ok(OpCode::Return),
+            30 => Ok(OpCode::Class),
+            31 => Ok(OpCode::GetProperty),
+            32 => Ok(OpCode::SetProperty),
+            33 => Ok(OpCode::Method),
+            34 => Ok(OpCode::Invoke),
+            35 => Ok(OpCode::Inherit),
+            36 => Ok(OpCode::Closure),
+            37 => Ok(OpCode::GetUpvalue),
+            38 => Ok(OpCode::SetUpvalue),
+            39 => Ok(OpCode::CloseUpvalue),
+            40 => Ok(OpCode::GetSuper),
+            41 => Ok(OpCode::SuperInvoke),
+            _ => Err(byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_op_code_decodes_to_itself() {
+        let mut byte = 0;
+        while let Ok(op_code) = OpCode::try_from(byte) {
+            assert_eq!(op_code as u8, byte);
+            byte += 1;
+        }
+        assert_eq!(byte, OpCode::SuperInvoke as u8 + 1);
+    }
+}
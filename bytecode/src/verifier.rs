@@ -0,0 +1,305 @@
+//! Contains the `verify` function.
+
+use crate::constant::Constant;
+use crate::function::Function;
+use crate::load_error::LoadError;
+use crate::op_code::OpCode;
+
+
+/// How many parameters a function can have, as in the compiler.
+const MAX_ARITY: usize = 255;
+
+/// How many variables a function can capture, as in the compiler.
+const MAX_UPVALUES: usize = 256;
+
+
+/// Check that the code of `function`, and of every function
+/// declared in it, is safe for the VM to run.
+///
+/// The compiler's code always is, but code loaded from a file
+/// could say anything.  The VM trusts what the compiler promises
+/// and would panic (or worse) on code that breaks those promises,
+/// so this checks each of them:
+/// - every instruction is a real one, with all its operands;
+/// - constants are there, and are names where names are wanted
+///   and functions where closures are made, and `OP_WIDE` only
+///   comes before an instruction that takes one of those;
+/// - jumps land inside the code, and running off the end isn't
+///   possible;
+/// - every way of reaching an instruction leaves the stack the
+///   same height, nothing takes more off the stack than there is,
+///   and locals and upvalues are only read where they exist.
+///
+/// Only instructions that can be reached are checked, since the
+/// others never run.
+pub fn verify(function: &Function) -> Result<(), LoadError> {
+    if function.arity > MAX_ARITY {
+        return Err(invalid(function, "has too many parameters"));
+    }
+    if function.upvalue_count > MAX_UPVALUES {
+        return Err(invalid(function, "captures too many variables"));
+    }
+    verify_code(function)?;
+    for constant in function.chunk.constants() {
+        if let Constant::Function(inner) = constant {
+            verify(inner)?;
+        }
+    }
+    Ok(())
+}
+
+
+fn invalid(function: &Function, problem: &str) -> LoadError {
+    LoadError::new(&format!("The code for {} {}.", function, problem))
+}
+
+
+/// Follow every path through the code from the start, working out
+/// how high the stack is before each instruction.
+fn verify_code(function: &Function) -> Result<(), LoadError> {
+    let chunk = &function.chunk;
+    let byte = |offset: usize| {
+        chunk.code.get(offset).copied().ok_or_else(|| invalid(function, "ends partway through an instruction"))
+    };
+    // `wide` is the high bytes of the index from an `OP_WIDE` in
+    // front, if there is one
+    let constant = |wide: usize, offset: usize| {
+        let index = wide | byte(offset)? as usize;
+        chunk.constants().get(index).ok_or_else(|| invalid(function, "uses a constant that isn't there"))
+    };
+    let name = |wide: usize, offset: usize| match constant(wide, offset)? {
+        Constant::String(_) => Ok(()),
+        _ => Err(invalid(function, "uses a constant that isn't a name as one")),
+    };
+    let jump = |offset: usize| Ok::<usize, LoadError>(u16::from_be_bytes([byte(offset)?, byte(offset + 1)?]) as usize);
+
+    // The stack height before each instruction reached so far.
+    // Slot 0 holds the function itself, then come the parameters.
+    let mut heights: Vec<Option<usize>> = vec![None; chunk.code.len()];
+    let mut to_check = vec![(0, function.arity + 1)];
+    while let Some((offset, height)) = to_check.pop() {
+        match heights.get(offset) {
+            None => return Err(invalid(function, "runs past the end")),
+            Some(Some(known)) if *known == height => continue,
+            Some(Some(_)) => return Err(invalid(function, "leaves the stack at different heights on different paths")),
+            Some(None) => heights[offset] = Some(height),
+        }
+        let needs = |count: usize| match height >= count {
+            true => Ok(()),
+            false => Err(invalid(function, "takes more off the stack than there is")),
+        };
+
+        // an `OP_WIDE` is checked along with the instruction it
+        // widens, which has to be one that takes a constant
+        let (offset, wide, widened) = match OpCode::try_from(byte(offset)?) {
+            Ok(OpCode::Wide) => (offset + 3, (byte(offset + 1)? as usize) << 16 | (byte(offset + 2)? as usize) << 8, true),
+            _ => (offset, 0, false),
+        };
+        let Ok(op_code) = OpCode::try_from(byte(offset)?) else {
+            return Err(invalid(function, "has an unknown instruction"));
+        };
+        let takes_name_or_function = matches!(
+            op_code,
+            OpCode::GetGlobal
+                | OpCode::DefineGlobal
+                | OpCode::SetGlobal
+                | OpCode::Class
+                | OpCode::GetProperty
+                | OpCode::SetProperty
+                | OpCode::Method
+                | OpCode::Invoke
+                | OpCode::GetSuper
+                | OpCode::SuperInvoke
+                | OpCode::Closure
+        );
+        if widened && !takes_name_or_function {
+            return Err(invalid(function, "widens an instruction that doesn't take a name or function"));
+        }
+        // Where the next instruction is and how high the stack is
+        // by then, if running carries on to it.
+        let (next, height) = match op_code {
+            OpCode::Constant => {
+                constant(wide, offset + 1)?;
+                (offset + 2, height + 1)
+            }
+            OpCode::ConstantLong => {
+                let index = u32::from_be_bytes([0, byte(offset + 1)?, byte(offset + 2)?, byte(offset + 3)?]);
+                if index as usize >= chunk.constants().len() {
+                    return Err(invalid(function, "uses a constant that isn't there"));
+                }
+                (offset + 4, height + 1)
+            }
+            OpCode::Nil | OpCode::True | OpCode::False => (offset + 1, height + 1),
+            OpCode::Pop | OpCode::Print | OpCode::CloseUpvalue => {
+                needs(1)?;
+                (offset + 1, height - 1)
+            }
+            OpCode::PopN => {
+                let count = byte(offset + 1)? as usize;
+                needs(count)?;
+                (offset + 2, height - count)
+            }
+            OpCode::GetLocal | OpCode::SetLocal => {
+                if byte(offset + 1)? as usize >= height {
+                    return Err(invalid(function, "uses a local that isn't there"));
+                }
+                match op_code {
+                    OpCode::GetLocal => (offset + 2, height + 1),
+                    _ => (offset + 2, height),
+                }
+            }
+            OpCode::GetGlobal | OpCode::Class => {
+                name(wide, offset + 1)?;
+                (offset + 2, height + 1)
+            }
+            OpCode::SetGlobal | OpCode::GetProperty => {
+                name(wide, offset + 1)?;
+                needs(1)?;
+                (offset + 2, height)
+            }
+            OpCode::DefineGlobal => {
+                name(wide, offset + 1)?;
+                needs(1)?;
+                (offset + 2, height - 1)
+            }
+            OpCode::SetProperty | OpCode::Method | OpCode::GetSuper => {
+                name(wide, offset + 1)?;
+                needs(2)?;
+                (offset + 2, height - 1)
+            }
+            OpCode::Equal
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Inherit => {
+                needs(2)?;
+                (offset + 1, height - 1)
+            }
+            OpCode::Not | OpCode::Negate => {
+                needs(1)?;
+                (offset + 1, height)
+            }
+            OpCode::Jump => {
+                to_check.push((offset + 3 + jump(offset + 1)?, height));
+                continue;
+            }
+            OpCode::JumpIfFalse => {
+                needs(1)?;
+                to_check.push((offset + 3 + jump(offset + 1)?, height));
+                (offset + 3, height)
+            }
+            OpCode::Loop => {
+                let Some(target) = (offset + 3).checked_sub(jump(offset + 1)?) else {
+                    return Err(invalid(function, "jumps back past the start"));
+                };
+                to_check.push((target, height));
+                continue;
+            }
+            OpCode::Call => {
+                let argument_count = byte(offset + 1)? as usize;
+                needs(argument_count + 1)?;
+                (offset + 2, height - argument_count)
+            }
+            OpCode::Invoke => {
+                name(wide, offset + 1)?;
+                let argument_count = byte(offset + 2)? as usize;
+                needs(argument_count + 1)?;
+                (offset + 3, height - argument_count)
+            }
+            OpCode::SuperInvoke => {
+                name(wide, offset + 1)?;
+                let argument_count = byte(offset + 2)? as usize;
+                needs(argument_count + 2)?;
+                (offset + 3, height - argument_count - 1)
+            }
+            OpCode::Wide => unreachable!("an OP_WIDE is checked with the instruction after it"),
+            OpCode::Return => {
+                needs(1)?;
+                continue;
+            }
+            OpCode::Closure => {
+                let Constant::Function(inner) = constant(wide, offset + 1)? else {
+                    return Err(invalid(function, "makes a closure out of something that isn't a function"));
+                };
+                let mut next = offset + 2;
+                for _ in 0..inner.upvalue_count {
+                    let index = byte(next + 1)? as usize;
+                    let exists = match byte(next)? {
+                        1 => index < height,
+                        0 => index < function.upvalue_count,
+                        _ => false,
+                    };
+                    if !exists {
+                        return Err(invalid(function, "captures a variable that isn't there"));
+                    }
+                    next += 2;
+                }
+                (next, height + 1)
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                if byte(offset + 1)? as usize >= function.upvalue_count {
+                    return Err(invalid(function, "uses an upvalue that isn't there"));
+                }
+                match op_code {
+                    OpCode::GetUpvalue => (offset + 2, height + 1),
+                    _ => {
+                        needs(1)?;
+                        (offset + 2, height)
+                    }
+                }
+            }
+        };
+        to_check.push((next, height));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{Chunk, LineRun};
+
+    fn function(code: &[u8], constants: Vec<Constant>) -> Function {
+        let mut function = Function::new("f");
+        function.chunk = Chunk::from_parts(code.to_vec(), constants, vec![LineRun { line: 1, count: code.len() }]);
+        function
+    }
+
+    #[test]
+    fn test_unsafe_code_is_turned_away() {
+        let name = || vec![Constant::String(String::from("x"))];
+        let cases = [
+            (function(&[OpCode::Nil as u8], vec![]), "runs past the end"),
+            (function(&[OpCode::Pop as u8, OpCode::Return as u8], vec![]), "takes more off the stack than there is"),
+            (function(&[OpCode::GetLocal as u8, 1, OpCode::Return as u8], vec![]), "uses a local that isn't there"),
+            (function(&[OpCode::Constant as u8, 0, OpCode::Return as u8], vec![]), "uses a constant that isn't there"),
+            (
+                function(&[OpCode::GetGlobal as u8, 0, OpCode::Return as u8], vec![Constant::Number(1.0)]),
+                "uses a constant that isn't a name as one",
+            ),
+            (function(&[OpCode::Jump as u8, 0, 9], vec![]), "runs past the end"),
+            (function(&[OpCode::Loop as u8, 0, 4], vec![]), "jumps back past the start"),
+            (function(&[OpCode::Nil as u8, OpCode::Loop as u8, 0, 4], vec![]), "leaves the stack at different heights on different paths"),
+            (function(&[OpCode::GetUpvalue as u8, 0, OpCode::Return as u8], vec![]), "uses an upvalue that isn't there"),
+            (function(&[OpCode::GetProperty as u8], name()), "ends partway through an instruction"),
+            (function(&[0xff], vec![]), "has an unknown instruction"),
+            (
+                function(&[OpCode::Wide as u8, 0, 1, OpCode::GetGlobal as u8, 0, OpCode::Return as u8], name()),
+                "uses a constant that isn't there",
+            ),
+            (
+                function(&[OpCode::Wide as u8, 0, 0, OpCode::Nil as u8, OpCode::Return as u8], vec![]),
+                "widens an instruction that doesn't take a name or function",
+            ),
+        ];
+
+        for (function, problem) in cases {
+            assert_eq!(verify(&function).unwrap_err().message, format!("The code for <fn f> {}.", problem));
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Contains the `CompileError` struct.
+
+use diagnostic::diagnostic::Diagnostic;
+use token::token::Token;
+use token::token_type::TokenType;
+
+
+/// A mistake found while compiling, either in the syntax or in
+/// how names are used.
+///
+/// The messages are the same as the parser's and the resolver's,
+/// so the tree-walker and the VM report mistakes the same way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(token: &Token, message: &str) -> Self {
+        CompileError {
+            token: token.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Turn the error into a `Diagnostic` pointing at the token.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let message = match self.token.token_type {
+            TokenType::EOF => format!("Error at end: {}", self.message),
+            _ => format!("Error at '{}': {}", self.token.lexeme, self.message),
+        };
+        Diagnostic::new(self.token.line, self.token.span, message)
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_diagnostic())
+    }
+}
@@ -0,0 +1,1225 @@
+//! Contains the `Compiler` struct and the `compile` function.
+//!
+//! This is a single-pass compiler: it pulls tokens from the
+//! scanner one at a time and writes bytecode as it goes, with no
+//! syntax tree in between.  Statements are compiled by recursive
+//! descent like the parser, and expressions by Pratt parsing: each
+//! token type has a rule saying how to compile it at the start of
+//! an expression (prefix), after one (infix), and how tightly it
+//! binds.
+//!
+//! Local variables live in stack slots, so the compiler keeps
+//! track of which slot each one is in and reads and writes them by
+//! number.  A local of an enclosing function is reached through an
+//! upvalue, which the closure captures when it's made.  Globals are
+//! looked up by name at runtime.
+
+use std::rc::Rc;
+
+use bytecode::chunk::Chunk;
+use bytecode::constant::Constant;
+use bytecode::function::{Function, MAX_FUNCTION_NESTING};
+use bytecode::op_code::OpCode;
+use diagnostic::diagnostic::Diagnostic;
+use scanner::scanner_struct::Scanner;
+use token::span::Span;
+use token::token::{Literal, Token};
+use token::token_type::TokenType;
+
+use crate::compile_error::CompileError;
+use crate::precedence::Precedence;
+
+
+/// The most arguments (or parameters) a call can have, since the
+/// count is one byte.
+const MAX_ARGUMENTS: usize = 255;
+
+/// The most locals a function can have, since a slot is one byte.
+const MAX_LOCALS: usize = 256;
+
+/// The most variables a function can capture, since an upvalue's
+/// index is one byte.
+const MAX_UPVALUES: usize = 256;
+
+/// How deeply statements and expressions can be nested, for the
+/// same reason as the parser's limit: the compiler recurses once
+/// per level.
+const MAX_NESTING: usize = 200;
+
+
+/// Compile `source` to the function for its top-level code.
+///
+/// Returns every problem the scanner and the compiler found, in
+/// the order they appear in the code, if there were any.
+pub fn compile(source: &str) -> Result<Function, Vec<Diagnostic>> {
+    Compiler::new(source).compile()
+}
+
+
+/// How to compile a token found at the start of an expression, or
+/// after one.  The flag says whether an assignment can go here.
+type ParseFn<'a> = fn(&mut Compiler<'a>, bool);
+
+
+/// The Pratt parsing rule for one token type.
+struct ParseRule<'a> {
+    prefix: Option<ParseFn<'a>>,
+    infix: Option<ParseFn<'a>>,
+    /// How tightly the token binds as an infix operator.
+    precedence: Precedence,
+}
+
+
+/// What kind of function is being compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionType {
+    Function,
+    Method,
+    /// An `init` method, which always gives back `this`.
+    Initializer,
+    /// The top-level code, where `return` isn't allowed.
+    Script,
+}
+
+
+/// The state of one class being compiled.
+#[derive(Debug)]
+struct ClassState {
+    has_superclass: bool,
+}
+
+
+/// A local variable and the scope it was declared in.
+#[derive(Debug)]
+struct Local {
+    name: String,
+    /// `None` while the variable's initializer is being compiled,
+    /// so it can't be read there.
+    depth: Option<usize>,
+    /// Whether a closure captures it, in which case it has to be
+    /// moved off the stack when it goes out of scope.
+    is_captured: bool,
+}
+
+
+/// A variable a function captures from the function around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Upvalue {
+    /// The stack slot of the local, or the index of the enclosing
+    /// function's own upvalue.
+    index: u8,
+    /// Whether it's a local of the enclosing function, rather than
+    /// one of its upvalues.
+    is_local: bool,
+}
+
+
+/// The state of one function being compiled.  Functions nest, so
+/// the compiler keeps a stack of these.
+#[derive(Debug)]
+struct FunctionState {
+    function: Function,
+    function_type: FunctionType,
+    /// The locals in scope, in the order of their stack slots.
+    locals: Vec<Local>,
+    /// The variables captured from enclosing functions, in the
+    /// order of their indexes.
+    upvalues: Vec<Upvalue>,
+    /// How many blocks deep we are; 0 is the function's top level.
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(name: &str, function_type: FunctionType) -> Self {
+        // slot 0 holds the function being called, which can't be
+        // named from Lox, or in a method the instance, which is `this`
+        let slot_zero = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "this",
+            FunctionType::Function | FunctionType::Script => "",
+        };
+        FunctionState {
+            function: Function::new(name),
+            function_type,
+            locals: vec![Local { name: slot_zero.to_string(), depth: Some(0), is_captured: false }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        }
+    }
+}
+
+
+/// The compiler struct.
+#[derive(Debug)]
+pub struct Compiler<'a> {
+    scanner: Scanner<'a>,
+
+    /// The token that's next to be consumed.
+    current: Token,
+
+    /// The token that was just consumed.
+    previous: Token,
+
+    /// Every error found so far.
+    pub errors: Vec<CompileError>,
+
+    /// Whether we've hit an error and are skipping ahead to the
+    /// next statement, so one mistake only gets reported once.
+    panic_mode: bool,
+
+    /// Whether the code got too deep and the rest of it was skipped,
+    /// after which the missing `}`s aren't worth reporting.
+    skipped_rest: bool,
+
+    /// How many nested statements and expressions we're inside of.
+    nesting: usize,
+
+    /// The functions being compiled, innermost last.
+    functions: Vec<FunctionState>,
+
+    /// The classes being compiled, innermost last.
+    classes: Vec<ClassState>,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(source: &'a str) -> Self {
+        //! Create a new `Compiler` for `source`.
+        let start = Token::new(TokenType::EOF, String::new(), Literal::NoLiteral, 1, Span::default());
+        Compiler {
+            scanner: Scanner::new(source),
+            current: start.clone(),
+            previous: start,
+            errors: Vec::new(),
+            panic_mode: false,
+            skipped_rest: false,
+            nesting: 0,
+            functions: vec![FunctionState::new("", FunctionType::Script)],
+            classes: Vec::new(),
+        }
+    }
+
+    pub fn compile(mut self) -> Result<Function, Vec<Diagnostic>> {
+        //! Compile the whole source.
+        //!
+        //! This is the main method and purpose of the compiler.
+        self.advance();
+        while !self.match_type(TokenType::EOF) {
+            self.declaration();
+        }
+        let (function, _) = self.end_function();
+
+        let mut diagnostics = std::mem::take(&mut self.scanner.errors);
+        diagnostics.extend(self.errors.iter().map(CompileError::to_diagnostic));
+        if diagnostics.is_empty() {
+            return Ok(function);
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        Err(diagnostics)
+    }
+
+    // ---- statements ----
+
+    fn declaration(&mut self) {
+        if self.match_type(TokenType::Class) {
+            self.class_declaration();
+        } else if self.match_type(TokenType::Fun) {
+            self.fun_declaration();
+        } else if self.match_type(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn class_declaration(&mut self) {
+        self.consume(TokenType::Identifier, "Expect class name.");
+        let class_name = self.previous.clone();
+        let name_constant = self.identifier_constant(&class_name);
+        self.declare_variable();
+
+        self.emit_with_constant(OpCode::Class, name_constant);
+        self.define_variable(name_constant);
+        self.classes.push(ClassState { has_superclass: false });
+
+        if self.match_type(TokenType::Less) {
+            self.consume(TokenType::Identifier, "Expect superclass name.");
+            if self.previous.lexeme == class_name.lexeme {
+                self.error("A class can't inherit from itself.");
+            }
+            self.variable(false);
+
+            // the superclass stays on the stack as a local called
+            // `super`, in a scope around the methods
+            self.begin_scope();
+            self.add_local("super");
+            self.define_variable(0);
+
+            self.named_variable(&class_name, false);
+            self.emit_op(OpCode::Inherit);
+            self.classes.last_mut().expect("we're in a class").has_superclass = true;
+        }
+
+        // the class goes on the stack so the methods can be added
+        self.named_variable(&class_name, false);
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.");
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
+            self.method();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.");
+        self.emit_op(OpCode::Pop);
+
+        if self.classes.pop().is_some_and(|class| class.has_superclass) {
+            self.end_scope();
+        }
+    }
+
+    fn method(&mut self) {
+        self.consume(TokenType::Identifier, "Expect method name.");
+        let name = self.previous.clone();
+        let constant = self.identifier_constant(&name);
+        let function_type = match name.lexeme.as_str() {
+            "init" => FunctionType::Initializer,
+            _ => FunctionType::Method,
+        };
+        self.function(function_type);
+        self.emit_with_constant(OpCode::Method, constant);
+    }
+
+    fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expect function name.");
+        // defined straight away, so the function can call itself
+        self.mark_initialized();
+        self.function(FunctionType::Function);
+        self.define_variable(global);
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+        if self.match_type(TokenType::Assignment) {
+            self.expression();
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.");
+        self.define_variable(global);
+    }
+
+    fn statement(&mut self) {
+        if !self.nest() {
+            return;
+        }
+        if self.match_type(TokenType::Print) {
+            self.print_statement();
+        } else if self.match_type(TokenType::For) {
+            self.for_statement();
+        } else if self.match_type(TokenType::If) {
+            self.if_statement();
+        } else if self.match_type(TokenType::Return) {
+            self.return_statement();
+        } else if self.match_type(TokenType::While) {
+            self.while_statement();
+        } else if self.match_type(TokenType::LeftBrace) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement();
+        }
+        self.nesting -= 1;
+    }
+
+    fn print_statement(&mut self) {
+        let keyword = self.previous.clone();
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after value.");
+        self.emit_op_at(OpCode::Print, &keyword);
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.");
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn block(&mut self) {
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF) {
+            self.declaration();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.");
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.");
+
+        // the condition stays on the stack through the jump, so
+        // each branch starts by popping it
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+        let else_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+        if self.match_type(TokenType::Else) {
+            self.statement();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.chunk().code.len();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+    }
+
+    /// A `for` loop, compiled like the `while` loop it stands for.
+    ///
+    /// The increment comes before the body in the source but runs
+    /// after it, so the body jumps back to the increment, which
+    /// then jumps back to the condition.
+    fn for_statement(&mut self) {
+        self.begin_scope();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        if self.match_type(TokenType::Semicolon) {
+            // no initializer
+        } else if self.match_type(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.chunk().code.len();
+        let mut exit_jump = None;
+        if !self.match_type(TokenType::Semicolon) {
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after loop condition.");
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse));
+            self.emit_op(OpCode::Pop);
+        }
+
+        if !self.match_type(TokenType::RightParen) {
+            let body_jump = self.emit_jump(OpCode::Jump);
+            let increment_start = self.chunk().code.len();
+            self.expression();
+            self.emit_op(OpCode::Pop);
+            self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_op(OpCode::Pop);
+        }
+        self.end_scope();
+    }
+
+    fn return_statement(&mut self) {
+        if self.state().function_type == FunctionType::Script {
+            self.error("Can't return from top-level code.");
+        }
+
+        if self.match_type(TokenType::Semicolon) {
+            self.emit_return();
+        } else {
+            if self.state().function_type == FunctionType::Initializer {
+                self.error("Can't return a value from an initializer.");
+            }
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after return value.");
+            self.emit_op(OpCode::Return);
+        }
+    }
+
+    /// Skip ahead to something that looks like the start of a
+    /// statement, so one mistake doesn't cause a pile of others.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current.token_type != TokenType::EOF {
+            if self.previous.token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.current.token_type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    // ---- functions ----
+
+    /// Compile a function's parameters and body (its name has just
+    /// been consumed) and add the code that pushes it.
+    fn function(&mut self, function_type: FunctionType) {
+        // the new function's depth is how many are open around it
+        if self.functions.len() > MAX_FUNCTION_NESTING {
+            self.too_deep();
+            return;
+        }
+        let name = self.previous.lexeme.clone();
+        self.functions.push(FunctionState::new(&name, function_type));
+        // the parameters and the body share one scope, which is
+        // never ended: returning throws the whole frame away
+        self.begin_scope();
+
+        let kind = match function_type {
+            FunctionType::Method | FunctionType::Initializer => "method",
+            FunctionType::Function | FunctionType::Script => "function",
+        };
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind));
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.state_mut().function.arity += 1;
+                if self.state().function.arity > MAX_ARGUMENTS {
+                    self.error_at_current("Can't have more than 255 parameters.");
+                }
+                let constant = self.parse_variable("Expect parameter name.");
+                self.define_variable(constant);
+                if !self.match_type(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.");
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind));
+        self.block();
+
+        let (function, upvalues) = self.end_function();
+        let index = self.chunk().add_constant(Constant::Function(Rc::new(function)));
+        if !self.emit_with_constant(OpCode::Closure, index) {
+            return;
+        }
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+    }
+
+    /// Finish the innermost function and give it back, with the
+    /// variables it captures.
+    fn end_function(&mut self) -> (Function, Vec<Upvalue>) {
+        self.emit_return();
+        let mut state = self.functions.pop().expect("there's always a function being compiled");
+        state.function.upvalue_count = state.upvalues.len();
+        (state.function, state.upvalues)
+    }
+
+    // ---- expressions ----
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    /// Compile an expression made of operators at least as tight
+    /// as `precedence`.
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        if !self.nest() {
+            return;
+        }
+        self.advance();
+        let Some(prefix) = rule(self.previous.token_type).prefix else {
+            self.error("Expect expression.");
+            self.nesting -= 1;
+            return;
+        };
+
+        // only an assignment can be the target of an assignment, so
+        // in `a + b ← c` the `b` isn't allowed to take the `←`
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix(self, can_assign);
+
+        while precedence <= rule(self.current.token_type).precedence {
+            self.advance();
+            let infix = rule(self.previous.token_type).infix.expect("a token with a precedence has an infix rule");
+            infix(self, can_assign);
+        }
+
+        if can_assign && self.match_type(TokenType::Assignment) {
+            self.error("Invalid assignment target.");
+        }
+        self.nesting -= 1;
+    }
+
+    fn number(&mut self, _can_assign: bool) {
+        let Literal::Number(number) = self.previous.literal else {
+            unreachable!("the scanner gives every number a literal");
+        };
+        self.emit_constant(Constant::Number(number));
+    }
+
+    fn string(&mut self, _can_assign: bool) {
+        let Literal::String(string) = &self.previous.literal else {
+            unreachable!("the scanner gives every string a literal");
+        };
+        self.emit_constant(Constant::String(string.clone()));
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        match self.previous.token_type {
+            TokenType::False => self.emit_op(OpCode::False),
+            TokenType::True => self.emit_op(OpCode::True),
+            _ => self.emit_op(OpCode::Nil),
+        }
+    }
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after expression.");
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous.clone();
+        self.parse_precedence(Precedence::Unary);
+        match operator.token_type {
+            TokenType::Not => self.emit_op_at(OpCode::Not, &operator),
+            _ => self.emit_op_at(OpCode::Negate, &operator),
+        }
+    }
+
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous.clone();
+        self.parse_precedence(rule(operator.token_type).precedence.next());
+        let op_code = match operator.token_type {
+            TokenType::EqualityTest => OpCode::Equal,
+            TokenType::NotEqual => {
+                self.emit_op_at(OpCode::Equal, &operator);
+                OpCode::Not
+            }
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            _ => OpCode::Divide,
+        };
+        self.emit_op_at(op_code, &operator);
+    }
+
+    /// `and` skips the right operand if the left one is falsey,
+    /// leaving the left one as the result.
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::And);
+        self.patch_jump(end_jump);
+    }
+
+    /// `or` skips the right operand if the left one is truthy,
+    /// leaving the left one as the result.
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(else_jump);
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    fn call(&mut self, _can_assign: bool) {
+        let argument_count = self.argument_list();
+        self.emit_op(OpCode::Call);
+        self.emit_byte(argument_count);
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut count = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.expression();
+                if count == MAX_ARGUMENTS {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                count += 1;
+                if !self.match_type(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.");
+        count.min(MAX_ARGUMENTS) as u8
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        let name = self.previous.clone();
+        self.named_variable(&name, can_assign);
+    }
+
+    /// Compile a read of the variable `name`, or a write if an
+    /// assignment follows and is allowed here.
+    fn named_variable(&mut self, name: &Token, can_assign: bool) {
+        let innermost = self.functions.len() - 1;
+        let (get, set, operand) = if let Some(slot) = self.resolve_local(innermost, name) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot as usize)
+        } else if let Some(index) = self.resolve_upvalue(innermost, name) {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, index as usize)
+        } else {
+            (OpCode::GetGlobal, OpCode::SetGlobal, self.identifier_constant(name))
+        };
+
+        // slots and upvalue indexes always fit in the one byte, so
+        // only a global's name can need the wide form
+        if can_assign && self.match_type(TokenType::Assignment) {
+            self.expression();
+            self.emit_with_constant_at(set, operand, name);
+        } else {
+            self.emit_with_constant(get, operand);
+        }
+    }
+
+    /// A property access, a method call or a field assignment.
+    fn dot(&mut self, can_assign: bool) {
+        self.consume(TokenType::Identifier, "Expect property name after '.'.");
+        let name = self.previous.clone();
+        let constant = self.identifier_constant(&name);
+
+        if can_assign && self.match_type(TokenType::Assignment) {
+            self.expression();
+            self.emit_with_constant_at(OpCode::SetProperty, constant, &name);
+        } else if self.match_type(TokenType::LeftParen) {
+            // calling a method straight away doesn't need the bound
+            // method that reading the property would make
+            let argument_count = self.argument_list();
+            if self.emit_with_constant_at(OpCode::Invoke, constant, &name) {
+                self.chunk().write(argument_count, name.line);
+            }
+        } else {
+            self.emit_with_constant(OpCode::GetProperty, constant);
+        }
+    }
+
+    /// `this` is the local in slot 0 of a method.
+    fn this(&mut self, _can_assign: bool) {
+        if self.classes.is_empty() {
+            self.error("Can't use 'this' outside of a class.");
+            return;
+        }
+        self.variable(false);
+    }
+
+    /// `super.method`, which finds the method on the superclass
+    /// (captured as the variable `super`) and binds it to `this`.
+    fn super_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => self.error("Can't use 'super' in a class with no superclass."),
+            Some(_) => {}
+        }
+        let keyword = self.previous.clone();
+        self.consume(TokenType::Dot, "Expect '.' after 'super'.");
+        self.consume(TokenType::Identifier, "Expect superclass method name.");
+        let name = self.previous.clone();
+        let constant = self.identifier_constant(&name);
+
+        let this = Token { lexeme: String::from("this"), ..keyword.clone() };
+        let superclass = Token { lexeme: String::from("super"), ..keyword };
+        self.named_variable(&this, false);
+        if self.match_type(TokenType::LeftParen) {
+            let argument_count = self.argument_list();
+            self.named_variable(&superclass, false);
+            if self.emit_with_constant_at(OpCode::SuperInvoke, constant, &name) {
+                self.chunk().write(argument_count, name.line);
+            }
+        } else {
+            self.named_variable(&superclass, false);
+            self.emit_with_constant(OpCode::GetSuper, constant);
+        }
+    }
+
+    // ---- variables ----
+
+    /// Consume a variable's name and declare it.  Gives back the
+    /// constant holding the name if it's a global.
+    fn parse_variable(&mut self, message: &str) -> usize {
+        self.consume(TokenType::Identifier, message);
+        self.declare_variable();
+        if self.state().scope_depth > 0 {
+            return 0;
+        }
+        let name = self.previous.clone();
+        self.identifier_constant(&name)
+    }
+
+    /// Add the local just named to the current scope.  Globals
+    /// aren't declared; they're found by name when they're used.
+    fn declare_variable(&mut self) {
+        let scope_depth = self.state().scope_depth;
+        if scope_depth == 0 {
+            return;
+        }
+
+        let name = self.previous.lexeme.clone();
+        let already_declared = self
+            .state()
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|depth| depth >= scope_depth))
+            .any(|local| local.name == name);
+        if already_declared {
+            self.error("Already a variable with this name in this scope.");
+        }
+
+        self.add_local(&name);
+    }
+
+    fn add_local(&mut self, name: &str) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.state_mut().locals.push(Local { name: name.to_string(), depth: None, is_captured: false });
+    }
+
+    /// Make the variable just declared ready to use: a local is
+    /// already in its slot, and a global gets defined.
+    fn define_variable(&mut self, global: usize) {
+        if self.state().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        self.emit_with_constant(OpCode::DefineGlobal, global);
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.state_mut();
+        if state.scope_depth == 0 {
+            return;
+        }
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(state.scope_depth);
+        }
+    }
+
+    /// The stack slot of the local `name`, if it's a local of the
+    /// `function`th function being compiled.
+    fn resolve_local(&mut self, function: usize, name: &Token) -> Option<u8> {
+        let (slot, local) = self.functions[function]
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| local.name == name.lexeme)?;
+        if local.depth.is_none() {
+            self.error("Can't read local variable in its own initializer.");
+        }
+        Some(slot as u8)
+    }
+
+    /// The index of the upvalue for `name` in the `function`th
+    /// function being compiled, if `name` is a local of a function
+    /// around it.
+    ///
+    /// Each function in between captures the variable too, so that
+    /// it's there to pass on when the inner closure is made.
+    fn resolve_upvalue(&mut self, function: usize, name: &Token) -> Option<u8> {
+        let enclosing = function.checked_sub(1)?;
+        if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, Upvalue { index: slot, is_local: true }));
+        }
+        let index = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(function, Upvalue { index, is_local: false }))
+    }
+
+    /// Capture `upvalue` in the `function`th function, unless it
+    /// already has, and give back its index.
+    fn add_upvalue(&mut self, function: usize, upvalue: Upvalue) -> u8 {
+        let upvalues = &self.functions[function].upvalues;
+        if let Some(index) = upvalues.iter().position(|existing| *existing == upvalue) {
+            return index as u8;
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+        let upvalues = &mut self.functions[function].upvalues;
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    /// The constant holding the name of a global.
+    fn identifier_constant(&mut self, name: &Token) -> usize {
+        self.chunk().add_constant(Constant::String(name.lexeme.clone()))
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    /// Leave a block, popping its locals off the stack.  Captured
+    /// ones are moved into their upvalues instead.
+    fn end_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+        let scope_depth = self.state().scope_depth;
+        while let Some(is_captured) = self
+            .state()
+            .locals
+            .last()
+            .filter(|local| local.depth.is_none_or(|depth| depth > scope_depth))
+            .map(|local| local.is_captured)
+        {
+            self.emit_op(if is_captured { OpCode::CloseUpvalue } else { OpCode::Pop });
+            self.state_mut().locals.pop();
+        }
+    }
+
+    // ---- emitting code ----
+
+    fn state(&self) -> &FunctionState {
+        self.functions.last().expect("there's always a function being compiled")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().expect("there's always a function being compiled")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+
+    /// Add a byte, from the line of the token just consumed.
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.previous.line;
+        self.chunk().write(byte, line);
+    }
+
+    /// Add an instruction, from the token just consumed.
+    fn emit_op(&mut self, op_code: OpCode) {
+        let span = self.previous.span;
+        self.chunk().mark_span(span);
+        self.emit_byte(op_code as u8);
+    }
+
+    /// Add an instruction from an earlier token, which is where a
+    /// runtime error in it is reported, as the tree-walker does.
+    fn emit_op_at(&mut self, op_code: OpCode, token: &Token) {
+        self.chunk().mark_span(token.span);
+        self.chunk().write_op(op_code, token.line);
+    }
+
+    /// Add `op_code` with the constant `index` as its operand, and
+    /// say whether there was room for it in the pool.
+    fn emit_with_constant(&mut self, op_code: OpCode, index: usize) -> bool {
+        let token = self.previous.clone();
+        self.emit_with_constant_at(op_code, index, &token)
+    }
+
+    /// `emit_with_constant`, from an earlier token.
+    fn emit_with_constant_at(&mut self, op_code: OpCode, index: usize, token: &Token) -> bool {
+        self.chunk().mark_span(token.span);
+        let written = self.chunk().write_with_constant(op_code, index, token.line).is_some();
+        if !written {
+            self.error("Too many constants in one chunk.");
+        }
+        written
+    }
+
+    fn emit_constant(&mut self, constant: Constant) {
+        let (line, span) = (self.previous.line, self.previous.span);
+        self.chunk().mark_span(span);
+        if self.chunk().write_constant(constant, line).is_none() {
+            self.error("Too many constants in one chunk.");
+        }
+    }
+
+    /// Falling off the end of a function gives `nil`, except in an
+    /// initializer, which gives `this`.
+    fn emit_return(&mut self) {
+        if self.state().function_type == FunctionType::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    /// Add a jump with a placeholder offset, and give back where the
+    /// offset is so `patch_jump` can fill it in.
+    fn emit_jump(&mut self, op_code: OpCode) -> usize {
+        self.emit_op(op_code);
+        self.emit_byte(0xff);
+        self.emit_byte(0xff);
+        self.chunk().code.len() - 2
+    }
+
+    /// Point the jump at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) {
+        let jump = self.chunk().code.len() - offset - 2;
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error("Too much code to jump over.");
+            return;
+        };
+        self.chunk().code[offset..offset + 2].copy_from_slice(&jump.to_be_bytes());
+    }
+
+    /// Add a jump back to `loop_start`.
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::Loop);
+        let offset = self.chunk().code.len() - loop_start + 2;
+        let Ok(offset) = u16::try_from(offset) else {
+            self.error("Loop body too large.");
+            return;
+        };
+        let [high, low] = offset.to_be_bytes();
+        self.emit_byte(high);
+        self.emit_byte(low);
+    }
+
+    // ---- tokens ----
+
+    fn advance(&mut self) {
+        let next = self.scanner.next_token();
+        self.previous = std::mem::replace(&mut self.current, next);
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        self.current.token_type == token_type
+    }
+
+    /// If the next token is `token_type`, consume it and return `true`.
+    fn match_type(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    /// Consume the next token if it's `token_type`, otherwise report
+    /// `message`.
+    fn consume(&mut self, token_type: TokenType, message: &str) {
+        if self.check(token_type) {
+            self.advance();
+        } else {
+            self.error_at_current(message);
+        }
+    }
+
+    /// Go one level deeper, or report it if that's too deep.  Every
+    /// `true` has to be matched by a `self.nesting -= 1`.
+    fn nest(&mut self) -> bool {
+        if self.nesting >= MAX_NESTING {
+            self.too_deep();
+            return false;
+        }
+        self.nesting += 1;
+        true
+    }
+
+    /// Report that the code is nested too deeply, and skip the rest,
+    /// since it's all too deep too.
+    fn too_deep(&mut self) {
+        self.error_at_current("Too much nesting.");
+        while !self.check(TokenType::EOF) {
+            self.advance();
+        }
+        self.skipped_rest = true;
+    }
+
+    /// Report a problem with the token just consumed.
+    fn error(&mut self, message: &str) {
+        let token = self.previous.clone();
+        self.error_at(&token, message);
+    }
+
+    /// Report a problem with the next token.
+    fn error_at_current(&mut self, message: &str) {
+        let token = self.current.clone();
+        self.error_at(&token, message);
+    }
+
+    fn error_at(&mut self, token: &Token, message: &str) {
+        if self.panic_mode || self.skipped_rest {
+            return;
+        }
+        self.panic_mode = true;
+        self.errors.push(CompileError::new(token, message));
+    }
+}
+
+
+/// The Pratt parsing rule for `token_type`.
+fn rule<'a>(token_type: TokenType) -> ParseRule<'a> {
+    let (prefix, infix, precedence): (Option<ParseFn<'a>>, Option<ParseFn<'a>>, Precedence) = match token_type {
+        TokenType::LeftParen => (Some(Compiler::grouping), Some(Compiler::call), Precedence::Call),
+        TokenType::Dot => (None, Some(Compiler::dot), Precedence::Call),
+        TokenType::Minus => (None, Some(Compiler::binary), Precedence::Term),
+        TokenType::Plus => (None, Some(Compiler::binary), Precedence::Term),
+        TokenType::Slash | TokenType::Star => (None, Some(Compiler::binary), Precedence::Factor),
+        TokenType::Not | TokenType::Negative => (Some(Compiler::unary), None, Precedence::None),
+        TokenType::NotEqual | TokenType::EqualityTest => (None, Some(Compiler::binary), Precedence::Equality),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+            (None, Some(Compiler::binary), Precedence::Comparison)
+        }
+        TokenType::Identifier => (Some(Compiler::variable), None, Precedence::None),
+        TokenType::String => (Some(Compiler::string), None, Precedence::None),
+        TokenType::Number => (Some(Compiler::number), None, Precedence::None),
+        TokenType::And => (None, Some(Compiler::and), Precedence::And),
+        TokenType::Or => (None, Some(Compiler::or), Precedence::Or),
+        TokenType::False | TokenType::True | TokenType::Nil => (Some(Compiler::literal), None, Precedence::None),
+        TokenType::This => (Some(Compiler::this), None, Precedence::None),
+        TokenType::Super => (Some(Compiler::super_), None, Precedence::None),
+        _ => (None, None, Precedence::None),
+    };
+    ParseRule { prefix, infix, precedence }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytecode::disassembler::disassemble_function;
+
+    fn messages(source: &str) -> Vec<String> {
+        compile(source).unwrap_err().into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn test_compiles_glyph_operators() {
+        let function = compile("var a ← ⁻1; print ¬(a ≤ 2) ≠ (a ≟ 3);").unwrap();
+
+        assert_eq!(
+            disassemble_function(&function),
+            "\
+== <script> ==
+0000    1 OP_CONSTANT         1 '1'
+0002    | OP_NEGATE
+0003    | OP_DEFINE_GLOBAL    0 'a'
+0005    | OP_GET_GLOBAL       0 'a'
+0007    | OP_CONSTANT         2 '2'
+0009    | OP_LESS_EQUAL
+0010    | OP_NOT
+0011    | OP_GET_GLOBAL       0 'a'
+0013    | OP_CONSTANT         3 '3'
+0015    | OP_EQUAL
+0016    | OP_EQUAL
+0017    | OP_NOT
+0018    | OP_PRINT
+0019    | OP_NIL
+0020    | OP_RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_locals_live_in_stack_slots() {
+        let function = compile("fun f(a) {\n  var b ← a;\n  while (b) b ← a and b;\n}").unwrap();
+
+        assert_eq!(
+            disassemble_function(&function),
+            "\
+== <script> ==
+0000    4 OP_CLOSURE          1 '<fn f>'
+0002    | OP_DEFINE_GLOBAL    0 'f'
+0004    | OP_NIL
+0005    | OP_RETURN
+
+== <fn f> ==
+0000    2 OP_GET_LOCAL        1
+0002    3 OP_GET_LOCAL        2
+0004    | OP_JUMP_IF_FALSE    4 -> 22
+0007    | OP_POP
+0008    | OP_GET_LOCAL        1
+0010    | OP_JUMP_IF_FALSE   10 -> 16
+0013    | OP_POP
+0014    | OP_GET_LOCAL        2
+0016    | OP_SET_LOCAL        2
+0018    | OP_POP
+0019    | OP_LOOP            19 -> 2
+0022    | OP_POP
+0023    4 OP_NIL
+0024    | OP_RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_captured_locals_become_upvalues() {
+        let function = compile("fun outer() {\n  var x ← 1;\n  fun inner() { x ← 2; }\n}").unwrap();
+
+        assert_eq!(
+            disassemble_function(&function).split("\n\n").skip(1).collect::<Vec<&str>>(),
+            [
+                "\
+== <fn outer> ==
+0000    2 OP_CONSTANT         0 '1'
+0002    3 OP_CLOSURE          1 '<fn inner>'
+0004    |                     local 1
+0006    4 OP_NIL
+0007    | OP_RETURN",
+                "\
+== <fn inner> ==
+0000    3 OP_CONSTANT         0 '2'
+0002    | OP_SET_UPVALUE      0
+0004    | OP_POP
+0005    | OP_NIL
+0006    | OP_RETURN
+",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_the_same_mistakes_as_the_tree_walker() {
+        assert_eq!(messages("print 1 + ;"), vec!["Error at ';': Expect expression."]);
+        assert_eq!(messages("1 + 2 ← 3;"), vec!["Error at '←': Invalid assignment target."]);
+        assert_eq!(messages("return 1;"), vec!["Error at 'return': Can't return from top-level code."]);
+        assert_eq!(messages("{ var a ← a; }"), vec!["Error at 'a': Can't read local variable in its own initializer."]);
+        assert_eq!(messages("class A { init() { return 1; } }"), vec!["Error at 'return': Can't return a value from an initializer."]);
+        assert_eq!(messages("class A < A {}"), vec!["Error at 'A': A class can't inherit from itself."]);
+        assert_eq!(messages("print this;"), vec!["Error at 'this': Can't use 'this' outside of a class."]);
+        assert_eq!(
+            messages("var ← 1;\nprint (;"),
+            vec!["Error at '←': Expect variable name.", "Error at ';': Expect expression."]
+        );
+    }
+
+    #[test]
+    fn test_functions_nested_as_deep_as_a_file_can_hold_compile() {
+        let nested = |depth: usize| format!("{}{}", "fun f() { ".repeat(depth), "}".repeat(depth));
+
+        let (at_the_limit, too_deep) = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || {
+                let script = compile(&nested(MAX_FUNCTION_NESTING)).unwrap();
+                let loaded = bytecode::loxc::load(&bytecode::loxc::save(&script));
+                (loaded == Ok(script), messages(&nested(MAX_FUNCTION_NESTING + 1)))
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert!(at_the_limit);
+        assert_eq!(too_deep, vec!["Error at '(': Too much nesting."]);
+    }
+}
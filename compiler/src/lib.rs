@@ -0,0 +1,18 @@
+//! Contains the single-pass compiler, which turns source code
+//! straight into bytecode without building a syntax tree.
+//!
+//! - `compiler_struct` has the `Compiler` and the `compile`
+//!   function.
+//! - `precedence` has the `Precedence` levels the compiler parses
+//!   expressions by.
+//! - `compile_error` has the `CompileError` struct.
+//! - `optimizer` has the `optimize` function, which makes the
+//!   compiled code smaller and faster.
+//! - `opt_level` has the `OptLevel` enum, for how much to optimize.
+
+
+pub mod compiler_struct;
+pub mod precedence;
+pub mod compile_error;
+pub mod optimizer;
+pub mod opt_level;
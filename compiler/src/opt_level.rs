@@ -0,0 +1,26 @@
+//! Contains the `OptLevel` enum.
+
+
+/// How much the compiled code is optimized before it runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptLevel {
+    /// Not at all: the code is just what the compiler wrote, which
+    /// follows the source most closely.
+    #[default]
+    O0,
+
+    /// Fold constants and tidy the code up with peephole passes
+    /// (see `optimizer`).
+    O1,
+}
+
+impl OptLevel {
+    /// The level a command line flag asks for: `-O0` or `-O1`.
+    pub fn from_flag(flag: &str) -> Option<OptLevel> {
+        match flag {
+            "-O0" => Some(OptLevel::O0),
+            "-O1" => Some(OptLevel::O1),
+            _ => None,
+        }
+    }
+}
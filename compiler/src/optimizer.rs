@@ -0,0 +1,655 @@
+//! Contains the `optimize` function, which runs between compiling
+//! and running.
+//!
+//! At `-O1` it takes the compiled code apart into a list of
+//! instructions, with jumps pointing at instructions instead of
+//! offsets, rewrites the list until nothing more changes, then
+//! puts the code back together:
+//! - arithmetic, comparisons and `¬` on literals are worked out
+//!   ahead of time, as are `⁻` on a number and `+` on two strings;
+//! - a jump to the next instruction goes, and so does a jump on a
+//!   literal, whose way is already known;
+//! - code nothing can reach goes;
+//! - a value pushed only to be popped straight away isn't pushed,
+//!   and pops in a row become one `OP_POP_N`;
+//! - `¬¬` goes where what's under it is a boolean already, or is
+//!   only tested for truthiness by an `if` or a loop.
+//!
+//! No rewrite spans an instruction something jumps to, other than
+//! the first one, since the code jumping in would skip part of it.
+
+use std::rc::Rc;
+
+use bytecode::chunk::{Chunk, MAX_CONSTANTS};
+use bytecode::constant::Constant;
+use bytecode::disassembler::instruction_size;
+use bytecode::function::Function;
+use bytecode::op_code::OpCode;
+use token::span::Span;
+
+use crate::opt_level::OptLevel;
+
+
+/// Optimize the compiled `function`, and every function declared
+/// in it, at `level`.  Whatever the level, the code does just what
+/// it did before.
+pub fn optimize(function: Function, level: OptLevel) -> Function {
+    match level {
+        OptLevel::O0 => function,
+        OptLevel::O1 => optimize_function(&function),
+    }
+}
+
+
+/// One instruction taken out of a chunk.
+#[derive(Debug, Clone)]
+struct Instruction {
+    /// `OP_CONSTANT_LONG` is kept as `OP_CONSTANT`, and put back
+    /// when the constant's index needs it.
+    op_code: OpCode,
+    /// The constant it uses, for the instructions that use one.
+    constant: Option<usize>,
+    /// Its other operands, after the constant.  Jumps have `target`
+    /// instead.
+    operands: Vec<u8>,
+    /// The index of the instruction a jump lands on.
+    target: Option<usize>,
+    line: usize,
+    span: Option<Span>,
+    /// Set by a rewrite; `compact` takes it out of the list.
+    removed: bool,
+}
+
+
+/// A value known from the code alone.
+#[derive(Debug, Clone, PartialEq)]
+enum Known {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl Known {
+    fn is_truthy(&self) -> bool {
+        !matches!(self, Known::Nil | Known::Bool(false))
+    }
+}
+
+
+fn optimize_function(function: &Function) -> Function {
+    let constants = function.chunk.constants().iter().map(|constant| match constant {
+        Constant::Function(inner) => Constant::Function(Rc::new(optimize_function(inner))),
+        constant => constant.clone(),
+    }).collect();
+    let mut chunk = Chunk::from_parts(Vec::new(), constants, Vec::new());
+
+    let mut instructions = decode(&function.chunk);
+    loop {
+        let rewritten = rewrite_all(&mut instructions, &mut chunk);
+        compact(&mut instructions);
+        let unreachable = remove_unreachable(&mut instructions);
+        compact(&mut instructions);
+        if !rewritten && !unreachable {
+            break;
+        }
+    }
+    drop_unused_constants(&mut instructions, &mut chunk);
+
+    // Only a fold that needs a long constant can make the code
+    // longer, and a jump too long to encode is unlikely enough that
+    // leaving the function as it was is fine.
+    match encode(&instructions, chunk) {
+        Some(chunk) => Function { chunk, ..function.clone() },
+        None => function.clone(),
+    }
+}
+
+
+// ---- taking the code apart and putting it back ----
+
+/// Whether `op_code`'s first operand is a constant.
+fn uses_constant(op_code: OpCode) -> bool {
+    matches!(
+        op_code,
+        OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::Class
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::Method
+            | OpCode::Invoke
+            | OpCode::Closure
+            | OpCode::GetSuper
+            | OpCode::SuperInvoke
+    )
+}
+
+fn decode(chunk: &Chunk) -> Vec<Instruction> {
+    let lines: Vec<usize> = chunk.lines().iter().flat_map(|run| std::iter::repeat_n(run.line, run.count)).collect();
+    let mut instructions = Vec::new();
+    // The index of the instruction at each offset, and where each
+    // jump lands, to turn one into the other once they're known.
+    let mut index_at = vec![0; chunk.code.len() + 1];
+    let mut jumps = Vec::new();
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let size = instruction_size(chunk, offset);
+        // an `OP_WIDE` goes into the constant of the instruction
+        // it widens
+        let (at, wide) = match OpCode::try_from(chunk.code[offset]) {
+            Ok(OpCode::Wide) => (offset + 3, (chunk.read_u16(offset + 1) as usize) << 8),
+            _ => (offset, 0),
+        };
+        let op_code = OpCode::try_from(chunk.code[at]).expect("the compiler only writes real instructions");
+        let operands = &chunk.code[at + 1..offset + size];
+        let mut instruction = Instruction {
+            op_code,
+            constant: None,
+            operands: Vec::new(),
+            target: None,
+            line: lines[offset],
+            span: chunk.span_at(offset),
+            removed: false,
+        };
+        match op_code {
+            OpCode::ConstantLong => {
+                instruction.op_code = OpCode::Constant;
+                instruction.constant = Some(chunk.read_u24(offset + 1));
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                jumps.push((instructions.len(), offset + 3 + chunk.read_u16(offset + 1) as usize));
+            }
+            OpCode::Loop => jumps.push((instructions.len(), offset + 3 - chunk.read_u16(offset + 1) as usize)),
+            _ if uses_constant(op_code) => {
+                instruction.constant = Some(wide | operands[0] as usize);
+                instruction.operands = operands[1..].to_vec();
+            }
+            _ => instruction.operands = operands.to_vec(),
+        }
+        index_at[offset] = instructions.len();
+        instructions.push(instruction);
+        offset += size;
+    }
+    index_at[chunk.code.len()] = instructions.len();
+
+    for (index, target) in jumps {
+        instructions[index].target = Some(index_at[target]);
+    }
+    instructions
+}
+
+fn encoded_size(instruction: &Instruction) -> usize {
+    match (instruction.op_code, instruction.constant) {
+        (OpCode::Constant, Some(constant)) if constant > u8::MAX as usize => 4,
+        (_, Some(constant)) if constant > u8::MAX as usize => 4 + instruction.operands.len(),
+        (OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop, _) => 3,
+        (_, constant) => 1 + constant.map_or(0, |_| 1) + instruction.operands.len(),
+    }
+}
+
+/// Write `instructions` into `chunk`, which already has the
+/// constants.  Gives back `None` if a jump has become too long.
+fn encode(instructions: &[Instruction], mut chunk: Chunk) -> Option<Chunk> {
+    let mut offsets = Vec::with_capacity(instructions.len() + 1);
+    let mut offset = 0;
+    for instruction in instructions {
+        offsets.push(offset);
+        offset += encoded_size(instruction);
+    }
+    offsets.push(offset);
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let line = instruction.line;
+        if let Some(span) = instruction.span {
+            chunk.mark_span(span);
+        }
+        match (instruction.op_code, instruction.constant, instruction.target) {
+            (OpCode::Constant, Some(constant), _) if constant > u8::MAX as usize => {
+                chunk.write_op(OpCode::ConstantLong, line);
+                for byte in &(constant as u32).to_be_bytes()[1..] {
+                    chunk.write(*byte, line);
+                }
+            }
+            (op_code, _, Some(target)) => {
+                let after = offsets[index] + 3;
+                let jump = match op_code {
+                    OpCode::Loop => after - offsets[target],
+                    _ => offsets[target] - after,
+                };
+                chunk.write_op(op_code, line);
+                for byte in u16::try_from(jump).ok()?.to_be_bytes() {
+                    chunk.write(byte, line);
+                }
+            }
+            (op_code, Some(constant), None) => {
+                chunk.write_with_constant(op_code, constant, line)?;
+                for byte in &instruction.operands {
+                    chunk.write(*byte, line);
+                }
+            }
+            (op_code, None, None) => {
+                chunk.write_op(op_code, line);
+                for byte in &instruction.operands {
+                    chunk.write(*byte, line);
+                }
+            }
+        }
+    }
+    Some(chunk)
+}
+
+/// Take the removed instructions out of the list, moving a jump to
+/// one of them on to the next one left.
+fn compact(instructions: &mut Vec<Instruction>) {
+    let mut new_index = Vec::with_capacity(instructions.len() + 1);
+    let mut kept = 0;
+    for instruction in instructions.iter() {
+        new_index.push(kept);
+        if !instruction.removed {
+            kept += 1;
+        }
+    }
+    new_index.push(kept);
+
+    instructions.retain(|instruction| !instruction.removed);
+    for instruction in instructions.iter_mut() {
+        if let Some(target) = instruction.target.as_mut() {
+            *target = new_index[*target];
+        }
+    }
+}
+
+/// Renumber the constants the code still uses, in the same order,
+/// and drop the rest.  Keeping the order means an index that fit
+/// in a byte still does.
+fn drop_unused_constants(instructions: &mut [Instruction], chunk: &mut Chunk) {
+    let mut used = vec![false; chunk.constants().len()];
+    for constant in instructions.iter().filter_map(|instruction| instruction.constant) {
+        used[constant] = true;
+    }
+    let mut new_index = vec![0; chunk.constants().len()];
+    let mut constants = Vec::new();
+    for (index, constant) in chunk.constants().iter().enumerate() {
+        if used[index] {
+            new_index[index] = constants.len();
+            constants.push(constant.clone());
+        }
+    }
+    *chunk = Chunk::from_parts(std::mem::take(&mut chunk.code), constants, chunk.lines().to_vec());
+    for instruction in instructions.iter_mut() {
+        if let Some(constant) = instruction.constant.as_mut() {
+            *constant = new_index[*constant];
+        }
+    }
+}
+
+
+// ---- the rewrites ----
+
+/// Remove the instructions no path from the start gets to.
+fn remove_unreachable(instructions: &mut [Instruction]) -> bool {
+    let mut reached = vec![false; instructions.len()];
+    let mut to_visit = vec![0];
+    while let Some(index) = to_visit.pop() {
+        if index >= instructions.len() || reached[index] {
+            continue;
+        }
+        reached[index] = true;
+        to_visit.extend(instructions[index].target);
+        if !matches!(instructions[index].op_code, OpCode::Jump | OpCode::Loop | OpCode::Return) {
+            to_visit.push(index + 1);
+        }
+    }
+
+    let mut removed = false;
+    for (instruction, reached) in instructions.iter_mut().zip(reached) {
+        if !reached {
+            instruction.removed = true;
+            removed = true;
+        }
+    }
+    removed
+}
+
+/// Go through the code once, rewriting everything that can be.
+fn rewrite_all(instructions: &mut [Instruction], chunk: &mut Chunk) -> bool {
+    let mut is_target = vec![false; instructions.len() + 1];
+    for target in instructions.iter().filter_map(|instruction| instruction.target) {
+        is_target[target] = true;
+    }
+
+    let mut rewritten = false;
+    let mut index = 0;
+    while index < instructions.len() {
+        // After a rewrite, look at the same place again, since the
+        // result might be rewritten too (as in `1 + 2 + 3`).
+        if !instructions[index].removed && rewrite(instructions, &is_target, chunk, index) {
+            rewritten = true;
+        } else {
+            index += 1;
+        }
+    }
+    rewritten
+}
+
+/// The first instruction left after `index`.
+fn next(instructions: &[Instruction], index: usize) -> Option<usize> {
+    (index + 1..instructions.len()).find(|&next| !instructions[next].removed)
+}
+
+/// The first instruction left at or after `index`, which is where
+/// a jump to `index` ends up.
+fn landing(instructions: &[Instruction], index: usize) -> Option<usize> {
+    (index..instructions.len()).find(|&next| !instructions[next].removed)
+}
+
+fn op_code_at(instructions: &[Instruction], index: Option<usize>) -> Option<OpCode> {
+    index.map(|index| instructions[index].op_code)
+}
+
+fn previous(instructions: &[Instruction], index: usize) -> Option<usize> {
+    (0..index).rev().find(|&previous| !instructions[previous].removed)
+}
+
+/// Try each rewrite that starts at the instruction at `first`.
+fn rewrite(instructions: &mut [Instruction], is_target: &[bool], chunk: &mut Chunk, first: usize) -> bool {
+    // The instructions after it that a rewrite can take in, which
+    // stops at the first one something jumps to.
+    let second = next(instructions, first).filter(|&second| !is_target[second]);
+    let third = second.and_then(|second| next(instructions, second)).filter(|&third| !is_target[third]);
+
+    if let (Some(second), Some(third)) = (second, third) {
+        let left = known(&instructions[first], chunk);
+        let right = known(&instructions[second], chunk);
+        if let (Some(left), Some(right)) = (left, right) {
+            if let Some(result) = fold_binary(instructions[third].op_code, left, right) {
+                if load(&mut instructions[first], result, chunk) {
+                    instructions[second].removed = true;
+                    instructions[third].removed = true;
+                    return true;
+                }
+            }
+        }
+    }
+
+    if let Some(second) = second {
+        if let Some(value) = known(&instructions[first], chunk) {
+            if instructions[second].op_code == OpCode::JumpIfFalse {
+                // The condition is a literal, so which way it goes
+                // is known.  The value stays on the stack either way.
+                match value.is_truthy() {
+                    true => instructions[second].removed = true,
+                    false => instructions[second].op_code = OpCode::Jump,
+                }
+                return true;
+            }
+            if let Some(result) = fold_unary(instructions[second].op_code, value) {
+                if load(&mut instructions[first], result, chunk) {
+                    instructions[second].removed = true;
+                    return true;
+                }
+            }
+        }
+    }
+
+    match op_code_at(instructions, Some(first)) {
+        // A jump to where running would carry on anyway.
+        Some(OpCode::Jump | OpCode::JumpIfFalse) => {
+            let target = instructions[first].target.and_then(|target| landing(instructions, target));
+            if target.is_some() && target == next(instructions, first) {
+                instructions[first].removed = true;
+                return true;
+            }
+        }
+        // A value that's popped as soon as it's pushed.
+        Some(OpCode::Constant | OpCode::Nil | OpCode::True | OpCode::False | OpCode::GetLocal | OpCode::GetUpvalue) => {
+            if let Some(second) = second {
+                match instructions[second].op_code {
+                    OpCode::Pop => {
+                        instructions[first].removed = true;
+                        instructions[second].removed = true;
+                        return true;
+                    }
+                    OpCode::PopN => {
+                        instructions[first].removed = true;
+                        let count = pop_count(&instructions[second]) - 1;
+                        set_pop_count(&mut instructions[second], count);
+                        return true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some(OpCode::Pop | OpCode::PopN) => {
+            if let Some(second) = second.filter(|&second| matches!(op_code_at(instructions, Some(second)), Some(OpCode::Pop | OpCode::PopN))) {
+                let count = pop_count(&instructions[first]) + pop_count(&instructions[second]);
+                if count <= u8::MAX as usize {
+                    set_pop_count(&mut instructions[first], count);
+                    instructions[second].removed = true;
+                    return true;
+                }
+            }
+        }
+        Some(OpCode::Not) if op_code_at(instructions, second) == Some(OpCode::Not) => {
+            let (Some(second), third) = (second, third) else {
+                return false;
+            };
+            // `¬¬` on a boolean gives it back.
+            let on_boolean = !is_target[first]
+                && matches!(
+                    op_code_at(instructions, previous(instructions, first)),
+                    Some(
+                        OpCode::Not
+                            | OpCode::Equal
+                            | OpCode::Greater
+                            | OpCode::GreaterEqual
+                            | OpCode::Less
+                            | OpCode::LessEqual
+                            | OpCode::True
+                            | OpCode::False
+                    )
+                );
+            // A condition whose value is popped straight after the
+            // jump, whichever way it goes, is only tested for
+            // truthiness, which `¬¬` doesn't change.
+            let tested = third.filter(|&third| instructions[third].op_code == OpCode::JumpIfFalse).is_some_and(|third| {
+                let target = instructions[third].target.and_then(|target| landing(instructions, target));
+                op_code_at(instructions, next(instructions, third)) == Some(OpCode::Pop) && op_code_at(instructions, target) == Some(OpCode::Pop)
+            });
+            if on_boolean || tested {
+                instructions[first].removed = true;
+                instructions[second].removed = true;
+                return true;
+            }
+        }
+        _ => {}
+    }
+    false
+}
+
+/// The value `instruction` pushes, if it only pushes a literal.
+fn known(instruction: &Instruction, chunk: &Chunk) -> Option<Known> {
+    match instruction.op_code {
+        OpCode::Nil => Some(Known::Nil),
+        OpCode::True => Some(Known::Bool(true)),
+        OpCode::False => Some(Known::Bool(false)),
+        OpCode::Constant => match &chunk.constants()[instruction.constant?] {
+            Constant::Number(number) => Some(Known::Number(*number)),
+            Constant::String(string) => Some(Known::String(string.clone())),
+            Constant::Function(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Work out `op_code` on two literals, where it can't fail.
+fn fold_binary(op_code: OpCode, left: Known, right: Known) -> Option<Known> {
+    let result = match (op_code, left, right) {
+        (OpCode::Equal, left, right) => Known::Bool(left == right),
+        (OpCode::Greater, Known::Number(left), Known::Number(right)) => Known::Bool(left > right),
+        (OpCode::GreaterEqual, Known::Number(left), Known::Number(right)) => Known::Bool(left >= right),
+        (OpCode::Less, Known::Number(left), Known::Number(right)) => Known::Bool(left < right),
+        (OpCode::LessEqual, Known::Number(left), Known::Number(right)) => Known::Bool(left <= right),
+        (OpCode::Add, Known::Number(left), Known::Number(right)) => Known::Number(left + right),
+        (OpCode::Add, Known::String(left), Known::String(right)) => Known::String(left + &right),
+        (OpCode::Subtract, Known::Number(left), Known::Number(right)) => Known::Number(left - right),
+        (OpCode::Multiply, Known::Number(left), Known::Number(right)) => Known::Number(left * right),
+        (OpCode::Divide, Known::Number(left), Known::Number(right)) => Known::Number(left / right),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Work out `op_code` on a literal, where it can't fail.
+fn fold_unary(op_code: OpCode, value: Known) -> Option<Known> {
+    match (op_code, value) {
+        (OpCode::Not, value) => Some(Known::Bool(!value.is_truthy())),
+        (OpCode::Negate, Known::Number(number)) => Some(Known::Number(-number)),
+        _ => None,
+    }
+}
+
+/// Make `instruction` push `value` instead.  Fails only if the
+/// constant pool is full.
+fn load(instruction: &mut Instruction, value: Known, chunk: &mut Chunk) -> bool {
+    let (op_code, constant) = match value {
+        Known::Nil => (OpCode::Nil, None),
+        Known::Bool(true) => (OpCode::True, None),
+        Known::Bool(false) => (OpCode::False, None),
+        Known::Number(_) | Known::String(_) if chunk.constants().len() >= MAX_CONSTANTS => return false,
+        Known::Number(number) => (OpCode::Constant, Some(chunk.add_constant(Constant::Number(number)))),
+        Known::String(string) => (OpCode::Constant, Some(chunk.add_constant(Constant::String(string)))),
+    };
+    instruction.op_code = op_code;
+    instruction.constant = constant;
+    instruction.operands.clear();
+    true
+}
+
+fn pop_count(instruction: &Instruction) -> usize {
+    match instruction.op_code {
+        OpCode::PopN => instruction.operands[0] as usize,
+        _ => 1,
+    }
+}
+
+fn set_pop_count(instruction: &mut Instruction, count: usize) {
+    match count {
+        1 => {
+            instruction.op_code = OpCode::Pop;
+            instruction.operands.clear();
+        }
+        _ => {
+            instruction.op_code = OpCode::PopN;
+            instruction.operands = vec![count as u8];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler_struct::compile;
+    use bytecode::disassembler::disassemble_function;
+
+    fn optimized(source: &str) -> String {
+        disassemble_function(&optimize(compile(source).unwrap(), OptLevel::O1))
+    }
+
+    #[test]
+    fn test_o0_changes_nothing() {
+        let source = "print 1 + 2;";
+
+        assert_eq!(optimize(compile(source).unwrap(), OptLevel::O0), compile(source).unwrap());
+    }
+
+    #[test]
+    fn test_folds_constants() {
+        assert_eq!(
+            optimized("print 1 + 2 * 3;\nprint ⁻4 - 1;\nprint «con» + «cat»;\nprint 1 ≠ 2 and ¬nil;\nprint «a» - 1;"),
+            "\
+== <script> ==
+0000    1 OP_CONSTANT         4 '7'
+0002    | OP_PRINT
+0003    2 OP_CONSTANT         2 '-5'
+0005    | OP_PRINT
+0006    3 OP_CONSTANT         3 'concat'
+0008    | OP_PRINT
+0009    4 OP_TRUE
+0010    | OP_PRINT
+0011    5 OP_CONSTANT         1 'a'
+0013    | OP_CONSTANT         0 '1'
+0015    | OP_SUBTRACT
+0016    | OP_PRINT
+0017    | OP_NIL
+0018    | OP_RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_drops_jumps_that_are_known_and_code_that_is_dead() {
+        assert_eq!(
+            optimized("if (false) print 1; else print 2;\nwhile (true) { print 3; }\nprint 4;"),
+            "\
+== <script> ==
+0000    1 OP_CONSTANT         0 '2'
+0002    | OP_PRINT
+0003    2 OP_CONSTANT         1 '3'
+0005    | OP_PRINT
+0006    | OP_LOOP             6 -> 3
+"
+        );
+    }
+
+    #[test]
+    fn test_merges_pops_and_drops_values_that_are_only_popped() {
+        assert_eq!(
+            optimized("{\n  var a ← 1;\n  var b ← 2;\n  var c ← 3;\n  a;\n  print a;\n}"),
+            "\
+== <script> ==
+0000    2 OP_CONSTANT         0 '1'
+0002    3 OP_CONSTANT         1 '2'
+0004    4 OP_CONSTANT         2 '3'
+0006    6 OP_GET_LOCAL        1
+0008    | OP_PRINT
+0009    7 OP_POP_N            3
+0011    | OP_NIL
+0012    | OP_RETURN
+"
+        );
+    }
+
+    #[test]
+    fn test_drops_double_negation_where_only_truthiness_matters() {
+        assert_eq!(
+            optimized("fun f(x) {\n  if (¬¬x) print x;\n  print ¬¬x;\n  return ¬¬(x < 1);\n}")
+                .split("\n\n")
+                .nth(1),
+            Some(
+                "\
+== <fn f> ==
+0000    2 OP_GET_LOCAL        1
+0002    | OP_JUMP_IF_FALSE    2 -> 12
+0005    | OP_POP
+0006    | OP_GET_LOCAL        1
+0008    | OP_PRINT
+0009    | OP_JUMP             9 -> 13
+0012    | OP_POP
+0013    3 OP_GET_LOCAL        1
+0015    | OP_NOT
+0016    | OP_NOT
+0017    | OP_PRINT
+0018    4 OP_GET_LOCAL        1
+0020    | OP_CONSTANT         0 '1'
+0022    | OP_LESS
+0023    | OP_RETURN
+"
+            )
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! Contains the `Precedence` enum.
+
+
+/// How tightly an operator binds, from loosest to tightest.
+///
+/// Parsing an expression at one level takes in every operator at
+/// that level or tighter, so `parse_precedence(Term)` on `a + b * c`
+/// takes all of it but on `a ≟ b` stops after `a`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    None,
+    /// `←`
+    Assignment,
+    /// `or`
+    Or,
+    /// `and`
+    And,
+    /// `≟ ≠`
+    Equality,
+    /// `< > ≤ ≥`
+    Comparison,
+    /// `+ -`
+    Term,
+    /// `* /`
+    Factor,
+    /// `¬ ⁻`
+    Unary,
+    /// `. ()`
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter level, for parsing the right operand of a
+    /// left-associative operator.
+    pub fn next(self) -> Self {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Contains the `Diagnostic` struct.
+
+use token::span::Span;
+
+
+/// A problem found in the source code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The line the problem is on (starting from 1).
+    pub line: usize,
+
+    /// The part of the source the problem is about.
+    pub span: Span,
+
+    /// What went wrong, for example `Error at ';': Expect expression.`
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, span: Span, message: String) -> Self {
+        Diagnostic {
+            line,
+            span,
+            message,
+        }
+    }
+
+    /// Render the diagnostic with the offending line of `source`
+    /// and carets under the span.
+    ///
+    /// # Example
+    ///
+    /// ```text
+    /// [line 1] Error at ';': Expect expression.
+    ///     print 1 + ;
+    ///               ^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let start = self.span.start.min(source.len());
+        let end = self.span.end.clamp(start, source.len());
+
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |index| start + index);
+        let line_text = &source[line_start..line_end];
+
+        // count characters rather than bytes so the carets
+        // line up under the glyph operators
+        let column = source[line_start..start].chars().count();
+        let width = source[start..end.min(line_end)].chars().count().max(1);
+
+        format!(
+            "[line {}] {}\n    {}\n    {}{}",
+            self.line,
+            self.message,
+            line_text,
+            " ".repeat(column),
+            "^".repeat(width),
+        )
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] {}", self.line, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_points_at_span() {
+        let source = "var x ← 1;\nprint x ≟ ;";
+        let diagnostic = Diagnostic::new(2, Span::new(25, 26), String::from("Error at ';': Expect expression."));
+
+        assert_eq!(
+            diagnostic.render(source),
+            "[line 2] Error at ';': Expect expression.\n    print x ≟ ;\n              ^",
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Contains the `Diagnostic` struct, which is how every
+//! stage (scanner, parser and onwards) reports problems
+//! in the user's code, and the renderer that turns one into
+//! something a human can read.
+
+
+pub mod diagnostic;
@@ -0,0 +1,20 @@
+//! Contains the `Callable` trait.
+
+use crate::interpreter_struct::Interpreter;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// Anything that can be called with `callee(arguments)`.
+pub trait Callable: std::fmt::Debug {
+    /// The name to show when the callable is printed.
+    fn name(&self) -> &str;
+
+    /// How many arguments the callable takes. Calls with any
+    /// other number of arguments are an error.
+    fn arity(&self) -> usize;
+
+    /// Call it. The interpreter has already checked that there
+    /// are `arity()` arguments.
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError>;
+}
@@ -0,0 +1,95 @@
+//! Contains the core library: the native functions every
+//! interpreter starts with.
+//!
+//! - `clock()` -- seconds since the Unix epoch, for timing
+//! - `len(string)` -- how many characters a string has
+//! - `str(value)` -- a value as a string, the way `print` shows it
+//! - `num(value)` -- a string parsed as a number, or `nil` if it
+//!   isn't one
+//! - `type(value)` -- the name of a value's type
+//! - `input()` -- the next line of stdin, or `nil` at the end
+//! - `exit(code)` -- stop the program with an exit code
+
+use std::io::BufRead;
+use std::rc::Rc;
+
+use crate::interpreter_struct::Interpreter;
+use crate::native_registry::NativeRegistry;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// Add the core library to `registry`.
+pub fn register(registry: &mut NativeRegistry) {
+    registry
+        .register("clock", 0, clock)
+        .register("len", 1, len)
+        .register("str", 1, str)
+        .register("num", 1, num)
+        .register("type", 1, type_of)
+        .register("input", 0, input)
+        .register("exit", 1, exit);
+}
+
+
+fn clock(_interpreter: &mut Interpreter, _arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+/// Counts characters, not bytes, so `len(«≟»)` is 1.
+fn len(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(string) => Ok(Value::Number(string.chars().count() as f64)),
+        other => Err(RuntimeError::native(&format!("len() expects a string, not a {}.", other.type_name()))),
+    }
+}
+
+fn str(interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::String(string) => Ok(Value::String(Rc::clone(string))),
+        other => {
+            let string: Rc<str> = Rc::from(other.to_string());
+            interpreter.hold(&string, string.len())?;
+            Ok(Value::String(string))
+        }
+    }
+}
+
+/// Surrounding whitespace is ignored.  Anything Rust can parse as
+/// an `f64` counts, which is a little looser than Lox's own
+/// number literals (it allows `1e3` and a leading `-`).
+fn num(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Number(number) => Ok(Value::Number(*number)),
+        Value::String(string) => Ok(string.trim().parse::<f64>().map_or(Value::Nil, Value::Number)),
+        other => Err(RuntimeError::native(&format!("num() expects a string or a number, not a {}.", other.type_name()))),
+    }
+}
+
+fn type_of(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    Ok(Value::String(Rc::from(arguments[0].type_name())))
+}
+
+/// The line comes back without its line ending.
+fn input(interpreter: &mut Interpreter, _arguments: &[Value]) -> Result<Value, RuntimeError> {
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => {
+            let line: Rc<str> = Rc::from(line.trim_end_matches(['\n', '\r']));
+            interpreter.hold(&line, line.len())?;
+            Ok(Value::String(line))
+        }
+        Err(error) => Err(RuntimeError::native(&format!("Couldn't read input: {}.", error))),
+    }
+}
+
+fn exit(_interpreter: &mut Interpreter, arguments: &[Value]) -> Result<Value, RuntimeError> {
+    match &arguments[0] {
+        Value::Number(code) if code.fract() == 0.0 && *code >= 0.0 && *code <= 255.0 => Err(RuntimeError::exit(*code as i32)),
+        _ => Err(RuntimeError::native("exit() expects a whole number from 0 to 255.")),
+    }
+}
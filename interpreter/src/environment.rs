@@ -0,0 +1,120 @@
+//! Contains the `Environment` struct.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use token::token::Token;
+
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// The variables of one scope.
+///
+/// Each block gets its own environment, which points at the one
+/// it is nested in.  Looking a name up tries the innermost scope
+/// first and works outwards, so an inner `var` shadows an outer
+/// one until the block ends.  The outermost environment holds
+/// the globals.
+///
+/// Environments are shared (`Rc<RefCell<…>>`) because more than
+/// one thing can need to keep a scope alive at once.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+
+    /// The scope this one is nested in, or `None` for the globals.
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        //! Create a new global `Environment`.
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        //! Create a new `Environment` nested inside `enclosing`.
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Declare a variable in this scope.
+    ///
+    /// Declaring a name that's already in this scope just replaces
+    /// its value, which is what the REPL wants for globals.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// The value of the variable `name`, from the innermost scope
+    /// that has it.
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        match (self.values.get(&name.lexeme), &self.enclosing) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(enclosing)) => enclosing.borrow().get(name),
+            (None, None) => Err(undefined(name)),
+        }
+    }
+
+    /// The value of `name` if it's declared in this scope itself.
+    pub fn get_here(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    /// The value of the variable `name` in the scope `distance`
+    /// steps out from this one, where the resolver found it.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, RuntimeError> {
+        if distance == 0 {
+            return self.values.get(&name.lexeme).cloned().ok_or_else(|| undefined(name));
+        }
+        self.ancestor(distance).borrow().get_at(0, name)
+    }
+
+    /// Change the value of the variable `name` in the scope
+    /// `distance` steps out from this one.
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if distance == 0 {
+            return match self.values.get_mut(&name.lexeme) {
+                Some(variable) => {
+                    *variable = value;
+                    Ok(())
+                }
+                None => Err(undefined(name)),
+            };
+        }
+        self.ancestor(distance).borrow_mut().assign_at(0, name, value)
+    }
+
+    /// The scope `distance` steps out from this one.
+    fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
+        let mut environment = Rc::clone(self.enclosing.as_ref().expect("the resolver counted a scope that isn't there"));
+        for _ in 1..distance {
+            let enclosing = Rc::clone(environment.borrow().enclosing.as_ref().expect("the resolver counted a scope that isn't there"));
+            environment = enclosing;
+        }
+        environment
+    }
+
+    /// Change the value of an existing variable, in the innermost
+    /// scope that has it.  Assigning to a name that was never
+    /// declared is an error.
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(variable) = self.values.get_mut(&name.lexeme) {
+            *variable = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined(name)),
+        }
+    }
+}
+
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError::new(name, &format!("Undefined variable '{}'.", name.lexeme))
+}
@@ -0,0 +1,51 @@
+//! Contains the `Instance` struct.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use token::token::Token;
+
+use crate::lox_class::LoxClass;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// An object made by calling a class.
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<LoxClass>,
+
+    pub fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        //! Create a new `Instance` of `class` with no fields.
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Read the property `name` of `instance`.
+    ///
+    /// Fields come first, so a field can shadow a method.  A method
+    /// comes back bound to the instance, so `this` still works when
+    /// it's called later.
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &Token) -> Result<Value, RuntimeError> {
+        let this = instance.borrow();
+        if let Some(value) = this.fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        match this.class.find_method(&name.lexeme) {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(Value::Instance(Rc::clone(instance)))))),
+            None => Err(RuntimeError::new(name, &format!("Undefined property '{}'.", name.lexeme))),
+        }
+    }
+
+    /// Write the field `name`, adding it if it isn't there yet.
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
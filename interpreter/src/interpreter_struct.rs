@@ -0,0 +1,922 @@
+//! Contains the `Interpreter` struct.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+use ast::arena::{ExprId, StmtId};
+use ast::expr::Expr;
+use ast::stmt::Stmt;
+use limits::limits::{Limit, Limits};
+use resolver::program::Program;
+use token::token::Token;
+use token::token_type::TokenType;
+
+use crate::callable::Callable;
+use crate::environment::Environment;
+use crate::instance::Instance;
+use crate::lox_class::LoxClass;
+use crate::lox_function::LoxFunction;
+use crate::native_registry::NativeRegistry;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// The interpreter struct.
+///
+/// This runs a program by walking its `Ast` directly: statements
+/// are executed and expressions are evaluated to `Value`s.
+///
+/// The globals outlive a single call to `interpret`, so running
+/// several programs on one interpreter (like the REPL does, one
+/// line at a time) lets later ones see what earlier ones declared.
+pub struct Interpreter {
+    /// The outermost scope.
+    pub globals: Rc<RefCell<Environment>>,
+
+    /// The scope of the code that's running right now.
+    environment: Rc<RefCell<Environment>>,
+
+    /// Where `print` writes to.
+    output: Box<dyn Write>,
+
+    /// How much a run is allowed to do.
+    limits: Limits,
+
+    /// How many steps the current run has taken.
+    steps: u64,
+
+    /// How many calls are in progress.
+    call_depth: usize,
+
+    /// How far down the Rust stack the current run can go: where
+    /// the stack was when it started, less the stack limit.  The
+    /// stack grows down on every platform Rust's `std` threads run
+    /// on.  `0` if there's no limit (or there hasn't been a run).
+    stack_floor: usize,
+
+    /// How many bytes the current run has allocated, all told.
+    allocated_bytes: usize,
+
+    /// What's been counted against the heap limit: for each object,
+    /// whether it's still alive, and how many bytes it was counted
+    /// as.  Only kept when there is a heap limit.
+    held: Vec<(Box<dyn Fn() -> bool>, usize)>,
+
+    /// How many bytes `held` adds up to, counting the objects that
+    /// have died since it was last pruned.
+    held_bytes: usize,
+
+    /// When the current run has to stop by, if there's a timeout.
+    deadline: Option<Instant>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        //! Create a new `Interpreter` that prints to stdout.
+        Interpreter::with_output(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        //! Create a new `Interpreter` that prints to `output`.
+        //!
+        //! The core library's native functions are already defined.
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            output,
+            limits: Limits::default(),
+            steps: 0,
+            call_depth: 0,
+            stack_floor: 0,
+            allocated_bytes: 0,
+            held: Vec::new(),
+            held_bytes: 0,
+            deadline: None,
+        };
+        interpreter.install(&NativeRegistry::core());
+        interpreter
+    }
+
+    pub fn install(&mut self, registry: &NativeRegistry) {
+        //! Define every function in `registry` as a global,
+        //! replacing any global that already has its name.
+        for native in registry.natives() {
+            let name = native.name().to_string();
+            self.globals.borrow_mut().define(&name, Value::Callable(Rc::new(native.clone())));
+        }
+    }
+
+    pub fn output(&mut self) -> &mut dyn Write {
+        //! Where `print` writes to, for native functions that
+        //! want to write to the same place.
+        &mut self.output
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        //! Change how much a run is allowed to do.  This takes
+        //! effect from the next run.
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        //! How much a run is allowed to do.
+        self.limits
+    }
+
+    pub fn reset_budget(&mut self) {
+        //! Start the step, allocation, stack and time budgets over
+        //! for a new run.
+        //!
+        //! `interpret` and `interpret_to_value` do this themselves;
+        //! it's only needed before calling into Lox with `call`.
+        //! The stack is measured from wherever this is called.
+        self.steps = 0;
+        self.stack_floor = match self.limits.max_stack_bytes {
+            Some(max_stack_bytes) => stack_address().saturating_sub(max_stack_bytes),
+            None => 0,
+        };
+        self.allocated_bytes = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Count one step against the step limit, and every so often
+    /// check the clock against the deadline.
+    fn step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(RuntimeError::limit(Limit::Steps, &format!("Step limit of {} exceeded.", max_steps)));
+            }
+        }
+        // reading the clock is slow next to a step, so don't do it
+        // every time
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(256) && Instant::now() >= deadline {
+                let timeout = self.limits.timeout.unwrap_or_default();
+                return Err(RuntimeError::limit(Limit::Time, &format!("Time limit of {:?} exceeded.", timeout)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure the run hasn't used more of the Rust stack than
+    /// the stack limit allows.
+    ///
+    /// How much stack a Lox call takes depends on how deeply its
+    /// statements and expressions are nested, so the call depth
+    /// alone can't keep the stack from overflowing.  This is
+    /// checked for every call, statement and expression, so going
+    /// over the limit only ever takes a few frames more.
+    #[inline]
+    fn check_stack(&self) -> Result<(), RuntimeError> {
+        if stack_address() < self.stack_floor {
+            return Err(self.stack_error());
+        }
+        Ok(())
+    }
+
+    #[cold]
+    fn stack_error(&self) -> RuntimeError {
+        let max_stack_bytes = self.limits.max_stack_bytes.unwrap_or_default();
+        RuntimeError::limit(Limit::Stack, &format!("Stack limit of {} bytes exceeded.", max_stack_bytes))
+    }
+
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), RuntimeError> {
+        //! Count `bytes` against the allocation budget.
+        //!
+        //! Native functions that make big values (long strings,
+        //! say) should call this, or `hold` if the value is behind
+        //! an `Rc`, so the limits can see them.
+        self.allocated_bytes = self.allocated_bytes.saturating_add(bytes);
+        if let Some(max_allocated_bytes) = self.limits.max_allocated_bytes {
+            if self.allocated_bytes > max_allocated_bytes {
+                return Err(RuntimeError::limit(
+                    Limit::Allocation,
+                    &format!("Allocation limit of {} bytes exceeded.", max_allocated_bytes),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn hold<T: ?Sized + 'static>(&mut self, owner: &Rc<T>, bytes: usize) -> Result<(), RuntimeError> {
+        //! Count `bytes` against the allocation budget, and against
+        //! the heap limit for as long as `owner` is alive.
+        //!
+        //! The dead are only pruned once the heap looks full, so a
+        //! program that keeps making garbage checks what's alive
+        //! every so often rather than on every allocation.
+        self.allocate(bytes)?;
+        let Some(max_heap_bytes) = self.limits.max_heap_bytes else {
+            return Ok(());
+        };
+
+        let owner = Rc::downgrade(owner);
+        self.held.push((Box::new(move || owner.strong_count() > 0), bytes));
+        self.held_bytes = self.held_bytes.saturating_add(bytes);
+        if self.held_bytes > max_heap_bytes {
+            self.held.retain(|(alive, _)| alive());
+            self.held_bytes = self.held.iter().map(|(_, bytes)| bytes).sum();
+            if self.held_bytes > max_heap_bytes {
+                return Err(RuntimeError::limit(Limit::Heap, &format!("Heap limit of {} bytes exceeded.", max_heap_bytes)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count `environment`, with room for `variables`, against the
+    /// memory limits.
+    pub(crate) fn hold_environment(&mut self, environment: &Rc<RefCell<Environment>>, variables: usize) -> Result<(), RuntimeError> {
+        self.hold(environment, size_of::<Environment>() + variables * size_of::<(String, Value)>())
+    }
+
+    pub fn interpret(&mut self, program: &Rc<Program>) -> Result<(), RuntimeError> {
+        //! Run a whole program.
+        //!
+        //! This is the main method and purpose of the interpreter.
+        //! It stops at the first runtime error.
+        //!
+        //! The `Program` is shared because the functions it declares
+        //! keep hold of it, so they can still be called after it's
+        //! finished.
+        self.reset_budget();
+        self.execute_statements(program, &program.ast.statements)?;
+        Ok(())
+    }
+
+    pub fn interpret_to_value(&mut self, program: &Rc<Program>) -> Result<Value, RuntimeError> {
+        //! Run a whole program like `interpret`, and give back the
+        //! value of the last statement if it's an expression
+        //! statement (otherwise `nil`).
+        //!
+        //! This is what lets `1 + 2;` work as a calculator from
+        //! code that embeds the interpreter.
+        self.reset_budget();
+        let Some((last, rest)) = program.ast.statements.split_last() else {
+            return Ok(Value::Nil);
+        };
+        self.execute_statements(program, rest)?;
+        match program.ast.stmt(*last) {
+            Stmt::Expression { expression } => self.evaluate(program, *expression),
+            _ => {
+                self.execute(program, *last)?;
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    pub fn call(&mut self, callee: Value, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        //! Call a function or a class with `arguments`.
+        //!
+        //! Errors about the call itself (the wrong number of
+        //! arguments, say) have no place in the source, so the
+        //! caller should point them somewhere with `located_at`.
+        self.step()?;
+        self.check_stack()?;
+        if let Some(max_call_depth) = self.limits.max_call_depth {
+            if self.call_depth >= max_call_depth {
+                return Err(RuntimeError::limit(
+                    Limit::CallDepth,
+                    &format!("Call depth limit of {} exceeded.", max_call_depth),
+                ));
+            }
+        }
+
+        self.call_depth += 1;
+        let result = match callee {
+            Value::Callable(callable) => {
+                check_arity(callable.arity(), arguments.len()).and_then(|()| callable.call(self, arguments))
+            }
+            Value::Class(class) => {
+                check_arity(class.arity(), arguments.len()).and_then(|()| LoxClass::instantiate(&class, self, arguments))
+            }
+            _ => Err(RuntimeError::native("Can only call functions and classes.")),
+        };
+        self.call_depth -= 1;
+        result
+    }
+
+    /// Run a statement.
+    ///
+    /// Gives back `Some(value)` if a `return` ran, so that the
+    /// statements around it stop and the function call it's in
+    /// can give back `value`.  The resolver makes sure `return`
+    /// is only used inside functions.
+    fn execute(&mut self, program: &Rc<Program>, stmt: StmtId) -> Result<Option<Value>, RuntimeError> {
+        self.step()?;
+        self.check_stack()?;
+        match program.ast.stmt(stmt) {
+            Stmt::Block { statements } => {
+                let environment = Rc::new(RefCell::new(Environment::with_enclosing(Rc::clone(&self.environment))));
+                self.hold_environment(&environment, statements.len())?;
+                return self.execute_block(program, statements, environment);
+            }
+            Stmt::Expression { expression } => {
+                self.evaluate(program, *expression)?;
+            }
+            Stmt::Class(declaration) => {
+                let superclass = match declaration.superclass {
+                    Some(superclass) => Some(self.evaluate_superclass(program, superclass)?),
+                    None => None,
+                };
+
+                // methods of a subclass close over a scope holding `super`
+                let closure = match &superclass {
+                    Some(superclass) => {
+                        let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                        environment.define("super", Value::Class(Rc::clone(superclass)));
+                        let environment = Rc::new(RefCell::new(environment));
+                        self.hold_environment(&environment, 1)?;
+                        environment
+                    }
+                    None => Rc::clone(&self.environment),
+                };
+                let methods = declaration
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        let is_initializer = method.name.lexeme == "init";
+                        let function = LoxFunction::new(Rc::clone(program), method.clone(), Rc::clone(&closure), is_initializer);
+                        (method.name.lexeme.clone(), Rc::new(function))
+                    })
+                    .collect();
+                let class = Rc::new(LoxClass::new(&declaration.name.lexeme, superclass, methods));
+                self.hold(&class, size_of::<LoxClass>() + declaration.methods.len() * size_of::<LoxFunction>())?;
+                self.environment.borrow_mut().define(&declaration.name.lexeme, Value::Class(class));
+            }
+            Stmt::Function(declaration) => {
+                let function = Rc::new(LoxFunction::new(Rc::clone(program), declaration.clone(), Rc::clone(&self.environment), false));
+                self.hold(&function, size_of::<LoxFunction>())?;
+                self.environment.borrow_mut().define(&declaration.name.lexeme, Value::Callable(function));
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                if self.evaluate(program, *condition)?.is_truthy() {
+                    return self.execute(program, *then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(program, *else_branch);
+                }
+            }
+            Stmt::Print { keyword, expression } => {
+                let value = self.evaluate(program, *expression)?;
+                if let Err(error) = writeln!(self.output, "{}", value) {
+                    return Err(RuntimeError::new(keyword, &format!("Couldn't print: {}.", error)));
+                }
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(program, *value)?,
+                    None => Value::Nil,
+                };
+                return Ok(Some(value));
+            }
+            Stmt::Var { name, initializer } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(program, *initializer)?,
+                    None => Value::Nil,
+                };
+                let environment = Rc::clone(&self.environment);
+                self.hold(&environment, name.lexeme.len() + size_of::<Value>())?;
+                environment.borrow_mut().define(&name.lexeme, value);
+            }
+            Stmt::While { condition, body } => {
+                while self.evaluate(program, *condition)?.is_truthy() {
+                    if let Some(returned) = self.execute(program, *body)? {
+                        return Ok(Some(returned));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn execute_block(
+        &mut self,
+        program: &Rc<Program>,
+        statements: &[StmtId],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<Option<Value>, RuntimeError> {
+        //! Run `statements` in `environment`, then go back to the
+        //! environment we were in, even if one of them failed or
+        //! returned.
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = self.execute_statements(program, statements);
+        self.environment = previous;
+        result
+    }
+
+    fn execute_statements(&mut self, program: &Rc<Program>, statements: &[StmtId]) -> Result<Option<Value>, RuntimeError> {
+        for statement in statements {
+            if let Some(returned) = self.execute(program, *statement)? {
+                return Ok(Some(returned));
+            }
+        }
+        Ok(None)
+    }
+
+    fn evaluate(&mut self, program: &Rc<Program>, expr: ExprId) -> Result<Value, RuntimeError> {
+        self.check_stack()?;
+        match program.ast.expr(expr) {
+            Expr::Assign { name, value } => {
+                let value = self.evaluate(program, *value)?;
+                match program.depths.get(expr) {
+                    Some(distance) => self.environment.borrow_mut().assign_at(*distance, name, value.clone())?,
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+                Ok(value)
+            }
+            Expr::Binary { left, operator, right } => {
+                let left = self.evaluate(program, *left)?;
+                let right = self.evaluate(program, *right)?;
+                let value = binary(operator, left, right)?;
+                // the only strings `binary` gives back are new ones
+                if let Value::String(string) = &value {
+                    self.hold(string, string.len())?;
+                }
+                Ok(value)
+            }
+            Expr::Call { callee, paren, arguments } => {
+                let callee = self.evaluate(program, *callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(program, *argument))
+                    .collect::<Result<Vec<Value>, RuntimeError>>()?;
+
+                self.call(callee, arguments).map_err(|error| error.located_at(paren))
+            }
+            Expr::Get { object, name } => match self.evaluate(program, *object)? {
+                Value::Instance(instance) => Instance::get(&instance, name),
+                _ => Err(RuntimeError::new(name, "Only instances have properties.")),
+            },
+            Expr::Grouping { expression } => self.evaluate(program, *expression),
+            Expr::Literal { value } => Ok(Value::from(value)),
+            Expr::Set { object, name, value } => {
+                let Value::Instance(instance) = self.evaluate(program, *object)? else {
+                    return Err(RuntimeError::new(name, "Only instances have fields."));
+                };
+                let value = self.evaluate(program, *value)?;
+                self.hold(&instance, name.lexeme.len() + size_of::<Value>())?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expr::Super { keyword, method } => {
+                // `super` is in the scope the resolver found, and the
+                // `this` of the method we're in is just inside it
+                let distance = *program.depths.get(expr).expect("the resolver only allows super in subclasses");
+                let Value::Class(superclass) = self.environment.borrow().get_at(distance, keyword)? else {
+                    unreachable!("super is always bound to a class");
+                };
+                let this = Token { lexeme: String::from("this"), ..keyword.clone() };
+                let object = self.environment.borrow().get_at(distance - 1, &this)?;
+
+                match superclass.find_method(&method.lexeme) {
+                    Some(found) => Ok(Value::Callable(Rc::new(found.bind(object)))),
+                    None => Err(RuntimeError::new(method, &format!("Undefined property '{}'.", method.lexeme))),
+                }
+            }
+            Expr::This { keyword } => self.look_up_variable(program, expr, keyword),
+            Expr::Logical { left, operator, right } => {
+                let left = self.evaluate(program, *left)?;
+                // short-circuit, giving back the operand that decided it
+                let decided = match operator.token_type {
+                    TokenType::Or => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+                if decided {
+                    Ok(left)
+                } else {
+                    self.evaluate(program, *right)
+                }
+            }
+            Expr::Unary { operator, right } => {
+                let right = self.evaluate(program, *right)?;
+                match (operator.token_type, right) {
+                    (TokenType::Not, right) => Ok(Value::Bool(!right.is_truthy())),
+                    (TokenType::Negative, Value::Number(number)) => Ok(Value::Number(-number)),
+                    (_, _) => Err(RuntimeError::new(operator, "Operand must be a number.")),
+                }
+            }
+            Expr::Variable { name } => self.look_up_variable(program, expr, name),
+        }
+    }
+
+    /// Evaluate the expression after a class's `<`, which has to
+    /// give a class.
+    fn evaluate_superclass(&mut self, program: &Rc<Program>, superclass: ExprId) -> Result<Rc<LoxClass>, RuntimeError> {
+        let Expr::Variable { name } = program.ast.expr(superclass) else {
+            unreachable!("the parser only allows a name after '<'");
+        };
+        match self.evaluate(program, superclass)? {
+            Value::Class(class) => Ok(class),
+            _ => Err(RuntimeError::new(name, "Superclass must be a class.")),
+        }
+    }
+
+    /// Read a variable from where the resolver found it: that many
+    /// scopes out if it's a local, otherwise from the globals.
+    fn look_up_variable(&self, program: &Program, expr: ExprId, name: &Token) -> Result<Value, RuntimeError> {
+        match program.depths.get(expr) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+}
+
+
+/// Make sure a call has as many arguments as the callee takes.
+fn check_arity(arity: usize, count: usize) -> Result<(), RuntimeError> {
+    if count != arity {
+        return Err(RuntimeError::native(&format!("Expected {} arguments but got {}.", arity, count)));
+    }
+    Ok(())
+}
+
+
+/// Apply a binary operator.
+///
+/// `+` adds numbers or concatenates strings, `≟` and `≠` work on
+/// any values, and everything else needs two numbers.
+fn binary(operator: &Token, left: Value, right: Value) -> Result<Value, RuntimeError> {
+    match (operator.token_type, left, right) {
+        (TokenType::EqualityTest, left, right) => Ok(Value::Bool(left == right)),
+        (TokenType::NotEqual, left, right) => Ok(Value::Bool(left != right)),
+        (TokenType::Plus, Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
+        (TokenType::Plus, Value::String(left), Value::String(right)) => {
+            Ok(Value::String(Rc::from(format!("{}{}", left, right))))
+        }
+        (TokenType::Plus, _, _) => Err(RuntimeError::new(operator, "Operands must be two numbers or two strings.")),
+        (TokenType::Minus, Value::Number(left), Value::Number(right)) => Ok(Value::Number(left - right)),
+        (TokenType::Star, Value::Number(left), Value::Number(right)) => Ok(Value::Number(left * right)),
+        (TokenType::Slash, Value::Number(left), Value::Number(right)) => Ok(Value::Number(left / right)),
+        (TokenType::Greater, Value::Number(left), Value::Number(right)) => Ok(Value::Bool(left > right)),
+        (TokenType::GreaterEqual, Value::Number(left), Value::Number(right)) => Ok(Value::Bool(left >= right)),
+        (TokenType::Less, Value::Number(left), Value::Number(right)) => Ok(Value::Bool(left < right)),
+        (TokenType::LessEqual, Value::Number(left), Value::Number(right)) => Ok(Value::Bool(left <= right)),
+        _ => Err(RuntimeError::new(operator, "Operands must be numbers.")),
+    }
+}
+
+
+/// Roughly where the top of the Rust stack is right now.
+#[inline(always)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parser_struct::Parser;
+    use scanner::scanner_struct::Scanner;
+    use std::cell::RefCell;
+
+    /// Somewhere to print to that the test can read back.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn parse(source: &str) -> Rc<Program> {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        let ast = Parser::new(scanner.tokens).parse().unwrap();
+        Rc::new(resolver::resolver_struct::resolve(ast).unwrap())
+    }
+
+    fn run(source: &str) -> Result<String, RuntimeError> {
+        let output = SharedOutput::default();
+        Interpreter::with_output(Box::new(output.clone())).interpret(&parse(source))?;
+        let printed = output.0.borrow().clone();
+        Ok(String::from_utf8(printed).unwrap())
+    }
+
+    #[test]
+    fn test_arithmetic_and_concatenation() {
+        assert_eq!(run("print 1 + 2 * 3; print ⁻(4 - 6) / 4; print «foo» + «bar»;").unwrap(), "7\n0.5\nfoobar\n");
+    }
+
+    #[test]
+    fn test_truthiness_and_equality() {
+        let output = run("print ¬nil; print ¬0; print «a» ≟ «a»; print 1 ≟ «1»; print nil ≠ false; print 0 and «yes»;");
+
+        assert_eq!(output.unwrap(), "true\nfalse\ntrue\nfalse\ntrue\nyes\n");
+    }
+
+    #[test]
+    fn test_loops_and_variables() {
+        let output = run("var total ← 0; for (var i ← 1; i ≤ 4; i ← i + 1) total ← total + i; print total;");
+
+        assert_eq!(output.unwrap(), "10\n");
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let error = run("print «a» - 1;").unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers.");
+        assert_eq!(error.span, token::span::Span::new(12, 13));
+
+        let error = run("print «a» + 1;").unwrap_err();
+        assert_eq!(error.message, "Operands must be two numbers or two strings.");
+    }
+
+    #[test]
+    fn test_blocks_shadow_and_assign_outwards() {
+        let output = run("var a ← «global»; var b ← 1; { var a ← «inner»; b ← 2; print a; } print a; print b;");
+
+        assert_eq!(output.unwrap(), "inner\nglobal\n2\n");
+    }
+
+    #[test]
+    fn test_assigning_an_undeclared_variable_fails() {
+        let error = run("{ var a ← 1; } a ← 2;").unwrap_err();
+
+        assert_eq!(error.message, "Undefined variable 'a'.");
+    }
+
+    #[test]
+    fn test_globals_survive_between_programs() {
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+
+        interpreter.interpret(&parse("var count ← 1;")).unwrap();
+        interpreter.interpret(&parse("{ var count ← 10; } count ← count + 1;")).unwrap();
+        interpreter.interpret(&parse("print count;")).unwrap();
+
+        assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn test_functions_return_and_recurse() {
+        let output = run("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10); print fib;");
+
+        assert_eq!(output.unwrap(), "55\n<fn fib>\n");
+    }
+
+    #[test]
+    fn test_closures_keep_their_environment() {
+        let source = "
+            fun make_counter() {
+                var count ← 0;
+                fun increment() { count ← count + 1; return count; }
+                return increment;
+            }
+            var first ← make_counter();
+            var second ← make_counter();
+            first(); first();
+            print first();
+            print second();
+            fun twice(f, x) { return f(f(x)); }
+            fun add_three(x) { return x + 3; }
+            print twice(add_three, 1);
+        ";
+
+        assert_eq!(run(source).unwrap(), "3\n1\n7\n");
+    }
+
+    #[test]
+    fn test_calls_check_arity_and_callee() {
+        let error = run("fun f(a, b) {} f(1);").unwrap_err();
+        assert_eq!(error.message, "Expected 2 arguments but got 1.");
+
+        let error = run("var x ← 1; x();").unwrap_err();
+        assert_eq!(error.message, "Can only call functions and classes.");
+    }
+
+    #[test]
+    fn test_closures_keep_the_binding_they_captured() {
+        let source = "
+            var a ← «global»;
+            {
+                fun show() { print a; }
+                show();
+                var a ← «block»;
+                show();
+            }
+        ";
+
+        assert_eq!(run(source).unwrap(), "global\nglobal\n");
+    }
+
+    #[test]
+    fn test_classes_with_init_this_and_bound_methods() {
+        let source = "
+            class Counter {
+                init(start) { this.count ← start; }
+                increment() { this.count ← this.count + 1; return this; }
+            }
+            var counter ← Counter(10);
+            counter.increment().increment();
+            print counter.count;
+            var increment ← counter.increment;
+            increment();
+            print counter.count;
+            print Counter;
+            print counter;
+            print counter.init(0) ≟ counter;
+        ";
+
+        assert_eq!(run(source).unwrap(), "12\n13\nCounter\nCounter instance\ntrue\n");
+    }
+
+    #[test]
+    fn test_property_errors() {
+        let error = run("class A {} print A().missing;").unwrap_err();
+        assert_eq!(error.message, "Undefined property 'missing'.");
+
+        let error = run("var x ← 1; x.y ← 2;").unwrap_err();
+        assert_eq!(error.message, "Only instances have fields.");
+
+        let error = run("class A { init(a) {} } A();").unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 0.");
+    }
+
+    #[test]
+    fn test_inheritance_and_super_calls() {
+        let source = "
+            class Animal {
+                init(name) { this.name ← name; }
+                speak() { return this.name + « makes a sound»; }
+                kind() { return «animal»; }
+            }
+            class Dog < Animal {
+                speak() { return super.speak() + «: woof»; }
+            }
+            var dog ← Dog(«Rex»);
+            print dog.speak();
+            print dog.kind();
+        ";
+
+        assert_eq!(run(source).unwrap(), "Rex makes a sound: woof\nanimal\n");
+    }
+
+    #[test]
+    fn test_superclass_must_be_a_class() {
+        let error = run("var NotAClass ← 1;\nclass A < NotAClass {}").unwrap_err();
+
+        assert_eq!(error.message, "Superclass must be a class.");
+        assert_eq!((error.line, error.span), (2, token::span::Span::new(31, 40)));
+    }
+
+    #[test]
+    fn test_core_library() {
+        let source = "
+            print len(«a≟b»);
+            print str(1.5) + str(nil);
+            print num(« 42 ») + 1;
+            print num(«forty-two»);
+            print type(1) + « » + type(«») + « » + type(clock) + « » + type(nil);
+        ";
+
+        assert_eq!(run(source).unwrap(), "3\n1.5nil\n43\nnil\nnumber string function nil\n");
+    }
+
+    #[test]
+    fn test_native_errors_point_at_the_call() {
+        let error = run("print 1;\nlen(1);").unwrap_err();
+
+        assert_eq!(error.message, "len() expects a string, not a number.");
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn test_exit_unwinds_with_its_code() {
+        let error = run("fun f() { exit(3); print «unreachable»; } f();").unwrap_err();
+
+        assert_eq!(error.kind, crate::runtime_error::ErrorKind::Exit(3));
+    }
+
+    #[test]
+    fn test_registered_natives_are_globals() {
+        let mut registry = NativeRegistry::new();
+        registry.register("double", 1, |_interpreter, arguments| match &arguments[0] {
+            Value::Number(number) => Ok(Value::Number(number * 2.0)),
+            _ => Err(RuntimeError::native("double() expects a number.")),
+        });
+
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.install(&registry);
+        interpreter.interpret(&parse("print double(21);")).unwrap();
+
+        assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "42\n");
+    }
+
+    /// Run `source` with `limits`, giving back the kind of error
+    /// it stopped with.
+    fn run_limited(source: &str, limits: Limits) -> crate::runtime_error::ErrorKind {
+        let mut interpreter = Interpreter::with_output(Box::new(SharedOutput::default()));
+        interpreter.set_limits(limits);
+        interpreter.interpret(&parse(source)).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_limits_stop_runaway_programs() {
+        use crate::runtime_error::ErrorKind::LimitExceeded;
+        let none = Limits { max_call_depth: None, max_stack_bytes: None, ..Limits::default() };
+
+        let steps = Limits { max_steps: Some(1000), ..none };
+        assert_eq!(run_limited("while (true) {}", steps), LimitExceeded(Limit::Steps));
+
+        let depth = Limits { max_call_depth: Some(50), ..none };
+        assert_eq!(run_limited("fun f() { f(); } f();", depth), LimitExceeded(Limit::CallDepth));
+
+        let allocation = Limits { max_allocated_bytes: Some(1 << 20), ..none };
+        let source = "var s ← «ab»; while (true) s ← s + s;";
+        assert_eq!(run_limited(source, allocation), LimitExceeded(Limit::Allocation));
+
+        let time = Limits { timeout: Some(std::time::Duration::from_millis(20)), ..none };
+        assert_eq!(run_limited("while (true) {}", time), LimitExceeded(Limit::Time));
+    }
+
+    #[test]
+    fn test_the_heap_limit_gives_back_what_is_dropped() {
+        use crate::runtime_error::ErrorKind::LimitExceeded;
+        let heap = Limits { max_heap_bytes: Some(64 << 10), ..Limits::default() };
+
+        // this makes megabytes of strings and scopes, but never keeps
+        // more than one of each
+        let garbage = "
+            var total ← 0;
+            for (var i ← 0; i < 20000; i ← i + 1) {
+                var s ← str(i) + «, a string long enough to add up»;
+                total ← total + len(s);
+            }
+            print total;
+        ";
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        interpreter.set_limits(heap);
+        interpreter.interpret(&parse(garbage)).unwrap();
+        assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "728890\n");
+
+        let budget = Limits { max_allocated_bytes: heap.max_heap_bytes, ..Limits::default() };
+        assert_eq!(run_limited(garbage, budget), LimitExceeded(Limit::Allocation));
+
+        // while what's kept still counts
+        let kept = "class Node {} var list ← nil; while (true) { var node ← Node(); node.next ← list; list ← node; }";
+        assert_eq!(run_limited(kept, heap), LimitExceeded(Limit::Heap));
+        assert_eq!(run_limited("var s ← «ab»; while (true) s ← s + s;", heap), LimitExceeded(Limit::Heap));
+    }
+
+    #[test]
+    fn test_deep_recursion_is_an_error_not_a_crash() {
+        // the default stack limit fits in a test thread's stack, even
+        // when every call is nested deep inside blocks
+        let blocks = 190;
+        let nested = format!("{}return f(n + 1);{}", "{".repeat(blocks), "}".repeat(blocks));
+        for source in ["fun f(n) { return f(n + 1); }\nf(0);".to_string(), format!("fun f(n) {{ {} }}\nf(0);", nested)] {
+            let error = run(&source).unwrap_err();
+            assert_eq!(error.kind, crate::runtime_error::ErrorKind::LimitExceeded(Limit::Stack));
+        }
+
+        // with a bigger stack and limit, recursion gets as deep as
+        // the VM's
+        let printed = std::thread::Builder::new()
+            .stack_size(256 << 20)
+            .spawn(|| {
+                let output = SharedOutput::default();
+                let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+                interpreter.set_limits(Limits { max_stack_bytes: Some(128 << 20), ..Limits::default() });
+                let program = parse("fun f(n) { if (n ≟ 0) return 0; return f(n - 1) + 1; }\nprint f(4000);");
+                interpreter.interpret(&program).unwrap();
+                let printed = output.0.borrow().clone();
+                String::from_utf8(printed).unwrap()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(printed, "4000\n");
+    }
+
+    #[test]
+    fn test_budgets_start_over_each_run() {
+        let mut interpreter = Interpreter::with_output(Box::new(SharedOutput::default()));
+        interpreter.set_limits(Limits { max_steps: Some(50), ..Limits::default() });
+        // about 30 steps: two runs' worth would be over the limit
+        let program = parse("var i ← 0; while (i < 30) i ← i + 1;");
+
+        for _ in 0..3 {
+            assert_eq!(interpreter.interpret(&program), Ok(()));
+        }
+        let twice = parse("var i ← 0; while (i < 60) i ← i + 1;");
+        let error = interpreter.interpret(&twice).unwrap_err();
+        assert_eq!(error.kind, crate::runtime_error::ErrorKind::LimitExceeded(Limit::Steps));
+    }
+}
@@ -0,0 +1,28 @@
+//! Contains the tree-walking interpreter, which runs a
+//! program by walking its syntax tree.
+//!
+//! - `interpreter_struct` has the `Interpreter` itself.
+//! - `value` has the `Value` enum for what expressions evaluate to.
+//! - `environment` has the `Environment` struct, one per scope.
+//! - `runtime_error` has the `RuntimeError` struct.
+//! - `callable` has the `Callable` trait, which `lox_function`
+//!   (functions declared in Lox) and `native_function` (functions
+//!   written in Rust) implement.
+//! - `native_registry` has the `NativeRegistry` for adding native
+//!   functions, and `core_library` has the ones every interpreter
+//!   starts with.
+//! - `lox_class` has classes, and `instance` has the objects
+//!   that calling a class makes.
+
+
+pub mod interpreter_struct;
+pub mod value;
+pub mod environment;
+pub mod runtime_error;
+pub mod callable;
+pub mod lox_function;
+pub mod native_function;
+pub mod native_registry;
+pub mod core_library;
+pub mod lox_class;
+pub mod instance;
@@ -0,0 +1,72 @@
+//! Contains the `LoxClass` struct.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::callable::Callable;
+use crate::instance::Instance;
+use crate::interpreter_struct::Interpreter;
+use crate::lox_function::LoxFunction;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// A class declared in Lox with `class`.
+///
+/// Calling a class makes a new instance of it, running its `init`
+/// method (if it has one) with the arguments.
+#[derive(Debug)]
+pub struct LoxClass {
+    pub name: String,
+
+    /// The class this one inherits from, if any.
+    pub superclass: Option<Rc<LoxClass>>,
+
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(name: &str, superclass: Option<Rc<LoxClass>>, methods: HashMap<String, Rc<LoxFunction>>) -> Self {
+        //! Create a new `LoxClass` with `methods`.
+        LoxClass {
+            name: name.to_string(),
+            superclass,
+            methods,
+        }
+    }
+
+    /// The method called `name`, not yet bound to an instance.
+    ///
+    /// Methods the class doesn't define itself are looked for up
+    /// the chain of superclasses.
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match (self.methods.get(name), &self.superclass) {
+            (Some(method), _) => Some(Rc::clone(method)),
+            (None, Some(superclass)) => superclass.find_method(name),
+            (None, None) => None,
+        }
+    }
+
+    /// How many arguments calling the class takes, which is however
+    /// many `init` takes.
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    /// Make a new instance of `class` and initialize it.
+    ///
+    /// This isn't `Callable::call` because the instance has to keep
+    /// hold of the class, which needs the `Rc` and not just `&self`.
+    /// The interpreter has already checked that there are `arity()`
+    /// arguments.
+    pub fn instantiate(class: &Rc<LoxClass>, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let instance = Rc::new(RefCell::new(Instance::new(Rc::clone(class))));
+        interpreter.hold(&instance, size_of::<Instance>())?;
+        let instance = Value::Instance(instance);
+        if let Some(init) = class.find_method("init") {
+            init.bind(instance.clone()).call(interpreter, arguments)?;
+        }
+        Ok(instance)
+    }
+}
@@ -0,0 +1,88 @@
+//! Contains the `LoxFunction` struct.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ast::stmt::FunctionDecl;
+use resolver::program::Program;
+
+use crate::callable::Callable;
+use crate::environment::Environment;
+use crate::interpreter_struct::Interpreter;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// A function declared in Lox with `fun`.
+///
+/// The function keeps the environment it was declared in (its
+/// closure), so the body can still see the variables around the
+/// declaration after the scope they were in has ended.  It also
+/// keeps the `Program` its body lives in, since a REPL line's
+/// tree is otherwise dropped once the line has run.
+#[derive(Debug)]
+pub struct LoxFunction {
+    program: Rc<Program>,
+    declaration: FunctionDecl,
+    closure: Rc<RefCell<Environment>>,
+
+    /// Whether this is a class's `init` method, which always gives
+    /// back the instance.
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        program: Rc<Program>,
+        declaration: FunctionDecl,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        //! Create a new `LoxFunction` that closes over `closure`.
+        LoxFunction { program, declaration, closure, is_initializer }
+    }
+
+    /// Make a copy of this method with `this` bound to `instance`.
+    ///
+    /// `this` goes in a scope of its own between the method and its
+    /// closure, which is where the resolver expects to find it.
+    pub fn bind(&self, instance: Value) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", instance);
+        LoxFunction {
+            program: Rc::clone(&self.program),
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl Callable for LoxFunction {
+    fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    /// Run the body in a new scope inside the closure, with the
+    /// parameters bound to the arguments.  Falling off the end of
+    /// the body gives `nil`, except in `init`, which gives `this`.
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let variables = arguments.len();
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+        let environment = Rc::new(RefCell::new(environment));
+        interpreter.hold_environment(&environment, variables)?;
+
+        let returned = interpreter.execute_block(&self.program, &self.declaration.body, environment)?;
+        if self.is_initializer {
+            return Ok(self.closure.borrow().get_here("this").expect("methods are bound before they're called"));
+        }
+        Ok(returned.unwrap_or(Value::Nil))
+    }
+}
@@ -0,0 +1,56 @@
+//! Contains the `NativeFunction` struct.
+
+use std::rc::Rc;
+
+use crate::callable::Callable;
+use crate::interpreter_struct::Interpreter;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// The Rust side of a native function.
+///
+/// It gets the interpreter that's calling it (to print, say) and
+/// the arguments, of which there are always exactly the arity the
+/// function was registered with.
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, RuntimeError>;
+
+
+/// A function written in Rust that Lox code can call.
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, function: Rc<NativeFn>) -> Self {
+        //! Create a new `NativeFunction` called `name`.
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function,
+        }
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        (self.function)(interpreter, &arguments)
+    }
+}
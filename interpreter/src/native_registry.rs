@@ -0,0 +1,66 @@
+//! Contains the `NativeRegistry` struct.
+
+use std::rc::Rc;
+
+use crate::interpreter_struct::Interpreter;
+use crate::native_function::NativeFunction;
+use crate::runtime_error::RuntimeError;
+use crate::value::Value;
+
+
+/// A set of native functions to make available to Lox code.
+///
+/// Each one is registered with the name Lox code calls it by, how
+/// many arguments it takes, and the Rust closure that runs it.
+/// Installing the registry in an interpreter defines them all as
+/// globals.  This is how the core library gets in, and how
+/// programs that embed the interpreter add their own functions.
+///
+/// ```
+/// use interpreter::interpreter_struct::Interpreter;
+/// use interpreter::native_registry::NativeRegistry;
+/// use interpreter::runtime_error::RuntimeError;
+/// use interpreter::value::Value;
+///
+/// let mut interpreter = Interpreter::new();
+/// let mut registry = NativeRegistry::new();
+/// registry.register("double", 1, |_interpreter, arguments| match &arguments[0] {
+///     Value::Number(number) => Ok(Value::Number(number * 2.0)),
+///     _ => Err(RuntimeError::native("double() expects a number.")),
+/// });
+/// interpreter.install(&registry);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NativeRegistry {
+    natives: Vec<NativeFunction>,
+}
+
+impl NativeRegistry {
+    pub fn new() -> Self {
+        //! Create a new, empty `NativeRegistry`.
+        NativeRegistry::default()
+    }
+
+    pub fn core() -> Self {
+        //! Create a new `NativeRegistry` with the core library in it.
+        let mut registry = NativeRegistry::new();
+        crate::core_library::register(&mut registry);
+        registry
+    }
+
+    /// Add a native function.  Registering a name twice replaces
+    /// the first one.
+    pub fn register<F>(&mut self, name: &str, arity: usize, function: F) -> &mut Self
+    where
+        F: Fn(&mut Interpreter, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        self.natives.retain(|native| crate::callable::Callable::name(native) != name);
+        self.natives.push(NativeFunction::new(name, arity, Rc::new(function)));
+        self
+    }
+
+    /// Every function registered so far, in the order they were added.
+    pub fn natives(&self) -> &[NativeFunction] {
+        &self.natives
+    }
+}
@@ -0,0 +1,101 @@
+//! Contains the `RuntimeError` struct.
+
+use diagnostic::diagnostic::Diagnostic;
+use token::span::Span;
+use limits::limits::Limit;
+use token::token::Token;
+
+
+/// What made the interpreter stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorKind {
+    /// Something went wrong in the program, like subtracting a string.
+    #[default]
+    Error,
+
+    /// The program called `exit(code)`.  That isn't a mistake, but
+    /// it has to unwind out of everything that's running the same
+    /// way an error does.
+    Exit(i32),
+
+    /// The program went over one of the interpreter's `Limits`.
+    LimitExceeded(Limit),
+}
+
+
+/// An error that happens while the program is running, like
+/// subtracting a string.
+///
+/// Keeps where the offending token is, so the error can be shown
+/// with the line it happened on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    /// The line the error happened on, or 0 if it came from a
+    /// native function and hasn't been given a place yet.
+    pub line: usize,
+    pub span: Span,
+    pub message: String,
+    pub kind: ErrorKind,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: &str) -> Self {
+        RuntimeError {
+            line: token.line,
+            span: token.span,
+            message: message.to_string(),
+            kind: ErrorKind::Error,
+        }
+    }
+
+    /// An error from a native function, which has no token of its
+    /// own to point at.  The interpreter points it at the call with
+    /// `located_at`.
+    pub fn native(message: &str) -> Self {
+        RuntimeError {
+            line: 0,
+            span: Span::default(),
+            message: message.to_string(),
+            kind: ErrorKind::Error,
+        }
+    }
+
+    /// Unwind because the program called `exit(code)`.
+    pub fn exit(code: i32) -> Self {
+        RuntimeError {
+            kind: ErrorKind::Exit(code),
+            ..RuntimeError::native(&format!("Exited with code {}.", code))
+        }
+    }
+
+    /// Stop because the program went over `limit`.
+    pub fn limit(limit: Limit, message: &str) -> Self {
+        RuntimeError {
+            kind: ErrorKind::LimitExceeded(limit),
+            ..RuntimeError::native(message)
+        }
+    }
+
+    /// Point the error at `token` if it doesn't have a place yet.
+    pub fn located_at(self, token: &Token) -> Self {
+        if self.line != 0 {
+            return self;
+        }
+        RuntimeError {
+            line: token.line,
+            span: token.span,
+            ..self
+        }
+    }
+
+    /// Turn the error into a `Diagnostic` pointing at the token.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::new(self.line, self.span, format!("Runtime error: {}", self.message))
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_diagnostic())
+    }
+}
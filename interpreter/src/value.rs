@@ -0,0 +1,114 @@
+//! Contains the `Value` enum.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use token::token::Literal;
+
+use crate::callable::Callable;
+use crate::instance::Instance;
+use crate::lox_class::LoxClass;
+
+
+/// What an expression evaluates to at runtime.
+///
+/// This mirrors `token::token::Literal`, plus the runtime objects
+/// that can't be written down as literals.  Cloning a value is
+/// cheap: strings and objects are reference counted.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+impl Value {
+    /// Lox's truthiness: `nil` and `false` are falsey, everything
+    /// else (including `0` and `«»`) is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// The name of the value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Callable(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Number(number) => Value::Number(*number),
+            Literal::String(string) => Value::String(Rc::from(string.as_str())),
+            Literal::Bool(boolean) => Value::Bool(*boolean),
+            Literal::Nil | Literal::NoLiteral => Value::Nil,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Value::Number(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(boolean: bool) -> Self {
+        Value::Bool(boolean)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::String(Rc::from(string))
+    }
+}
+
+/// The equality behind `≟` and `≠`.
+///
+/// Numbers, strings, booleans and `nil` are compared by what they
+/// hold, so two separately built strings with the same characters
+/// are equal.  Values of different types are never equal.  Functions,
+/// classes and instances are only equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Callable(left), Value::Callable(right)) => Rc::ptr_eq(left, right),
+            (Value::Class(left), Value::Class(right)) => Rc::ptr_eq(left, right),
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+/// How `print` shows a value.
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(boolean) => write!(f, "{}", boolean),
+            // `f64`'s Display already leaves off a trailing `.0`
+            Value::Number(number) => write!(f, "{}", number),
+            Value::String(string) => write!(f, "{}", string),
+            Value::Callable(callable) => write!(f, "<fn {}>", callable.name()),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Contains the `Limits` struct, the caps on how much a program
+//! can do that both the interpreter and the VM keep to.
+
+
+pub mod limits;
@@ -0,0 +1,112 @@
+//! Contains the `Limits` struct.
+
+use std::time::Duration;
+
+
+/// How deep calls can nest unless the limits say otherwise.
+///
+/// Both engines use it, so that recursion that runs on one runs
+/// on the other, as long as it fits in the interpreter's stack
+/// limit.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 4096;
+
+/// How much of the Rust stack a run can use unless the limits say
+/// otherwise.
+///
+/// Every Lox call and every nested statement or expression takes
+/// Rust stack frames, so this is what really keeps deep recursion
+/// from overflowing the stack.  It leaves room to spare in a main
+/// thread's 8MB stack, and in the 2MB that spawned threads get.
+pub const DEFAULT_MAX_STACK_BYTES: usize = 1 << 20;
+
+
+/// Caps on how much a program can do, for running code you don't
+/// trust.
+///
+/// `None` means no cap.  The budgets for steps, allocation and
+/// time start over each time the interpreter or the VM is asked
+/// to run something.  The heap cap doesn't: what's alive is alive,
+/// whichever run made it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// How many steps a run can take.
+    ///
+    /// The interpreter counts statements run and calls made.  The
+    /// VM counts calls and jumps back to the top of a loop, which
+    /// is enough to stop anything that doesn't end by itself, so
+    /// the same program takes fewer steps there.
+    pub max_steps: Option<u64>,
+
+    /// How many calls can be in progress at once.
+    pub max_call_depth: Option<usize>,
+
+    /// How many bytes of the Rust stack a run can use.  Going
+    /// over this is an error rather than a crash, so it should be
+    /// well under the size of the stack the interpreter runs on.
+    ///
+    /// Only the interpreter needs this.  The VM doesn't recurse on
+    /// the Rust stack, so it leaves it to `max_call_depth`.
+    pub max_stack_bytes: Option<usize>,
+
+    /// How many bytes a run can allocate in all, for strings,
+    /// variables, objects, the scope of each block it enters and so
+    /// on.
+    ///
+    /// This is a budget for the whole run, not a cap on how much
+    /// memory is in use at once: what's freed isn't given back, so
+    /// a long enough loop runs out even if it keeps nothing.  It
+    /// stops a program that grows without bound, and together with
+    /// `max_steps` it bounds how much work a run can make.
+    pub max_allocated_bytes: Option<usize>,
+
+    /// How many bytes of strings, variables, objects and scopes can
+    /// be alive at once.
+    ///
+    /// Unlike `max_allocated_bytes`, what's been dropped is given
+    /// back, so a loop that makes garbage can run for as long as
+    /// it likes while a program that keeps everything is stopped.
+    /// The VM collects garbage when its heap gets this big, and
+    /// only stops the program if that leaves it too big still.
+    pub max_heap_bytes: Option<usize>,
+
+    /// How long a run can take, by the clock on the wall.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_stack_bytes: Some(DEFAULT_MAX_STACK_BYTES),
+            max_allocated_bytes: None,
+            max_heap_bytes: None,
+            timeout: None,
+        }
+    }
+}
+
+
+/// Which limit a program went over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps,
+    CallDepth,
+    Stack,
+    Allocation,
+    Heap,
+    Time,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Steps => write!(f, "step"),
+            Limit::CallDepth => write!(f, "call depth"),
+            Limit::Stack => write!(f, "stack"),
+            Limit::Allocation => write!(f, "allocation"),
+            Limit::Heap => write!(f, "heap"),
+            Limit::Time => write!(f, "time"),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Contains the `Error` enum.
+
+use bytecode::load_error::LoadError;
+use diagnostic::diagnostic::Diagnostic;
+use interpreter::runtime_error::RuntimeError;
+use vm::vm_error::VmError;
+
+
+/// Anything that can go wrong running Lox code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The code didn't compile, so none of it ran.  Every problem
+    /// found is here, in the order they appear in the code.
+    Compile(Vec<Diagnostic>),
+
+    /// The code hit an error while it was running (or called
+    /// `exit`, which `RuntimeError::kind` says).
+    Runtime(RuntimeError),
+
+    /// The code hit an error while running on the `Vm` (or called
+    /// `exit`, which `VmError::kind` says).
+    Vm(VmError),
+
+    /// A compiled `.loxc` file couldn't be loaded, so none of it
+    /// ran.
+    Load(LoadError),
+}
+
+impl Error {
+    /// Render the error with the lines of `source` it's about,
+    /// the same way the command line tool shows it.  The VM's
+    /// errors have their stack trace under that, or on its own if
+    /// the code was loaded from a `.loxc` file, which doesn't know
+    /// where in the source it came from.
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            Error::Compile(diagnostics) => {
+                diagnostics.iter().map(|diagnostic| diagnostic.render(source)).collect::<Vec<String>>().join("\n")
+            }
+            Error::Runtime(error) => error.to_diagnostic().render(source),
+            Error::Vm(error) => match error.span() {
+                Some(span) => {
+                    let diagnostic = Diagnostic::new(error.line(), span, format!("Runtime error: {}", error.message));
+                    let trace = error.trace.iter().map(|frame| format!("\n{}", frame)).collect::<String>();
+                    format!("{}{}", diagnostic.render(source), trace)
+                }
+                None => error.to_string(),
+            },
+            Error::Load(error) => error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Compile(diagnostics) => {
+                let lines = diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect::<Vec<String>>();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Error::Runtime(error) => write!(f, "{}", error),
+            Error::Vm(error) => write!(f, "{}", error),
+            Error::Load(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+impl From<VmError> for Error {
+    fn from(error: VmError) -> Self {
+        Error::Vm(error)
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(error: LoadError) -> Self {
+        Error::Load(error)
+    }
+}
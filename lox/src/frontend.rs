@@ -0,0 +1,62 @@
+//! Contains the `parse_source`, `resolve_source`,
+//! `compile_source` and `compile_source_at` functions
+//!
+//! This is the front end that running, checking and printing
+//! code all share.
+
+use ast::arena::Ast;
+use bytecode::function::Function;
+use compiler::opt_level::OptLevel;
+use diagnostic::diagnostic::Diagnostic;
+use parser::parser_struct::Parser;
+use resolver::program::Program;
+use scanner::scanner_struct::Scanner;
+
+
+/// Scan and parse the code.
+///
+/// Returns the syntax tree if there were no problems, otherwise
+/// every problem the scanner and the parser found, in the order
+/// they appear in the code.
+pub fn parse_source(code: &str) -> Result<Ast, Vec<Diagnostic>> {
+    let mut myscanner = Scanner::new(code);
+    myscanner.scan_tokens();
+    let mut diagnostics = std::mem::take(&mut myscanner.errors);
+
+    let mut myparser = Parser::new(myscanner.tokens);
+    match myparser.parse() {
+        Ok(ast) if diagnostics.is_empty() => return Ok(ast),
+        Ok(_) => {}
+        Err(errors) => diagnostics.extend(errors.iter().map(|error| error.to_diagnostic())),
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    Err(diagnostics)
+}
+
+
+/// Scan, parse and resolve the code.
+///
+/// The resolver only runs if the code parsed, since it needs a
+/// whole tree.  Returns the resolved program, or every problem
+/// found, in the order they appear in the code.
+pub fn resolve_source(code: &str) -> Result<Program, Vec<Diagnostic>> {
+    let ast = parse_source(code)?;
+    resolver::resolver_struct::resolve(ast)
+        .map_err(|errors| errors.iter().map(|error| error.to_diagnostic()).collect())
+}
+
+
+/// Compile the code to bytecode, for the VM.
+///
+/// Returns the function for the code's top level, or every
+/// problem found, in the order they appear in the code.
+pub fn compile_source(code: &str) -> Result<Function, Vec<Diagnostic>> {
+    compiler::compiler_struct::compile(code)
+}
+
+
+/// Compile the code to bytecode and optimize it at `level`.
+pub fn compile_source_at(code: &str, level: OptLevel) -> Result<Function, Vec<Diagnostic>> {
+    compile_source(code).map(|function| compiler::optimizer::optimize(function, level))
+}
@@ -0,0 +1,152 @@
+//! Contains the `Interpreter` struct.
+
+use std::io::Write;
+use std::rc::Rc;
+
+use limits::limits::Limits;
+use interpreter::native_registry::NativeRegistry;
+use interpreter::runtime_error::RuntimeError;
+use interpreter::value::Value;
+
+use crate::error::Error;
+use crate::NativeContext;
+
+
+/// A Lox interpreter to embed in a Rust program.
+///
+/// Globals live as long as the interpreter does, so code run by
+/// one `eval` can use what an earlier one declared, and Rust can
+/// read and write them in between.
+pub struct Interpreter {
+    inner: interpreter::interpreter_struct::Interpreter,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        //! Create a new `Interpreter` that prints to stdout, with
+        //! the core library already defined.
+        Interpreter::with_output(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        //! Create a new `Interpreter` that prints to `output`.
+        Interpreter {
+            inner: interpreter::interpreter_struct::Interpreter::with_output(output),
+        }
+    }
+
+    pub fn with_limits(limits: Limits) -> Self {
+        //! Create a new `Interpreter` that prints to stdout, for
+        //! running code that can't be trusted to stop by itself.
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(limits);
+        interpreter
+    }
+
+    /// Change how much each `eval` or `call_function` is allowed
+    /// to do.  Going over a limit is a `Runtime` error whose kind
+    /// is `ErrorKind::LimitExceeded`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.set_limits(limits);
+    }
+
+    /// Run `code`.
+    ///
+    /// Gives back the value of the last statement if it's an
+    /// expression statement, so `eval("1 + 2;")` is `3`, and `nil`
+    /// otherwise.  Nothing runs if the code doesn't compile.
+    pub fn eval(&mut self, code: &str) -> Result<Value, Error> {
+        let program = crate::frontend::resolve_source(code).map_err(Error::Compile)?;
+        Ok(self.inner.interpret_to_value(&Rc::new(program))?)
+    }
+
+    /// Define (or redefine) the global variable `name`.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.inner.globals.borrow_mut().define(name, value);
+    }
+
+    /// The value of the global variable `name`, if there is one.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.inner.globals.borrow().get_here(name)
+    }
+
+    /// Call the global function (or class) `name` with `arguments`.
+    pub fn call_function(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+        let Some(callee) = self.get_global(name) else {
+            return Err(Error::Runtime(RuntimeError::native(&format!("Undefined variable '{}'.", name))));
+        };
+        self.inner.reset_budget();
+        Ok(self.inner.call(callee, arguments)?)
+    }
+
+    /// Define every native function in `registry` as a global.
+    pub fn install(&mut self, registry: &NativeRegistry) {
+        self.inner.install(registry);
+    }
+
+    /// Define one native function as a global.
+    ///
+    /// This is a shortcut for installing a `NativeRegistry` with
+    /// just the one function in it.
+    pub fn register_native<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&mut NativeContext, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    {
+        let mut registry = NativeRegistry::new();
+        registry.register(name, arity, function);
+        self.install(&registry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interpreter::runtime_error::ErrorKind;
+
+    #[test]
+    fn test_eval_gives_back_the_last_expression() {
+        let mut lox = Interpreter::new();
+
+        assert_eq!(lox.eval("var a ← 20; a * 2 + 2;").unwrap(), Value::from(42.0));
+        assert_eq!(lox.eval("var b ← 1;").unwrap(), Value::Nil);
+        assert_eq!(lox.get_global("a"), Some(Value::from(20.0)));
+    }
+
+    #[test]
+    fn test_errors_say_what_kind_they_are() {
+        let mut lox = Interpreter::new();
+
+        assert!(matches!(lox.eval("print ;"), Err(Error::Compile(_))));
+        assert!(matches!(lox.eval("-«a»;"), Err(Error::Compile(_))));
+        assert!(matches!(lox.eval("«a» - 1;"), Err(Error::Runtime(_))));
+        assert!(matches!(lox.call_function("missing", vec![]), Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn test_host_functions_can_be_called_from_lox() {
+        let mut lox = Interpreter::new();
+        lox.register_native("greet", 1, |_context: &mut NativeContext, arguments| {
+            Ok(Value::from(format!("hello, {}", arguments[0]).as_str()))
+        });
+
+        assert_eq!(lox.eval("greet(«world»);").unwrap(), Value::from("hello, world"));
+    }
+
+    #[test]
+    fn test_limits_are_runtime_errors() {
+        let mut lox = Interpreter::with_limits(Limits { max_steps: Some(100), ..Limits::default() });
+        lox.eval("fun spin() { while (true) {} }").unwrap();
+
+        let Err(Error::Runtime(error)) = lox.call_function("spin", vec![]) else {
+            panic!("spin() should have hit the step limit");
+        };
+        assert_eq!(error.kind, ErrorKind::LimitExceeded(crate::Limit::Steps));
+        assert_eq!(lox.eval("1 + 1;").unwrap(), Value::from(2.0));
+    }
+}
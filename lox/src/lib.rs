@@ -0,0 +1,43 @@
+//! Lox as a library, for programs that want to run Lox code.
+//!
+//! ```
+//! let mut lox = lox::Interpreter::new();
+//! lox.set_global("base", lox::Value::from(40.0));
+//! lox.eval("fun add(a, b) { return a + b; }").unwrap();
+//!
+//! let answer = lox.call_function("add", vec![lox.get_global("base").unwrap(), lox::Value::from(2.0)]);
+//! assert_eq!(answer.unwrap(), lox::Value::from(42.0));
+//! ```
+//!
+//! - `interpreter_struct` has the `Interpreter`, the main way in.
+//! - `vm_struct` has the `Vm`, which runs the same code compiled
+//!   to bytecode, a lot faster.
+//! - `error` has the `Error` enum for everything that can go wrong.
+//! - `frontend` has the functions that turn source code into a
+//!   tree, for tools that want to check or print code without
+//!   running it, or compile it without running it.
+
+
+pub mod interpreter_struct;
+pub mod vm_struct;
+pub mod error;
+pub mod frontend;
+
+pub use interpreter_struct::Interpreter;
+pub use vm_struct::Vm;
+pub use error::Error;
+
+pub use bytecode::load_error::LoadError;
+pub use compiler::opt_level::OptLevel;
+pub use diagnostic::diagnostic::Diagnostic;
+pub use limits::limits::{Limit, Limits};
+/// What a native function gets to reach back into the running
+/// interpreter with: to call Lox values, count allocations
+/// against the limits, or print where `print` does.
+pub use interpreter::interpreter_struct::Interpreter as NativeContext;
+pub use interpreter::native_registry::NativeRegistry;
+pub use interpreter::runtime_error::{ErrorKind, RuntimeError};
+pub use interpreter::value::Value;
+pub use vm::gc_options::{GcMode, GcOptions};
+pub use vm::gc_stats::GcStats;
+pub use vm::vm_error::{ErrorKind as VmErrorKind, VmError};
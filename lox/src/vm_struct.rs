@@ -0,0 +1,312 @@
+//! Contains the `Vm` struct.
+
+use std::io::Write;
+
+use crate::error::Error;
+use crate::{GcOptions, GcStats, Limits, OptLevel};
+
+
+/// A Lox virtual machine to embed in a Rust program.
+///
+/// This compiles code to bytecode and runs it, which is a lot
+/// faster than the `Interpreter` for code that does real work.
+/// Programs print and fail the same way on both.  Like the
+/// `Interpreter`, globals live as long as the VM does.
+pub struct Vm {
+    inner: vm::vm_struct::Vm,
+    opt_level: OptLevel,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        //! Create a new `Vm` that prints to stdout, with the core
+        //! library already defined.
+        Vm::with_output(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        //! Create a new `Vm` that prints to `output`.
+        Vm {
+            inner: vm::vm_struct::Vm::with_output(output),
+            opt_level: OptLevel::default(),
+        }
+    }
+
+    pub fn with_gc_options(options: GcOptions) -> Self {
+        //! Create a new `Vm` that prints to stdout, with its
+        //! garbage collector set up by `options`.
+        let mut vm = Vm::new();
+        vm.set_gc_options(options);
+        vm
+    }
+
+    pub fn with_limits(limits: Limits) -> Self {
+        //! Create a new `Vm` that prints to stdout, for running
+        //! code that can't be trusted to stop by itself.
+        let mut vm = Vm::new();
+        vm.set_limits(limits);
+        vm
+    }
+
+    /// Change how much each `eval` is allowed to do.  Going over a
+    /// limit is a `Vm` error whose kind is
+    /// `VmErrorKind::LimitExceeded`.  The VM doesn't use
+    /// `max_stack_bytes`, since it doesn't recurse on the Rust
+    /// stack.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.set_limits(limits);
+    }
+
+    /// Change how the garbage collector behaves.
+    pub fn set_gc_options(&mut self, options: GcOptions) {
+        self.inner.set_gc_options(options);
+    }
+
+    /// Change how much `eval` optimizes the code it compiles.
+    pub fn set_opt_level(&mut self, level: OptLevel) {
+        self.opt_level = level;
+    }
+
+    /// How much time the garbage collector has taken so far, to
+    /// check for long pauses.
+    pub fn gc_stats(&self) -> GcStats {
+        self.inner.gc_stats()
+    }
+
+    /// Compile and run `code`.  Nothing runs if the code doesn't
+    /// compile.
+    pub fn eval(&mut self, code: &str) -> Result<(), Error> {
+        let function = crate::frontend::compile_source_at(code, self.opt_level).map_err(Error::Compile)?;
+        self.inner.interpret(&function).map_err(Error::Vm)
+    }
+
+    /// Load and run a script compiled to the bytes of a `.loxc`
+    /// file (by `bytecode::loxc::save`).  Nothing runs if the file
+    /// is damaged or its code isn't safe to run.
+    pub fn eval_compiled(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let function = bytecode::loxc::load(bytes).map_err(Error::Load)?;
+        self.inner.interpret(&function).map_err(Error::Vm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GcMode, Limit};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Somewhere to print to that the test can read back.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Run `code` on both engines and give back what each printed,
+    /// followed by the message of the error it stopped on, if any.
+    fn run_both(code: &str) -> (String, String) {
+        let tree_output = SharedOutput::default();
+        let tree_error = match crate::Interpreter::with_output(Box::new(tree_output.clone())).eval(code) {
+            Err(Error::Runtime(error)) => error.message,
+            Err(error) => panic!("{} didn't compile: {}", code, error),
+            Ok(_) => String::new(),
+        };
+
+        // The VM collects garbage as often as it can, both ways, so
+        // anything it forgets to keep alive shows up as a difference.
+        let mut vm_runs = [GcMode::StopTheWorld, GcMode::Incremental].map(|mode| {
+            let vm_output = SharedOutput::default();
+            let mut vm = Vm::with_output(Box::new(vm_output.clone()));
+            vm.set_gc_options(GcOptions { mode, stress: true, log: false });
+            let vm_error = match vm.eval(code) {
+                Err(Error::Vm(error)) => error.message,
+                Err(error) => panic!("{} didn't compile: {}", code, error),
+                Ok(_) => String::new(),
+            };
+            let printed = vm_output.0.borrow().clone();
+            String::from_utf8(printed).unwrap() + &vm_error
+        });
+        assert_eq!(vm_runs[0], vm_runs[1], "the collectors disagree on: {}", code);
+
+        let tree = String::from_utf8(tree_output.0.borrow().clone()).unwrap() + &tree_error;
+        (tree, std::mem::take(&mut vm_runs[0]))
+    }
+
+    /// Compile `code` at `level` to the bytes of a `.loxc` file,
+    /// then load and run those, giving back the same as `run_both`
+    /// does.  Loading checks the code is safe to run, so this checks
+    /// the optimizer's code is too.
+    fn run_compiled(code: &str, level: OptLevel) -> String {
+        let function = crate::frontend::compile_source_at(code, level).unwrap();
+        let output = SharedOutput::default();
+        let error = match Vm::with_output(Box::new(output.clone())).eval_compiled(&bytecode::loxc::save(&function)) {
+            Err(Error::Vm(error)) => error.message,
+            Err(error) => panic!("{} didn't load: {}", code, error),
+            Ok(_) => String::new(),
+        };
+        let printed = output.0.borrow().clone();
+        String::from_utf8(printed).unwrap() + &error
+    }
+
+    #[test]
+    fn test_both_engines_run_programs_the_same() {
+        let programs = [
+            "print 1 + 2 * 3; print 7 / 2; print ⁻(4 - 6); print 1 ≟ 1; print «a» ≠ «b»; print nil;",
+            "print «con» + «cat»; print 2 ≤ 2; print 3 ≥ 4; print ¬nil; print true and false or «x»;",
+            "var a ← 1; { var a ← 2; print a; } print a; a ← a + 41; print a;",
+            "var total ← 0; for (var i ← 0; i < 10; i ← i + 1) total ← total + i; print total;",
+            "var n ← 3; while (n > 0) { print n; n ← n - 1; } if (n) print «yes»; else print «no»;",
+            "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); } print fib(15); print fib;",
+            "fun noop() {} print noop(); print clock;",
+            "print len(«héllo»); print str(12) + «!»; print num(« 2.5 ») * 2; print num(«x»); print type(nil);",
+            "class Pair { init(a, b) { this.a ← a; this.b ← b; } sum() { return this.a + this.b; } }
+             var p ← Pair(1, 2); print p.sum(); print p; print Pair; p.a ← 40; print p.sum();",
+            "class A { name() { return «A»; } } class B < A {} var f ← B().name; print f(); print B().init;",
+            "class C { init() { return; } } var c ← C(); print c.init();",
+            "fun adder(n) { fun add(m) { return n + m; } return add; } print adder(40)(2); print adder;",
+            "var f; { var a ← «one»; fun g() { print a; a ← «two»; } f ← g; } f(); f();",
+            "class A { init(x) { this.x ← x; } get() { return this.x; } }
+             class B < A { init(x) { super.init(x * 2); } get() { fun twice() { return super.get() * 2; } return twice(); } }
+             print B(10).get();",
+            "var x ← 1; x.y;",
+            "«a» < 1;",
+            "print missing;",
+            "undefined ← 1;",
+            "fun f(a) {} f(1, 2);",
+            "var n ← 3; n();",
+            "class A {} A().missing;",
+            "class A {} A(1);",
+            "var NotAClass ← 1; class B < NotAClass {}",
+            "print len(1);",
+            "var n ← nil; print ⁻n;",
+            "print 1 + 2 * 3 ≟ 7; if (¬¬«s») print ⁻2; while (false) print 1; print «a» + «b» ≠ «ab»;
+             { var a ← 1; var b ← ¬¬(a < 2); a; print b; } print 0 / 0 ≟ 0 / 0;",
+            "print ⁻«a» + 1;",
+        ];
+
+        for program in programs {
+            let (tree, vm) = run_both(program);
+            assert_eq!(vm, tree, "the engines disagree on: {}", program);
+            assert_eq!(run_compiled(program, OptLevel::O0), vm, "saving and loading changes: {}", program);
+            assert_eq!(run_compiled(program, OptLevel::O1), vm, "optimizing changes: {}", program);
+        }
+    }
+
+    #[test]
+    fn test_names_and_functions_can_come_after_256_other_constants() {
+        let literals: String = (0..300).map(|number| format!("n ← {};", number)).collect();
+        let program = String::from("var n;")
+            + &literals
+            + "var y ← 1; print y; fun f() { return y; } y ← 2; print f();
+               class A { get() { return this.x; } } class B < A { get() { return super.get() + 1; } }
+               var b ← B(); b.x ← y; print b.get(); print b.x;";
+
+        let (tree, vm) = run_both(&program);
+        assert_eq!(tree, "1\n2\n3\n2\n");
+        assert_eq!(vm, tree);
+        assert_eq!(run_compiled(&program, OptLevel::O0), vm);
+        assert_eq!(run_compiled(&program, OptLevel::O1), vm);
+    }
+
+    #[test]
+    fn test_errors_render_the_same_on_both_engines() {
+        let programs = [
+            "print 1 +\n  ;",
+            "fun half(x) {\n  return x / 2;\n}\nprint half(«a»);",
+            "class A {}\nvar a ← A();\nprint 1 + a.missing;",
+            "var a ← 1;\na.b ← 2;",
+            "print ⁻«x»;",
+            "undefined ← 1;",
+            "fun f(a) {}\nf(1,\n  2);",
+            "class A {}\nA().missing(1);",
+        ];
+
+        for program in programs {
+            let tree = crate::Interpreter::new().eval(program).map(|_| ()).unwrap_err().render(program);
+            let vm = Vm::new().eval(program).unwrap_err().render(program);
+
+            // the VM's has the stack trace under it
+            let (diagnostic, trace) = vm.split_at(tree.len());
+            assert_eq!(diagnostic, tree, "the engines disagree on: {}", program);
+            assert!(trace.trim_start().lines().all(|line| line.starts_with("[line ")), "not a stack trace: {:?}", trace);
+        }
+
+        let program = "fun half(x) {\n  return x / 2;\n}\nprint half(«a»);";
+        assert_eq!(
+            Vm::new().eval(program).unwrap_err().render(program),
+            "[line 2] Runtime error: Operands must be numbers.\n      return x / 2;\n               ^\n[line 2] in half()\n[line 4] in script",
+        );
+    }
+
+    /// Run `code` on both engines with `limits`, giving back the
+    /// limit each went over, if any.
+    fn run_limited(code: &str, limits: Limits) -> (Option<Limit>, Option<Limit>) {
+        let mut interpreter = crate::Interpreter::with_output(Box::new(SharedOutput::default()));
+        interpreter.set_limits(limits);
+        let tree = match interpreter.eval(code) {
+            Err(Error::Runtime(error)) => match error.kind {
+                crate::ErrorKind::LimitExceeded(limit) => Some(limit),
+                _ => panic!("{} failed: {}", code, error.message),
+            },
+            Err(error) => panic!("{} didn't compile: {}", code, error),
+            Ok(_) => None,
+        };
+
+        // an incremental collection frees garbage later, which
+        // mustn't make the heap limit stop a program any sooner
+        let [vm, incremental] = [GcMode::StopTheWorld, GcMode::Incremental].map(|mode| {
+            let mut vm = Vm::with_output(Box::new(SharedOutput::default()));
+            vm.set_gc_options(GcOptions { mode, stress: false, log: false });
+            vm.set_limits(limits);
+            match vm.eval(code) {
+                Err(Error::Vm(error)) => match error.kind {
+                    crate::VmErrorKind::LimitExceeded(limit) => Some(limit),
+                    _ => panic!("{} failed: {}", code, error.message),
+                },
+                Err(error) => panic!("{} didn't compile: {}", code, error),
+                Ok(_) => None,
+            }
+        });
+        assert_eq!(vm, incremental, "the collectors disagree on: {}", code);
+        (tree, vm)
+    }
+
+    #[test]
+    fn test_both_engines_keep_to_the_limits() {
+        let none = Limits { max_call_depth: None, max_stack_bytes: None, ..Limits::default() };
+        let spin = "while (true) {}";
+        let recurse = "fun f() { f(); } f();";
+        let double = "var s ← «ab»; while (true) s ← s + s;";
+        let keep = "class Node {} var list ← nil; while (true) { var node ← Node(); node.next ← list; list ← node; }";
+        // makes megabytes of strings, but never keeps more than one
+        let garbage = "for (var i ← 0; i < 20000; i ← i + 1) { var s ← str(i) + «, a string long enough to add up»; }";
+
+        let cases = [
+            (spin, Limits { max_steps: Some(1000), ..none }, Some(Limit::Steps)),
+            (recurse, Limits { max_call_depth: Some(50), ..none }, Some(Limit::CallDepth)),
+            (double, Limits { max_allocated_bytes: Some(1 << 20), ..none }, Some(Limit::Allocation)),
+            (garbage, Limits { max_allocated_bytes: Some(64 << 10), ..none }, Some(Limit::Allocation)),
+            (double, Limits { max_heap_bytes: Some(64 << 10), ..none }, Some(Limit::Heap)),
+            (keep, Limits { max_heap_bytes: Some(64 << 10), ..none }, Some(Limit::Heap)),
+            (garbage, Limits { max_heap_bytes: Some(64 << 10), ..none }, None),
+            (spin, Limits { timeout: Some(std::time::Duration::from_millis(20)), ..none }, Some(Limit::Time)),
+        ];
+        for (code, limits, limit) in cases {
+            assert_eq!(run_limited(code, limits), (limit, limit), "{} with {:?}", code, limits);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! Contains the parser, which turns the scanner's tokens
+//! into the statements in the `ast` crate.
+
+
+pub mod parser_struct;
+pub mod parse_error;
@@ -0,0 +1,40 @@
+//! Contains the `ParseError` struct.
+
+use diagnostic::diagnostic::Diagnostic;
+use token::token::Token;
+use token::token_type::TokenType;
+
+
+/// An error found while parsing.
+///
+/// Keeps the token the parser was looking at so the
+/// message can say where things went wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(token: Token, message: &str) -> Self {
+        ParseError {
+            token,
+            message: message.to_string(),
+        }
+    }
+
+    /// Turn the error into a `Diagnostic` pointing at the token.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let message = match self.token.token_type {
+            TokenType::EOF => format!("Error at end: {}", self.message),
+            _ => format!("Error at '{}': {}", self.token.lexeme, self.message),
+        };
+        Diagnostic::new(self.token.line, self.token.span, message)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_diagnostic())
+    }
+}
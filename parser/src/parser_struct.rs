@@ -0,0 +1,853 @@
+//! Contains the `Parser` struct.
+//!
+//! This is a recursive descent parser. Each grammar rule
+//! gets its own method, and the methods for the rules with
+//! lower precedence call the ones with higher precedence.
+//!
+//! ```text
+//! program     → declaration* EOF
+//! declaration → classDecl | funDecl | varDecl | statement
+//! classDecl   → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}"
+//! statement   → exprStmt | forStmt | ifStmt | printStmt
+//!             | returnStmt | whileStmt | block
+//! expression  → assignment
+//! assignment  → ( call "." )? IDENTIFIER "←" assignment | logic_or
+//! logic_or    → logic_and ( "or" logic_and )*
+//! logic_and   → equality ( "and" equality )*
+//! equality    → comparison ( ( "≠" | "≟" ) comparison )*
+//! comparison  → term ( ( ">" | "≥" | "<" | "≤" ) term )*
+//! term        → factor ( ( "-" | "+" ) factor )*
+//! factor      → unary ( ( "/" | "*" ) unary )*
+//! unary       → ( "¬" | "⁻" ) unary | call
+//! call        → primary ( "(" arguments? ")" | "." IDENTIFIER )*
+//! primary     → "true" | "false" | "nil" | "this" | NUMBER | STRING
+//!             | IDENTIFIER | "(" expression ")" | "super" "." IDENTIFIER
+//! ```
+//!
+//! The nodes go into an `Ast` arena as they're parsed, each with
+//! the span of the tokens it was parsed from.
+
+use ast::arena::{Ast, ExprId, NodeId, StmtId};
+use ast::expr::Expr;
+use ast::stmt::{ClassDecl, FunctionDecl, Stmt};
+use token::span::Span;
+use token::token::{Literal, Token};
+use token::token_type::TokenType;
+
+use crate::parse_error::ParseError;
+
+
+/// The most arguments (or parameters) a call can have.
+const MAX_ARGUMENTS: usize = 255;
+
+/// How deeply statements and expressions can be nested.
+///
+/// The parser recurses once per level, and so do the passes that
+/// walk the tree, so without a limit deeply nested (usually
+/// generated) code would overflow the stack.  With it, it's a
+/// normal parse error instead.
+const MAX_NESTING: usize = 200;
+
+/// How deep the tree under one expression can be.
+///
+/// A chain like `1 + 1 + … + 1` or `a.b.c…` is parsed in a loop,
+/// so it doesn't count towards `MAX_NESTING`, but the tree it makes
+/// is as deep as the chain is long and the passes that walk it still
+/// recurse once per level.  Those frames are much smaller than the
+/// parser's, so this can be a lot higher.
+const MAX_EXPR_DEPTH: usize = 10_000;
+
+
+/// The parser struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parser {
+    /// The tokens from the scanner. The last one is always `EOF`.
+    pub tokens: Vec<Token>,
+
+    /// Index of the next token to be consumed.
+    pub current: usize,
+
+    /// Every error found so far.
+    ///
+    /// The parser doesn't stop at the first error. It records it,
+    /// skips ahead to the next statement boundary and carries on,
+    /// so that one run reports all the syntax errors.
+    pub errors: Vec<ParseError>,
+
+    /// How many blocks we're inside of, so that `synchronize`
+    /// knows whether a `}` closes a block we're parsing.
+    block_depth: usize,
+
+    /// How many nested statements and expressions we're inside of.
+    /// See `MAX_NESTING`.
+    nesting: usize,
+
+    /// How deep the tree under each expression is, indexed like
+    /// the expressions.  See `add_expr`.
+    expr_depths: Vec<usize>,
+
+    /// Where the nodes go.
+    ast: Ast,
+}
+
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Parser {
+        //! Create a new `Parser`.
+        Parser {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            block_depth: 0,
+            nesting: 0,
+            expr_depths: Vec::new(),
+            ast: Ast::new(),
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Ast, Vec<ParseError>> {
+        //! Parse the whole program.
+        //!
+        //! This is the main method and purpose of the parser.
+        //!
+        //! Returns every error found if there were any.
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                self.ast.statements.push(statement);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(std::mem::take(&mut self.ast))
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Parse a declaration, recovering from any error inside it.
+    ///
+    /// This is the statement boundary where we recover, so on an
+    /// error, record it, synchronize and return `None`.
+    fn declaration(&mut self) -> Option<StmtId> {
+        let start = self.current;
+
+        let result = if self.match_types(&[TokenType::Class]) {
+            self.class_declaration()
+        } else if self.match_types(&[TokenType::Fun]) {
+            let keyword = self.previous().span;
+            self.function("function").map(|declaration| self.add_stmt(Stmt::Function(declaration), keyword))
+        } else if self.match_types(&[TokenType::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        match result {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                // make sure we always move forward, otherwise a stray
+                // token that synchronize stops at would loop forever
+                if self.current == start {
+                    self.advance();
+                }
+                None
+            }
+        }
+    }
+
+    /// Skip tokens until we're probably at the start of the next statement.
+    ///
+    /// That's either just after a `;`, or just before a keyword that
+    /// starts a statement, or just before the `}` that closes the
+    /// block we're in.  Anything in between would only produce
+    /// bogus follow-on errors.
+    ///
+    /// Braces that open inside the skipped tokens are skipped along
+    /// with everything up to their matching `}`, so a broken function
+    /// header doesn't leave its body behind to be misparsed.
+    fn synchronize(&mut self) {
+        let mut skipped_braces = 0;
+
+        while !self.is_at_end() {
+            if skipped_braces == 0 && self.current > 0 && self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::LeftBrace => skipped_braces += 1,
+                TokenType::RightBrace if skipped_braces > 0 => skipped_braces -= 1,
+                TokenType::RightBrace if self.block_depth > 0 => return,
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return if skipped_braces == 0 => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let superclass = if self.match_types(&[TokenType::Less]) {
+            let name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            let span = name.span;
+            Some(self.add_expr(Expr::Variable { name }, span)?)
+        } else {
+            None
+        };
+
+        // nest before the `{`, so that if it's too deep, the body is
+        // skipped along with it instead of being parsed one level up
+        self.nest()?;
+        let methods = self.class_body();
+        self.nesting -= 1;
+        let methods = methods?;
+
+        Ok(self.add_stmt(Stmt::Class(ClassDecl { name, superclass, methods }), keyword))
+    }
+
+    /// Parse a class body: its methods between braces.
+    fn class_body(&mut self) -> Result<Vec<FunctionDecl>, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(methods)
+    }
+
+    /// Parse the part of a function declaration after `fun`, or a
+    /// method in a class body.
+    ///
+    /// `kind` is only used in the error messages.
+    fn function(&mut self, kind: &str) -> Result<FunctionDecl, ParseError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    // report but keep going -- the parser isn't confused
+                    self.errors.push(ParseError::new(self.peek().clone(), "Can't have more than 255 parameters."));
+                }
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                if !self.match_types(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        // see class_declaration for why this nests before the `{`
+        self.nest()?;
+        let body = self
+            .consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))
+            .and_then(|_| self.block());
+        self.nesting -= 1;
+        let body = body?;
+
+        Ok(FunctionDecl { name, params, body })
+    }
+
+    fn var_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().span;
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+
+        let initializer = if self.match_types(&[TokenType::Assignment]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
+        Ok(self.add_stmt(Stmt::Var { name, initializer }, keyword))
+    }
+
+    fn statement(&mut self) -> Result<StmtId, ParseError> {
+        self.nest()?;
+        let result = self.nested_statement();
+        self.nesting -= 1;
+        result
+    }
+
+    fn nested_statement(&mut self) -> Result<StmtId, ParseError> {
+        if self.match_types(&[TokenType::For]) {
+            return self.for_statement();
+        }
+        if self.match_types(&[TokenType::If]) {
+            return self.if_statement();
+        }
+        if self.match_types(&[TokenType::Print]) {
+            return self.print_statement();
+        }
+        if self.match_types(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.match_types(&[TokenType::While]) {
+            return self.while_statement();
+        }
+        if self.match_types(&[TokenType::LeftBrace]) {
+            let brace = self.previous().span;
+            let statements = self.block()?;
+            return Ok(self.add_stmt(Stmt::Block { statements }, brace));
+        }
+        self.expression_statement()
+    }
+
+    /// Parse a `for` loop and desugar it into a `while` loop.
+    ///
+    /// `for (initializer; condition; increment) body` becomes
+    ///
+    /// ```text
+    /// {
+    ///     initializer;
+    ///     while (condition) {
+    ///         body;
+    ///         increment;
+    ///     }
+    /// }
+    /// ```
+    fn for_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_types(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_types(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.check(TokenType::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+
+        // the nodes we make up get the span of the whole loop
+        let span = keyword.to(self.previous().span);
+
+        if let Some(increment) = increment {
+            let increment = self.ast.add_stmt(Stmt::Expression { expression: increment }, self.ast.expr_span(increment));
+            body = self.ast.add_stmt(Stmt::Block { statements: vec![body, increment] }, span);
+        }
+
+        // a missing condition loops forever
+        let condition = match condition {
+            Some(condition) => condition,
+            None => self.add_expr(Expr::Literal { value: Literal::Bool(true) }, span)?,
+        };
+        body = self.ast.add_stmt(Stmt::While { condition, body }, span);
+
+        if let Some(initializer) = initializer {
+            body = self.ast.add_stmt(Stmt::Block { statements: vec![initializer, body] }, span);
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = self.statement()?;
+        // the `else` binds to the nearest `if`
+        let else_branch = if self.match_types(&[TokenType::Else]) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+
+        Ok(self.add_stmt(Stmt::If { condition, then_branch, else_branch }, keyword))
+    }
+
+    fn print_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        let expression = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        let span = keyword.span;
+        Ok(self.add_stmt(Stmt::Print { keyword, expression }, span))
+    }
+
+    fn return_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        let value = if !self.check(TokenType::Semicolon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        let span = keyword.span;
+        Ok(self.add_stmt(Stmt::Return { keyword, value }, span))
+    }
+
+    fn while_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().span;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+        Ok(self.add_stmt(Stmt::While { condition, body }, keyword))
+    }
+
+    /// Parse the statements of a block. The `{` has already been consumed.
+    fn block(&mut self) -> Result<Vec<StmtId>, ParseError> {
+        let mut statements = Vec::new();
+        self.block_depth += 1;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        self.block_depth -= 1;
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> Result<StmtId, ParseError> {
+        let expression = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        let start = self.ast.expr_span(expression);
+        Ok(self.add_stmt(Stmt::Expression { expression }, start))
+    }
+
+    fn expression(&mut self) -> Result<ExprId, ParseError> {
+        self.assignment()
+    }
+
+    /// Parse an assignment.
+    ///
+    /// We don't know we're in an assignment until we reach the `←`,
+    /// so parse the left-hand side as a normal expression first and
+    /// then check that it's something that can be assigned to.
+    ///
+    /// This is also where nested expressions recurse (through
+    /// groupings and right-associative assignments), so it's where
+    /// the nesting is checked.
+    fn assignment(&mut self) -> Result<ExprId, ParseError> {
+        self.nest()?;
+        let result = self.nested_assignment();
+        self.nesting -= 1;
+        result
+    }
+
+    fn nested_assignment(&mut self) -> Result<ExprId, ParseError> {
+        let expr = self.or()?;
+
+        if self.match_types(&[TokenType::Assignment]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?;
+            let span = self.ast.expr_span(expr).to(self.ast.expr_span(value));
+
+            return match self.ast.expr(expr) {
+                Expr::Variable { name } => {
+                    let name = name.clone();
+                    self.add_expr(Expr::Assign { name, value }, span)
+                }
+                Expr::Get { object, name } => {
+                    let (object, name) = (*object, name.clone());
+                    self.add_expr(Expr::Set { object, name, value }, span)
+                }
+                _ => {
+                    // report but keep going -- the parser isn't confused
+                    self.errors.push(ParseError::new(equals, "Invalid assignment target."));
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<ExprId, ParseError> {
+        let mut expr = self.and()?;
+        while self.match_types(&[TokenType::Or]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            let span = self.ast.expr_span(expr).to(self.ast.expr_span(right));
+            expr = self.add_expr(Expr::Logical { left: expr, operator, right }, span)?;
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<ExprId, ParseError> {
+        let mut expr = self.equality()?;
+        while self.match_types(&[TokenType::And]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            let span = self.ast.expr_span(expr).to(self.ast.expr_span(right));
+            expr = self.add_expr(Expr::Logical { left: expr, operator, right }, span)?;
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<ExprId, ParseError> {
+        self.left_associative_binary(
+            &[TokenType::NotEqual, TokenType::EqualityTest],
+            Parser::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> Result<ExprId, ParseError> {
+        self.left_associative_binary(
+            &[TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual],
+            Parser::term,
+        )
+    }
+
+    fn term(&mut self) -> Result<ExprId, ParseError> {
+        self.left_associative_binary(&[TokenType::Minus, TokenType::Plus], Parser::factor)
+    }
+
+    fn factor(&mut self) -> Result<ExprId, ParseError> {
+        self.left_associative_binary(&[TokenType::Slash, TokenType::Star], Parser::unary)
+    }
+
+    /// Parse a run of left-associative binary operators that all
+    /// have the same precedence.
+    ///
+    /// `operand` parses the operands, which is the rule with the
+    /// next higher precedence.
+    fn left_associative_binary(
+        &mut self,
+        operators: &[TokenType],
+        operand: fn(&mut Parser) -> Result<ExprId, ParseError>,
+    ) -> Result<ExprId, ParseError> {
+        let mut expr = operand(self)?;
+        while self.match_types(operators) {
+            let operator = self.previous().clone();
+            let right = operand(self)?;
+            let span = self.ast.expr_span(expr).to(self.ast.expr_span(right));
+            expr = self.add_expr(Expr::Binary { left: expr, operator, right }, span)?;
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<ExprId, ParseError> {
+        if self.match_types(&[TokenType::Not, TokenType::Negative]) {
+            let operator = self.previous().clone();
+            self.nest()?;
+            let right = self.unary();
+            self.nesting -= 1;
+            let right = right?;
+            let span = operator.span.to(self.ast.expr_span(right));
+            return self.add_expr(Expr::Unary { operator, right }, span);
+        }
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<ExprId, ParseError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_types(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_types(&[TokenType::Dot]) {
+                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                let span = self.ast.expr_span(expr).to(name.span);
+                expr = self.add_expr(Expr::Get { object: expr, name }, span)?;
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    /// Parse the arguments of a call. The `(` has already been consumed.
+    fn finish_call(&mut self, callee: ExprId) -> Result<ExprId, ParseError> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    // report but keep going -- the parser isn't confused
+                    self.errors.push(ParseError::new(self.peek().clone(), "Can't have more than 255 arguments."));
+                }
+                arguments.push(self.expression()?);
+                if !self.match_types(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        let span = self.ast.expr_span(callee).to(paren.span);
+        self.add_expr(Expr::Call { callee, paren, arguments }, span)
+    }
+
+    fn primary(&mut self) -> Result<ExprId, ParseError> {
+        let token = self.peek().clone();
+        let span = token.span;
+        let expr = match token.token_type {
+            TokenType::False => Expr::Literal { value: Literal::Bool(false) },
+            TokenType::True => Expr::Literal { value: Literal::Bool(true) },
+            TokenType::Nil => Expr::Literal { value: Literal::Nil },
+            TokenType::Number | TokenType::String => Expr::Literal { value: token.literal },
+            TokenType::This => Expr::This { keyword: token },
+            TokenType::Super => {
+                self.advance();
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+                let span = span.to(method.span);
+                return self.add_expr(Expr::Super { keyword: token, method }, span);
+            }
+            TokenType::Identifier => Expr::Variable { name: token },
+            TokenType::LeftParen => {
+                self.advance();
+                let expression = self.expression()?;
+                let paren = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                return self.add_expr(Expr::Grouping { expression }, span.to(paren.span));
+            }
+            _ => return Err(ParseError::new(token, "Expect expression.")),
+        };
+        self.advance();
+        self.add_expr(expr, span)
+    }
+
+    /// Put an expression in the arena, or fail if that makes the
+    /// tree too deep.
+    ///
+    /// See `MAX_EXPR_DEPTH`.
+    fn add_expr(&mut self, expr: Expr, span: Span) -> Result<ExprId, ParseError> {
+        let depth = |id: &ExprId| self.expr_depths[id.index()];
+        let depth = 1 + match &expr {
+            Expr::Assign { value, .. } => depth(value),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => depth(left).max(depth(right)),
+            Expr::Call { callee, arguments, .. } => arguments.iter().map(depth).fold(depth(callee), usize::max),
+            Expr::Get { object, .. } => depth(object),
+            Expr::Grouping { expression } => depth(expression),
+            Expr::Set { object, value, .. } => depth(object).max(depth(value)),
+            Expr::Unary { right, .. } => depth(right),
+            Expr::Literal { .. } | Expr::Super { .. } | Expr::This { .. } | Expr::Variable { .. } => 0,
+        };
+        if depth > MAX_EXPR_DEPTH {
+            return Err(ParseError::new(self.peek().clone(), "Too much nesting."));
+        }
+        self.expr_depths.push(depth);
+        Ok(self.ast.add_expr(expr, span))
+    }
+
+    /// Add a statement that started at `start` and ends with the
+    /// token that was just consumed.
+    fn add_stmt(&mut self, stmt: Stmt, start: Span) -> StmtId {
+        let span = start.to(self.previous().span);
+        self.ast.add_stmt(stmt, span)
+    }
+
+    /// Go one level deeper, or fail if that's too deep.
+    ///
+    /// Every successful call has to be matched by a
+    /// `self.nesting -= 1` once the nested part is parsed.
+    fn nest(&mut self) -> Result<(), ParseError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(ParseError::new(self.peek().clone(), "Too much nesting."));
+        }
+        self.nesting += 1;
+        Ok(())
+    }
+
+    /// If the next token is any of `token_types`, consume it and return `true`.
+    fn match_types(&mut self, token_types: &[TokenType]) -> bool {
+        if token_types.iter().any(|token_type| self.check(*token_type)) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    /// Consume the next token if it is `token_type`, otherwise
+    /// return an error with `message`.
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ParseError> {
+        if self.check(token_type) {
+            return Ok(self.advance().clone());
+        }
+        Err(ParseError::new(self.peek().clone(), message))
+    }
+
+    /// Is the next token `token_type`? Never consumes anything.
+    fn check(&self, token_type: TokenType) -> bool {
+        !self.is_at_end() && self.peek().token_type == token_type
+    }
+
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::EOF
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scanner::scanner_struct::Scanner;
+
+    fn parse(source: &str) -> Result<Ast, Vec<ParseError>> {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        Parser::new(scanner.tokens).parse()
+    }
+
+    #[test]
+    fn test_factor_binds_tighter_than_term() {
+        let ast = parse("1 + 2 * 3;").unwrap();
+
+        let Stmt::Expression { expression } = ast.stmt(ast.statements[0]) else {
+            panic!("expected an expression statement, got {:?}", ast);
+        };
+        let Expr::Binary { operator, right, .. } = ast.expr(*expression) else {
+            panic!("expected a binary expression, got {:?}", ast);
+        };
+        assert_eq!(operator.token_type, TokenType::Plus);
+        assert!(matches!(ast.expr(*right), Expr::Binary { operator, .. } if operator.token_type == TokenType::Star));
+    }
+
+    #[test]
+    fn test_for_is_desugared_into_while() {
+        let ast = parse("for (var i ← 0; i < 3; i ← i + 1) print i;").unwrap();
+
+        let Stmt::Block { statements } = ast.stmt(ast.statements[0]) else {
+            panic!("expected a block, got {:?}", ast);
+        };
+        assert!(matches!(ast.stmt(statements[0]), Stmt::Var { .. }));
+        assert!(matches!(ast.stmt(statements[1]), Stmt::While { .. }));
+    }
+
+    #[test]
+    fn test_nodes_have_spans() {
+        let source = "print «a» + ⁻1;";
+        let ast = parse(source).unwrap();
+
+        let statement = ast.statements[0];
+        let Stmt::Print { expression, .. } = ast.stmt(statement) else {
+            panic!("expected a print statement, got {:?}", ast);
+        };
+        let span = ast.expr_span(*expression);
+        assert_eq!(&source[span.start..span.end], "«a» + ⁻1");
+        let span = ast.stmt_span(statement);
+        assert_eq!(&source[span.start..span.end], source);
+    }
+
+    #[test]
+    fn test_subclass_with_property_access() {
+        let ast = parse("class B < A { get() { return super.get() + this.x; } } a.b.c ← 1;").unwrap();
+
+        assert_eq!(
+            ast::sexpr_printer::print_program(&ast),
+            "(class B < A (fun get () (return (+ (call (super get)) (. this x)))))\n(; (← (. (. a b) c) 1))",
+        );
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        let errors = parse("1 ← 2;").unwrap_err();
+
+        assert_eq!(errors[0].message, "Invalid assignment target.");
+    }
+
+    #[test]
+    fn test_reports_every_error_without_cascading() {
+        let errors = parse("var ← 1;\nprint 1 +;\n{ var y ← ; }\nprint «fine»;\nfun (a) {}").unwrap_err();
+
+        let messages: Vec<(usize, &str)> = errors.iter().map(|error| (error.token.line, error.message.as_str())).collect();
+        assert_eq!(messages, vec![
+            (1, "Expect variable name."),
+            (2, "Expect expression."),
+            (3, "Expect expression."),
+            (5, "Expect function name."),
+        ]);
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error_not_a_crash() {
+        // test threads get a smaller stack than the main thread,
+        // so give this one the main thread's 8MB
+        let errors = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                let source = format!("print {}1{};", "(".repeat(10_000), ")".repeat(10_000));
+                parse(&source).unwrap_err()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Too much nesting.");
+    }
+
+    #[test]
+    fn test_deeply_nested_functions_and_classes_are_an_error_not_a_crash() {
+        let errors = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                [
+                    format!("{}{}", "fun f() { ".repeat(50_000), "}".repeat(50_000)),
+                    format!("{}{}", "class A { m() { ".repeat(50_000), "} }".repeat(50_000)),
+                ].map(|source| parse(&source).unwrap_err())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        for errors in errors {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Too much nesting.");
+        }
+    }
+
+    #[test]
+    fn test_long_chains_have_their_own_limit() {
+        // the parser builds these in a loop, but the tree they make
+        // is as deep as the chain is long
+        for source in [
+            format!("print 1{};", " + 1".repeat(20_000)),
+            format!("print a{};", ".b".repeat(20_000)),
+            format!("f{};", "()".repeat(20_000)),
+            format!("print true{};", " or true".repeat(20_000)),
+        ] {
+            let errors = parse(&source).unwrap_err();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].message, "Too much nesting.");
+        }
+
+        // but a flat one much longer than MAX_NESTING is fine
+        assert!(parse(&format!("print 1{};", " + 1".repeat(999))).is_ok());
+    }
+}
@@ -0,0 +1,13 @@
+//! Contains the resolver, a pass between the parser and the
+//! interpreter that works out which declaration each variable
+//! refers to.
+//!
+//! - `resolver_struct` has the `Resolver` pass and `resolve`.
+//! - `program` has the `Program` struct, a tree plus what the
+//!   resolver found out about it.
+//! - `resolve_error` has the `ResolveError` struct.
+
+
+pub mod resolver_struct;
+pub mod program;
+pub mod resolve_error;
@@ -0,0 +1,16 @@
+//! Contains the `Program` struct.
+
+use ast::arena::{Ast, ExprId, SideTable};
+
+
+/// A syntax tree that has been through the resolver.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub ast: Ast,
+
+    /// For each variable and assignment expression that refers to
+    /// a local, how many scopes out from the one it's in the
+    /// declaration is (0 is the same scope).  Expressions that
+    /// aren't in here refer to globals.
+    pub depths: SideTable<ExprId, usize>,
+}
@@ -0,0 +1,36 @@
+//! Contains the `ResolveError` struct.
+
+use diagnostic::diagnostic::Diagnostic;
+use token::token::Token;
+
+
+/// A mistake the resolver found, like returning from top-level code.
+///
+/// These are found before anything runs, so they're reported
+/// the same way as syntax errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl ResolveError {
+    pub fn new(token: &Token, message: &str) -> Self {
+        ResolveError {
+            token: token.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Turn the error into a `Diagnostic` pointing at the token.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let message = format!("Error at '{}': {}", self.token.lexeme, self.message);
+        Diagnostic::new(self.token.line, self.token.span, message)
+    }
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_diagnostic())
+    }
+}
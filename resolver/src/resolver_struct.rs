@@ -0,0 +1,298 @@
+//! Contains the `Resolver` struct and the `resolve` function.
+
+use std::collections::HashMap;
+
+use ast::arena::{Ast, ExprId, SideTable, StmtId};
+use ast::expr::Expr;
+use ast::stmt::{ClassDecl, FunctionDecl};
+use ast::visitor::{walk_block, walk_program, Visitor};
+use token::token::Token;
+
+use crate::program::Program;
+use crate::resolve_error::ResolveError;
+
+
+/// Work out where each variable in `ast` is declared.
+///
+/// Gives back the tree along with the depth of every local
+/// variable reference, or every mistake found if there were any.
+pub fn resolve(ast: Ast) -> Result<Program, Vec<ResolveError>> {
+    let mut resolver = Resolver::default();
+    walk_program(&mut resolver, &ast);
+
+    if resolver.errors.is_empty() {
+        Ok(Program { ast, depths: resolver.depths })
+    } else {
+        Err(resolver.errors)
+    }
+}
+
+
+/// What kind of function body the resolver is inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum FunctionType {
+    #[default]
+    None,
+    Function,
+    Method,
+    /// An `init` method, which can't return a value.
+    Initializer,
+}
+
+
+/// What kind of class body the resolver is inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ClassType {
+    #[default]
+    None,
+    Class,
+    /// A class with a superclass, where `super` can be used.
+    Subclass,
+}
+
+
+/// The resolver struct.
+///
+/// This walks the tree once before it runs, keeping a stack of
+/// the local scopes it's inside.  Each time a variable is used
+/// or assigned, the resolver counts how many scopes out the
+/// declaration is and records it, so the interpreter can go
+/// straight to the right environment instead of searching.
+/// That way a closure keeps seeing the variable it captured
+/// even if a later declaration shadows the name.
+///
+/// Globals aren't tracked: a name that isn't found in any local
+/// scope is assumed to be global.
+#[derive(Debug, Default)]
+pub struct Resolver {
+    /// One map per local scope, innermost last.  A name maps to
+    /// `false` while its initializer is being resolved and `true`
+    /// once it's ready to use.
+    scopes: Vec<HashMap<String, bool>>,
+
+    /// The function we're in, if any, to catch a stray `return`.
+    function: FunctionType,
+
+    /// The class we're in, if any, to catch a stray `this`.
+    class: ClassType,
+
+    pub depths: SideTable<ExprId, usize>,
+    pub errors: Vec<ResolveError>,
+}
+
+impl Resolver {
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Add `name` to the innermost scope, but not ready to use yet.
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.lexeme) {
+            self.errors.push(ResolveError::new(name, "Already a variable with this name in this scope."));
+        }
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    /// Mark `name` in the innermost scope as ready to use.
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    /// Record how far out `name` is declared, if it's a local.
+    fn resolve_local(&mut self, expr: ExprId, name: &Token) {
+        let found = self.scopes.iter().rev().position(|scope| scope.contains_key(&name.lexeme));
+        if let Some(depth) = found {
+            self.depths.insert(expr, depth);
+        }
+    }
+
+    fn resolve_function(&mut self, ast: &Ast, declaration: &FunctionDecl, function: FunctionType) {
+        let enclosing = std::mem::replace(&mut self.function, function);
+
+        // the parameters and the body share one scope, the same
+        // way they share one environment when the function runs
+        self.begin_scope();
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param);
+        }
+        walk_block(self, ast, &declaration.body);
+        self.end_scope();
+
+        self.function = enclosing;
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_block(&mut self, ast: &Ast, _stmt: StmtId, statements: &[StmtId]) {
+        self.begin_scope();
+        walk_block(self, ast, statements);
+        self.end_scope();
+    }
+
+    fn visit_class(&mut self, ast: &Ast, _stmt: StmtId, declaration: &ClassDecl) {
+        let enclosing = std::mem::replace(&mut self.class, ClassType::Class);
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+
+        // the superclass's methods are reached through a scope of
+        // their own holding `super`, just outside the one for `this`
+        if let Some(superclass) = declaration.superclass {
+            if let Expr::Variable { name } = ast.expr(superclass) {
+                if name.lexeme == declaration.name.lexeme {
+                    self.errors.push(ResolveError::new(name, "A class can't inherit from itself."));
+                }
+            }
+            self.class = ClassType::Subclass;
+            self.visit_expr(ast, superclass);
+            self.begin_scope();
+            self.scopes.last_mut().unwrap().insert(String::from("super"), true);
+        }
+
+        // methods close over a scope that holds just `this`, which
+        // is what binding a method to an instance makes at runtime
+        self.begin_scope();
+        self.scopes.last_mut().unwrap().insert(String::from("this"), true);
+        for method in &declaration.methods {
+            let function = match method.name.lexeme.as_str() {
+                "init" => FunctionType::Initializer,
+                _ => FunctionType::Method,
+            };
+            self.resolve_function(ast, method, function);
+        }
+        self.end_scope();
+        if declaration.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.class = enclosing;
+    }
+
+    fn visit_function(&mut self, ast: &Ast, _stmt: StmtId, declaration: &FunctionDecl) {
+        // defined straight away, so the function can call itself
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+        self.resolve_function(ast, declaration, FunctionType::Function);
+    }
+
+    fn visit_return(&mut self, ast: &Ast, _stmt: StmtId, keyword: &Token, value: Option<ExprId>) {
+        if self.function == FunctionType::None {
+            self.errors.push(ResolveError::new(keyword, "Can't return from top-level code."));
+        }
+        if let Some(value) = value {
+            if self.function == FunctionType::Initializer {
+                self.errors.push(ResolveError::new(keyword, "Can't return a value from an initializer."));
+            }
+            self.visit_expr(ast, value);
+        }
+    }
+
+    fn visit_var(&mut self, ast: &Ast, _stmt: StmtId, name: &Token, initializer: Option<ExprId>) {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.visit_expr(ast, initializer);
+        }
+        self.define(name);
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, expr: ExprId, name: &Token, value: ExprId) {
+        self.visit_expr(ast, value);
+        self.resolve_local(expr, name);
+    }
+
+    fn visit_super(&mut self, _ast: &Ast, expr: ExprId, keyword: &Token, _method: &Token) {
+        match self.class {
+            ClassType::None => {
+                self.errors.push(ResolveError::new(keyword, "Can't use 'super' outside of a class."));
+            }
+            ClassType::Class => {
+                self.errors.push(ResolveError::new(keyword, "Can't use 'super' in a class with no superclass."));
+            }
+            ClassType::Subclass => self.resolve_local(expr, keyword),
+        }
+    }
+
+    fn visit_this(&mut self, _ast: &Ast, expr: ExprId, keyword: &Token) {
+        if self.class == ClassType::None {
+            self.errors.push(ResolveError::new(keyword, "Can't use 'this' outside of a class."));
+            return;
+        }
+        self.resolve_local(expr, keyword);
+    }
+
+    fn visit_variable(&mut self, _ast: &Ast, expr: ExprId, name: &Token) {
+        let in_own_initializer = self
+            .scopes
+            .last()
+            .is_some_and(|scope| scope.get(&name.lexeme) == Some(&false));
+        if in_own_initializer {
+            self.errors.push(ResolveError::new(name, "Can't read local variable in its own initializer."));
+        }
+        self.resolve_local(expr, name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parser_struct::Parser;
+    use scanner::scanner_struct::Scanner;
+
+    fn resolve_source(source: &str) -> Result<Program, Vec<ResolveError>> {
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        resolve(Parser::new(scanner.tokens).parse().unwrap())
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        resolve_source(source).unwrap_err().into_iter().map(|error| error.message).collect()
+    }
+
+    /// The depth of every variable use, in the order they're walked.
+    fn variable_depths(program: &Program) -> Vec<Option<usize>> {
+        struct Uses<'a>(&'a Program, Vec<Option<usize>>);
+
+        impl Visitor for Uses<'_> {
+            fn visit_variable(&mut self, _ast: &Ast, expr: ExprId, _name: &Token) {
+                self.1.push(self.0.depths.get(expr).copied());
+            }
+        }
+
+        let mut uses = Uses(program, Vec::new());
+        walk_program(&mut uses, &program.ast);
+        uses.1
+    }
+
+    #[test]
+    fn test_depths_count_scopes_outwards() {
+        let program = resolve_source("var g; { var a; { var b; a; b; g; } } fun f(x) { { x; } }").unwrap();
+
+        assert_eq!(variable_depths(&program), vec![Some(1), Some(0), None, Some(1)]);
+    }
+
+    #[test]
+    fn test_reports_scope_mistakes() {
+        assert_eq!(messages("{ var a ← a; }"), vec!["Can't read local variable in its own initializer."]);
+        assert_eq!(messages("return 1;"), vec!["Can't return from top-level code."]);
+        assert_eq!(messages("fun f() { var a; var a; }"), vec!["Already a variable with this name in this scope."]);
+        assert_eq!(messages("print this;"), vec!["Can't use 'this' outside of a class."]);
+        assert_eq!(messages("class A { init() { return 1; } }"), vec!["Can't return a value from an initializer."]);
+        assert_eq!(messages("class A < A {}"), vec!["A class can't inherit from itself."]);
+        assert_eq!(messages("class A { f() { super.f(); } }"), vec!["Can't use 'super' in a class with no superclass."]);
+        assert_eq!(messages("super.f();"), vec!["Can't use 'super' outside of a class."]);
+    }
+
+    #[test]
+    fn test_globals_can_be_redeclared() {
+        assert!(resolve_source("var a ← 1; var a ← a;").is_ok());
+    }
+}
@@ -0,0 +1,26 @@
+//! Contains the `check` function
+//! 
+//! This is what runs for `rustlox check <file>`.
+
+
+/// Check a file for syntax errors (and mistakes the resolver
+/// catches, like `return` outside a function) without running it.
+/// 
+/// Every error in the file is reported (not just the first),
+/// each with the line it's on and a caret under the offending
+/// part.  Exits with code 65 if there were any errors, so this
+/// can be used in scripts.
+pub fn check(path_string: &str) {
+    let code = crate::read_source_file::read_source_file(path_string);
+
+    match lox::frontend::resolve_source(&code) {
+        Ok(_) => println!("No errors found in {}.", path_string),
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}\n", diagnostic.render(&code));
+            }
+            eprintln!("Found {} error(s) in {}.", diagnostics.len(), path_string);
+            std::process::exit(65);
+        }
+    }
+}
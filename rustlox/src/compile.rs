@@ -0,0 +1,56 @@
+//! Contains the `compile` function
+//!
+//! This is what runs for `rustlox compile <file> [-O0|-O1] [-o <out>]`.
+
+use std::path::{Path, PathBuf};
+
+use lox::OptLevel;
+
+
+/// How `rustlox compile` is meant to be used.
+pub const USAGE: &str = "rustlox compile <file> [-O0|-O1] [-o <out>]";
+
+
+/// Compile a file to bytecode and save it as a `.loxc` file, which
+/// `rustlox run` runs on the VM without compiling it again.
+///
+/// The file goes next to the source with its extension changed
+/// to `.loxc`, unless `-o` says where.  `-O1` optimizes the code
+/// first (`-O0`, the default, doesn't).
+///
+/// Exits with code 65 if the file doesn't compile, and 73 if the
+/// `.loxc` file can't be written.
+pub fn compile(path_string: &str, options: &[String]) {
+    let mut out = Path::new(path_string).with_extension("loxc");
+    let mut opt_level = OptLevel::default();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (option.as_str(), OptLevel::from_flag(option)) {
+            (_, Some(level)) => opt_level = level,
+            ("-o", _) => match options.next() {
+                Some(path) => out = PathBuf::from(path),
+                None => crate::usage(USAGE),
+            },
+            _ => crate::usage(USAGE),
+        }
+    }
+    let code = crate::read_source_file::read_source_file(path_string);
+
+    let function = match lox::frontend::compile_source_at(&code, opt_level) {
+        Ok(function) => function,
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}\n", diagnostic.render(&code));
+            }
+            std::process::exit(65);
+        }
+    };
+    if let Err(e) = std::fs::write(&out, bytecode::loxc::save(&function)) {
+        utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
+            String::from("There was an error writing the file:"),
+            out.display().to_string(),
+            format!("{:?}", e),
+        ]);
+        std::process::exit(73);
+    }
+}
@@ -0,0 +1,35 @@
+//! Contains the `disasm` function
+//! 
+//! This is what runs for `rustlox disasm <file> [-O0|-O1]`.
+
+use lox::OptLevel;
+
+
+/// How `rustlox disasm` is meant to be used.
+pub const USAGE: &str = "rustlox disasm <file> [-O0|-O1]";
+
+
+/// Compile a file to bytecode and print the disassembly of it
+/// and of every function in it, without running anything.
+/// 
+/// This is for seeing what the compiler emits, and with `-O1`
+/// what the optimizer makes of it.  Exits with code 65 if the file
+/// doesn't compile.
+pub fn disasm(path_string: &str, options: &[String]) {
+    let opt_level = match options {
+        [] => OptLevel::default(),
+        [flag] => OptLevel::from_flag(flag).unwrap_or_else(|| crate::usage(USAGE)),
+        _ => crate::usage(USAGE),
+    };
+    let code = crate::read_source_file::read_source_file(path_string);
+
+    match lox::frontend::compile_source_at(&code, opt_level) {
+        Ok(function) => print!("{}", bytecode::disassembler::disassemble_function(&function)),
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}\n", diagnostic.render(&code));
+            }
+            std::process::exit(65);
+        }
+    }
+}
@@ -1,26 +1,81 @@
 //! There is a main function that is the starting point.
 //! From the main function, it can either do `run_file` or
 //! `run_prompt` -- both of which are simply entry points into
-//! the `run` function.
+//! the `run` function -- or one of the subcommands like
+//! `check`.
+//!
+//! All the actual work is done by the `lox` library; this is
+//! just the command line around it.
 
 
 mod run_prompt;
 mod run_file;
 mod run;
+mod run_with_engine;
+mod check;
+mod print_ast;
+mod disasm;
+mod compile;
+mod read_source_file;
 
 use std::env::args;
 use std::cmp::Ordering;
 
+
+/// How big a stack the command line runs on.
+///
+/// The tree-walking interpreter recurses on the Rust stack for
+/// every Lox call, so recursion only gets as deep as on the VM
+/// with a lot more stack than the 8MB a main thread gets.  Only
+/// the pages that are used take up memory.
+pub const STACK_SIZE: usize = 256 << 20;
+
+
+/// Run the command line on a thread with a `STACK_SIZE` stack.
+fn main() {
+    let command_line = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(command_line)
+        .expect("there should be room for the command line's thread");
+    if let Err(panic) = command_line.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
 /// Get the command line args.
+/// - If the first one is a subcommand, then do that. They are
+///   - `check <file>`
+///   - `ast <file> [--format sexpr|rpn|source]`
+///   - `disasm <file> [-O0|-O1]`
+///   - `compile <file> [-O0|-O1] [-o <out>]`
+///   - `run <file> [--engine tree|vm] [-O0|-O1] [--incremental-gc] [--stress-gc] [--log-gc]`,
+///     where the file can be a `.loxc` file from `compile`
 /// - If there is more than one (in addition to
 ///   the first arg, which is the file path), then we
 ///   want to break and tell the user we only want one arg.
 /// - if there is only one, then find that file and run the
 ///   code inside it.
 /// - if there are no arguments, then run rustlox as an interpreter
-fn main() {
+fn command_line() {
     let args: Vec<String> = args().collect();
 
+    if let Some(subcommand) = args.get(1) {
+        match (subcommand.as_str(), &args[2..]) {
+            ("check", [path]) => return check::check(path),
+            ("check", _) => usage("rustlox check <file>"),
+            ("ast", [path]) => return print_ast::print_ast(path, "sexpr"),
+            ("ast", [path, flag, format]) if flag == "--format" => return print_ast::print_ast(path, format),
+            ("ast", _) => usage("rustlox ast <file> [--format sexpr|rpn|source]"),
+            ("disasm", [path, options @ ..]) => return disasm::disasm(path, options),
+            ("disasm", _) => usage(disasm::USAGE),
+            ("compile", [path, options @ ..]) => return compile::compile(path, options),
+            ("compile", _) => usage(compile::USAGE),
+            ("run", [path, options @ ..]) => return run_with_engine::run_with_engine(path, options),
+            ("run", _) => usage(run_with_engine::USAGE),
+            _ => {},
+        }
+    }
+
     match args.len().cmp(&2) {
         Ordering::Greater => {
             utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
@@ -41,3 +96,12 @@ fn main() {
 
 }
 
+
+/// Tell the user how a subcommand is meant to be used and exit with 64.
+fn usage(usage: &str) -> ! {
+    utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
+        String::from("Usage:"),
+        usage.to_string(),
+    ]);
+    std::process::exit(64);
+}
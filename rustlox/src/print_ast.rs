@@ -0,0 +1,40 @@
+//! Contains the `print_ast` function
+//! 
+//! This is what runs for `rustlox ast <file> --format <format>`.
+
+
+/// Parse a file and print its syntax tree in `format`, which
+/// is one of
+/// 
+/// - `sexpr` -- fully parenthesized, like `(* (⁻ 123) (group 45.67))`
+/// - `rpn` -- reverse Polish notation, like `1 2 + 4 3 - *`
+/// - `source` -- the canonical Lox source for the tree
+/// 
+/// This is for debugging precedence issues in the parser, and
+/// for golden tests of it.
+pub fn print_ast(path_string: &str, format: &str) {
+    let print_program = match format {
+        "sexpr" => ast::sexpr_printer::print_program,
+        "rpn" => ast::rpn_printer::print_program,
+        "source" => ast::source_printer::print_program,
+        _ => {
+            utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
+                format!("Unknown format '{}'.", format),
+                String::from("It should be one of: sexpr, rpn, source"),
+            ]);
+            std::process::exit(64);
+        }
+    };
+
+    let code = crate::read_source_file::read_source_file(path_string);
+
+    match lox::frontend::parse_source(&code) {
+        Ok(ast) => println!("{}", print_program(&ast)),
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}\n", diagnostic.render(&code));
+            }
+            std::process::exit(65);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Contains the `read_source_file` and `read_compiled_file`
+//! functions
+//! 
+//! Used by the subcommands that take a file.
+
+
+/// Read the file at `path_string`.
+/// 
+/// Unlike `run_file`, the subcommands don't ask again when the
+/// file can't be read -- they tell the user and exit with 66.
+pub fn read_source_file(path_string: &str) -> String {
+    match std::fs::read_to_string(path_string) {
+        Ok(code) => code,
+        Err(e) => cannot_read(path_string, e),
+    }
+}
+
+
+/// Read the compiled `.loxc` file at `path_string`, which isn't
+/// text.  Exits with 66 if it can't be read, like
+/// `read_source_file`.
+pub fn read_compiled_file(path_string: &str) -> Vec<u8> {
+    match std::fs::read(path_string) {
+        Ok(bytes) => bytes,
+        Err(e) => cannot_read(path_string, e),
+    }
+}
+
+
+fn cannot_read(path_string: &str, e: std::io::Error) -> ! {
+    utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
+        String::from("There was an error reading the file:"),
+        path_string.to_string(),
+        format!("{:?}", e),
+    ]);
+    std::process::exit(66);
+}
@@ -1,16 +1,130 @@
 //! Contains the run function
 
-use scanner::scanner_struct::Scanner;
+use lox::{Error, ErrorKind, Interpreter, Limits, RuntimeError, Vm, VmError};
+
+
+/// Why running some code failed (or stopped early).
+///
+/// The errors themselves have already been reported by the time
+/// `run` gives one of these back; it's only here so the caller
+/// knows how to exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunError {
+    /// The code had syntax errors, so none of it ran.
+    Compile,
+    /// The code started running but hit a runtime error.
+    Runtime,
+    /// The code called `exit(code)`.
+    Exit(i32),
+}
+
+impl RunError {
+    /// The exit code for this kind of failure (the same ones
+    /// `sysexits.h` uses: 65 for bad input data, 70 for an
+    /// internal software error), or the code passed to `exit`.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            RunError::Compile => 65,
+            RunError::Runtime => 70,
+            RunError::Exit(code) => code,
+        }
+    }
+}
+
+
+/// A new interpreter for the command line.
+///
+/// Its stack limit is half of the stack the command line runs on
+/// (see `STACK_SIZE`), which is plenty for the VM's call depth
+/// even in a debug build and still leaves room to spare.
+pub fn new_interpreter() -> Interpreter {
+    Interpreter::with_limits(Limits { max_stack_bytes: Some(crate::STACK_SIZE / 2), ..Limits::default() })
+}
+
 
 /// Run the code inside the interpreter.
 /// 
-/// for now, just print the code.
+/// Scan, parse and resolve the code, then execute it. If there
+/// are syntax errors, they are all reported and nothing runs.
+/// A runtime error is reported with the line it happened on
+/// and a caret under the offending part.
+/// 
+/// The code runs in `interpreter`, so anything it declares is
+/// still there for the next code run in the same one.
 /// 
 /// This will be used by `run_file` and `run_prompt`.
-pub fn run(code: &str) {
-    let mut myscanner = Scanner::new(code);
-    myscanner.scan_tokens();
-    println!("Running code:\n{}", code);
-    println!("Tokens: {:?}", myscanner.tokens);
+pub fn run(code: &str, interpreter: &mut Interpreter) -> Result<(), RunError> {
+    report(code, interpreter.eval(code).map(|_| ()))
 }
 
+
+/// Run the code on the bytecode VM instead.
+///
+/// Errors are reported the same way as by `run`, with the Lox
+/// stack trace under a runtime error.
+pub fn run_vm(code: &str, vm: &mut Vm) -> Result<(), RunError> {
+    report(code, vm.eval(code))
+}
+
+
+/// Run a script compiled to a `.loxc` file on the VM.
+///
+/// A file that can't be loaded is reported in a box, like a file
+/// that can't be read, and counts as a compile error: none of it
+/// runs.  A runtime error is reported the same way as by `run_vm`.
+pub fn run_compiled(bytes: &[u8], vm: &mut Vm) -> Result<(), RunError> {
+    match vm.eval_compiled(bytes) {
+        Err(Error::Load(error)) => {
+            utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
+                String::from("There was an error loading the compiled file:"),
+                error.message,
+            ]);
+            Err(RunError::Compile)
+        }
+        result => report("", result),
+    }
+}
+
+
+/// Report what went wrong, if anything, and say how to exit.
+fn report(code: &str, result: Result<(), Error>) -> Result<(), RunError> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(Error::Runtime(RuntimeError { kind: ErrorKind::Exit(exit_code), .. })) => Err(RunError::Exit(exit_code)),
+        Err(Error::Vm(VmError { kind: lox::VmErrorKind::Exit(exit_code), .. })) => Err(RunError::Exit(exit_code)),
+        Err(error) => {
+            eprintln!("{}", error.render(code));
+            match error {
+                Error::Compile(_) | Error::Load(_) => Err(RunError::Compile),
+                Error::Runtime(_) | Error::Vm(_) => Err(RunError::Runtime),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_way_of_failing_has_its_exit_code() {
+        let programs = [
+            ("print 1;", Ok(())),
+            ("print 1 +;", Err(RunError::Compile)),
+            ("print «a» - 1;", Err(RunError::Runtime)),
+            ("exit(3);", Err(RunError::Exit(3))),
+        ];
+        for (code, result) in programs {
+            assert_eq!(run(code, &mut new_interpreter()), result, "on the tree-walker: {}", code);
+            assert_eq!(run_vm(code, &mut Vm::new()), result, "on the VM: {}", code);
+        }
+
+        let compiled = bytecode::loxc::save(&lox::frontend::compile_source("print «a» - 1;").unwrap());
+        assert_eq!(run_compiled(&compiled, &mut Vm::new()), Err(RunError::Runtime));
+        assert_eq!(run_compiled(b"not bytecode", &mut Vm::new()), Err(RunError::Compile));
+
+        assert_eq!(RunError::Compile.exit_code(), 65);
+        assert_eq!(RunError::Runtime.exit_code(), 70);
+        assert_eq!(RunError::Exit(3).exit_code(), 3);
+    }
+}
@@ -3,7 +3,7 @@
 //! Requires on the `run` function.
 
 use std::path::PathBuf;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 
 
 /// Run the code from a file.
@@ -18,8 +18,13 @@ use std::io::Write;
 /// 
 /// If the user's argument does not give a valid string from
 /// the file, we'll go into a loop asking the user to try
-/// again.  If they type in `exit` or `quit`, then we'll
-/// break the loop.
+/// again.  If they type in `exit` or `quit` (or stdin runs
+/// out), then we'll break the loop.
+/// 
+/// If the code fails, we exit straight away with the code
+/// from `RunError::exit_code` so scripts can tell.  When stdin
+/// isn't a terminal, nobody is there to pass another path, so
+/// we stop after the first file that runs.
 pub fn run_file(first_path_string: &str) {
 
     let mut is_first_time = true;
@@ -43,9 +48,9 @@ pub fn run_file(first_path_string: &str) {
 
             path_string = String::new();
             
-            std::io::stdin().read_line(&mut path_string).unwrap();
+            let bytes_read = std::io::stdin().read_line(&mut path_string).unwrap();
             
-            if path_string.trim() == "exit" || path_string.trim() == "quit" {
+            if bytes_read == 0 || path_string.trim() == "exit" || path_string.trim() == "quit" {
                 break;
             }
             string_from_file = std::fs::read_to_string(path_string.trim());
@@ -54,7 +59,12 @@ pub fn run_file(first_path_string: &str) {
         
         match string_from_file {
             Ok(string_from_file) => {
-                crate::run::run(&string_from_file);
+                if let Err(error) = crate::run::run(&string_from_file, &mut crate::run::new_interpreter()) {
+                    std::process::exit(error.exit_code());
+                }
+                if !std::io::stdin().is_terminal() {
+                    break;
+                }
                 utilities::print_with_surrounding_box::print_with_surrounding_box(vec![
                     String::from("That seemed to work!"),
                     String::from("If you want to run more, pass another file path."),
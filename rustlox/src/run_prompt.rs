@@ -18,6 +18,9 @@ use std::io::Write;
 /// Break the loop if the line is empty or if the user
 /// types `exit` or `quit`.
 /// 
+/// Every line runs in the same `Interpreter`, so variables
+/// declared on one line can be used on the next.
+/// 
 /// At the very beginning, tell the user that they can exit by
 /// typing `exit` or `quit`.
 pub fn run_prompt() {
@@ -25,18 +28,24 @@ pub fn run_prompt() {
         String::from("Running prompt. Type 'exit' or 'quit' to exit"),
     ]);
 
+    let mut interpreter = crate::run::new_interpreter();
+
     loop {
 
         print!("> ");
         std::io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
+        let bytes_read = std::io::stdin().read_line(&mut input).unwrap();
 
-        if input.trim() == "exit" || input.trim() == "quit" {
+        if bytes_read == 0 || input.trim() == "exit" || input.trim() == "quit" {
             break;
         }
 
-        crate::run::run(&(input.trim()));
+        // the error has already been reported, and whatever the
+        // line did before it failed is kept
+        if let Err(crate::run::RunError::Exit(code)) = crate::run::run(input.trim(), &mut interpreter) {
+            std::process::exit(code);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+//! Contains the `run_with_engine` function
+//! 
+//! This is what runs for
+//! `rustlox run <file> [--engine tree|vm] [-O0|-O1] [--incremental-gc] [--stress-gc] [--log-gc]`.
+
+use lox::{GcMode, GcOptions, OptLevel};
+
+
+/// How `rustlox run` is meant to be used.
+pub const USAGE: &str = "rustlox run <file> [--engine tree|vm] [-O0|-O1] [--incremental-gc] [--stress-gc] [--log-gc]";
+
+
+/// Run a file once on the engine the user picked: `tree` for the
+/// tree-walking interpreter (what plain `rustlox <file>` uses) or
+/// `vm` for the bytecode VM.
+/// 
+/// `-O1` optimizes the bytecode before it runs (`-O0`, the default,
+/// doesn't).  `--incremental-gc` makes the VM collect garbage a bit
+/// at a time, `--stress-gc` makes it collect after every allocation
+/// and `--log-gc` reports each collection on stderr, and how long
+/// the program was paused for them all at the end.  They're all
+/// about the VM, so they need `--engine vm`.
+/// 
+/// A `.loxc` file (made by `rustlox compile`) is already bytecode,
+/// so it runs on the VM without being scanned or compiled again,
+/// as optimized as it was compiled: `--engine tree`, `-O0` and
+/// `-O1` are usage errors for one.
+/// 
+/// Unlike `run_file`, this never asks for another file, so it's
+/// the one to use from scripts and benchmarks.  It exits with the
+/// same codes.
+pub fn run_with_engine(path_string: &str, options: &[String]) {
+    let compiled = path_string.ends_with(".loxc");
+    let mut engine = if compiled { "vm" } else { "tree" };
+    let mut gc_options = GcOptions::default();
+    let mut opt_level = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--engine" => match options.next() {
+                Some(name) => engine = name,
+                None => crate::usage(USAGE),
+            },
+            "-O0" | "-O1" => opt_level = OptLevel::from_flag(option),
+            "--incremental-gc" => gc_options.mode = GcMode::Incremental,
+            "--stress-gc" => gc_options.stress = true,
+            "--log-gc" => gc_options.log = true,
+            _ => crate::usage(USAGE),
+        }
+    }
+
+    let result = match engine {
+        "tree" if gc_options == GcOptions::default() && opt_level.is_none() && !compiled => {
+            let code = crate::read_source_file::read_source_file(path_string);
+            crate::run::run(&code, &mut crate::run::new_interpreter())
+        }
+        "vm" if !(compiled && opt_level.is_some()) => {
+            let mut vm = lox::Vm::with_gc_options(gc_options);
+            vm.set_opt_level(opt_level.unwrap_or_default());
+            let result = if compiled {
+                let bytes = crate::read_source_file::read_compiled_file(path_string);
+                crate::run::run_compiled(&bytes, &mut vm)
+            } else {
+                let code = crate::read_source_file::read_source_file(path_string);
+                crate::run::run_vm(&code, &mut vm)
+            };
+            if gc_options.log {
+                let stats = vm.gc_stats();
+                eprintln!(
+                    "-- gc: {} collections in {} pauses, {:?} in all, the longest {:?}",
+                    stats.collections, stats.pauses, stats.total_pause, stats.longest_pause,
+                );
+            }
+            result
+        }
+        _ => crate::usage(USAGE),
+    };
+    if let Err(error) = result {
+        std::process::exit(error.exit_code());
+    }
+}
@@ -1,5 +1,7 @@
 //! Contains the `Scanner` struct.
 
+use diagnostic::diagnostic::Diagnostic;
+use token::span::Span;
 use token::token::{Token, Literal};
 use token::token_type::TokenType;
 
@@ -43,6 +45,8 @@ pub struct Scanner<'a> {
     /// Works with `start` as offsets that index into
     /// the `source` string.  This field (`current`) points
     /// at the character currently being considered.
+    /// Both are byte offsets, so they can be used to
+    /// slice `source` even when it holds glyphs like `←`.
     /// The character at `current` is in
     /// current_char.
     pub current: usize,
@@ -54,19 +58,25 @@ pub struct Scanner<'a> {
 
     /// Tracks what source line `current` is on.
     pub line: usize,
+
+    /// Problems found while scanning, like characters that
+    /// aren't part of the language.  Scanning carries on past
+    /// them so that one run can report all of them.
+    pub errors: Vec<Diagnostic>,
 }
 
 
 impl Scanner<'_> {
-    pub fn new(source: &str) -> Scanner {
+    pub fn new(source: &str) -> Scanner<'_> {
         //! Create a new `Scanner`.
         Scanner {
             source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
-            current_char: source.chars().nth(0),
-            line: 0,
+            current_char: source.chars().next(),
+            line: 1,
+            errors: Vec::new(),
         }
     }
 
@@ -75,34 +85,50 @@ impl Scanner<'_> {
         //!
         //! This is the main method and purpose of the scanner.
 
+        loop {
+            let token = self.next_token();
+            let at_end = token.token_type == TokenType::EOF;
+            self.tokens.push(token);
+            if at_end {
+                break;
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        //! Scan just the next token and give it back, rather than
+        //! adding it to `tokens`.
+        //!
+        //! This is for a compiler that pulls tokens one at a time.
+        //! Problems are still added to `errors`, and once the end
+        //! of the file is reached every call gives back `EOF`.
+
         loop {
             // We are at the beginning of the next lexeme.
             self.start = self.current;
             match self.scan_token() {
                 ScanTokenResult::TokenFound(token_type) => {
-
                     let lexeme = self.source[self.start..self.current].to_string();
-                    self.tokens.push(Token::new(token_type, lexeme, Literal::NoLiteral, self.line));
+                    return Token::new(token_type, lexeme, Literal::NoLiteral, self.line, self.span());
                 }
-                ScanTokenResult::EndOfFile => {
-                    break;
+                ScanTokenResult::TokenFoundAndAdded => {
+                    return self.tokens.pop().expect("the token was just added");
                 }
                 ScanTokenResult::NoTokenFromScanPass => {
                     // do nothing
                 }
-                ScanTokenResult::TokenFoundAndAdded => {
-                    // do nothing
+                ScanTokenResult::EndOfFile => {
+                    // We are at the end of the file.
+                    return Token::new(
+                        TokenType::EOF,
+                        "".to_string(),
+                        Literal::NoLiteral,
+                        self.line,
+                        Span::new(self.current, self.current),
+                    );
                 }
             }
         }
-
-        // We are at the end of the file.
-        self.tokens.push(Token::new(
-            TokenType::EOF,
-            "".to_string(),
-            token::token::Literal::NoLiteral,
-            self.line,
-        ));
     }
 
     fn scan_token(&mut self) -> ScanTokenResult {
@@ -152,7 +178,7 @@ impl Scanner<'_> {
                                     self.advance(1);
                                     match self.current_char {
                                         None => return ScanTokenResult::EndOfFile,
-                                        Some(current_char) if current_char == '\n' => {
+                                        Some('\n') => {
                                             return ScanTokenResult::NoTokenFromScanPass;
                                         }
                                         _ => {} // do nothing
@@ -169,31 +195,58 @@ impl Scanner<'_> {
                         loop {
                             match self.current_char {
                                 None => {
-                                    // crate::run_time_error::run_time_error(self.line, "Unterminated string.".to_string());
+                                    self.error("Unterminated string.");
                                     return ScanTokenResult::EndOfFile;
                                 }
-                                Some(current_char) if current_char == '»' => {
+                                Some('»') => {
                                     self.advance(1);
                                     break;
                                 }
+                                Some('\n') => {
+                                    // strings can span lines, so keep the line count honest
+                                    self.line += 1;
+                                    self.advance(1);
+                                }
                                 Some(_) => {
                                     self.advance(1);
                                 }
                             }
                         }
-                        let literal = self.source[self.start..self.current].to_string();
+                        // the literal is everything between the quotes, and the
+                        // lexeme is the whole thing, quotes included.
+                        let literal = self.source[self.start + '«'.len_utf8()..self.current - '»'.len_utf8()].to_string();
                         self.tokens.push(Token::new(
                             TokenType::String,
-                            format!("«{}»", literal),
+                            self.source[self.start..self.current].to_string(),
                             Literal::String(literal),
                             self.line,
+                            self.span(),
                         ));
                         ScanTokenResult::TokenFoundAndAdded
                     }
 
                     // if c is a digit then we have a number
+                    c if c.is_ascii_digit() => {
+                        self.number();
+                        ScanTokenResult::TokenFoundAndAdded
+                    }
 
                     // if c is a letter then we have a keyword or identifier
+                    c if c.is_alphabetic() || c == '_' => {
+                        self.identifier()
+                    }
+
+                    // whitespace separates tokens but isn't one
+                    '\n' => {
+                        self.line += 1;
+                        self.advance(1);
+                        ScanTokenResult::NoTokenFromScanPass
+                    }
+                    ' ' | '\r' | '\t' => {
+                        self.advance(1);
+                        ScanTokenResult::NoTokenFromScanPass
+                    }
+
                     '(' => {
                         self.advance(1);
                         ScanTokenResult::TokenFound(TokenType::LeftParen)
@@ -271,10 +324,8 @@ impl Scanner<'_> {
                         ScanTokenResult::TokenFound(TokenType::Assignment)
                     }
                     _ => {
-                        // crate::run_time_error::run_time_error(self.line, "Unexpected character.".to_string());
-
-                        // I don't really have this implemented yet
                         self.advance(1);
+                        self.error(&format!("Unexpected character '{}'.", c));
                         ScanTokenResult::NoTokenFromScanPass
                     }
                 }
@@ -282,10 +333,96 @@ impl Scanner<'_> {
         }
     }
 
+    /// Scan the rest of a number literal.
+    ///
+    /// The integer part is required, and a fractional part is
+    /// only taken if there is a digit after the `.`, so that
+    /// `123.` still scans as a number followed by a dot.
+    fn number(&mut self) {
+        while self.current_char.is_some_and(|c| c.is_ascii_digit()) {
+            self.advance(1);
+        }
+
+        if self.current_char == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+            // consume the "."
+            self.advance(1);
+            while self.current_char.is_some_and(|c| c.is_ascii_digit()) {
+                self.advance(1);
+            }
+        }
+
+        let lexeme = &self.source[self.start..self.current];
+        self.tokens.push(Token::new(
+            TokenType::Number,
+            lexeme.to_string(),
+            Literal::Number(lexeme.parse().unwrap()),
+            self.line,
+            self.span(),
+        ));
+    }
+
+    /// Scan the rest of an identifier and decide whether it
+    /// is actually one of the reserved keywords.
+    fn identifier(&mut self) -> ScanTokenResult {
+        while self.current_char.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.advance(1);
+        }
+
+        let token_type = match &self.source[self.start..self.current] {
+            "and" => TokenType::And,
+            "class" => TokenType::Class,
+            "else" => TokenType::Else,
+            "false" => TokenType::False,
+            "fun" => TokenType::Fun,
+            "for" => TokenType::For,
+            "if" => TokenType::If,
+            "nil" => TokenType::Nil,
+            "or" => TokenType::Or,
+            "print" => TokenType::Print,
+            "return" => TokenType::Return,
+            "super" => TokenType::Super,
+            "this" => TokenType::This,
+            "true" => TokenType::True,
+            "var" => TokenType::Var,
+            "while" => TokenType::While,
+            _ => TokenType::Identifier,
+        };
+
+        ScanTokenResult::TokenFound(token_type)
+    }
+
+    /// The span of the lexeme being scanned.
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current)
+    }
+
+    /// Record a problem with the lexeme being scanned.
+    fn error(&mut self, message: &str) {
+        self.errors.push(Diagnostic::new(
+            self.line,
+            self.span(),
+            format!("Error: {}", message),
+        ));
+    }
+
+    /// Look at the character after `current_char` without consuming anything.
+    fn peek_next(&self) -> Option<char> {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next()
+    }
+
     /// increment the current index and current character by `increment_by`.
+    ///
+    /// `increment_by` counts characters, not bytes.
     pub fn advance(&mut self, increment_by: usize) {
 
-        self.current += increment_by;
-        self.current_char = self.source.chars().nth(self.current);
+        for _ in 0..increment_by {
+            match self.current_char {
+                Some(c) => self.current += c.len_utf8(),
+                None => break,
+            }
+            self.current_char = self.source[self.current..].chars().next();
+        }
     }
 }
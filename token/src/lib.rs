@@ -1,2 +1,3 @@
 pub mod token_type;
-pub mod token;
\ No newline at end of file
+pub mod token;
+pub mod span;
@@ -0,0 +1,24 @@
+//! Contains the `Span` struct.
+
+
+/// Where something is in the source code.
+///
+/// `start` and `end` are byte offsets into the source,
+/// so `&source[span.start..span.end]` is the text that
+/// was spanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// The smallest span that covers both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
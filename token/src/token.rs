@@ -4,6 +4,7 @@ pub enum Literal {
     Number(f64),
     String(String),
     Bool(bool),
+    Nil,
     NoLiteral,
 }
 
@@ -15,20 +16,24 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Literal,
     pub line: usize,
+    pub span: crate::span::Span,
 }
 
 impl Token {
-    pub fn new(token_type: crate::token_type::TokenType, lexeme: String, literal: Literal,line: usize) -> Self {
+    pub fn new(token_type: crate::token_type::TokenType, lexeme: String, literal: Literal,line: usize, span: crate::span::Span) -> Self {
         Token {
             token_type,
             lexeme,
             literal,
             line,
+            span,
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("{:?} {} {}", self.token_type, self.lexeme, self.line)
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {} {}", self.token_type, self.lexeme, self.line)
     }
 }
 
@@ -32,6 +32,7 @@ pub enum TokenType {
 
     // keywords
     And,
+    Class,
     Else,
     False,
     Fun,
@@ -41,6 +42,8 @@ pub enum TokenType {
     Or,
     Print,
     Return,
+    Super,
+    This,
     True,
     Var,
     While,
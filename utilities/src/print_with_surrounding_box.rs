@@ -7,9 +7,11 @@
 /// # Example
 /// 
 /// ```
+/// use utilities::print_with_surrounding_box::print_with_surrounding_box;
+///
 /// print_with_surrounding_box(vec![
-///     "This is the first line",
-///     "And the second",
+///     String::from("This is the first line"),
+///     String::from("And the second"),
 /// ]);
 /// ```
 /// 
@@ -40,7 +42,7 @@ pub fn print_with_surrounding_box(lines_of_statements_in_response: Vec<String>)
 /// 
 /// # Example
 /// 
-/// ```
+/// ```ignore
 /// get_surrounding_box(vec![
 ///     String::from("This is the first line"),
 ///     String::from("And the second"),
@@ -0,0 +1,39 @@
+//! Contains the `CallFrame` struct.
+
+use std::rc::Rc;
+
+use token::span::Span;
+
+use crate::object::{ObjFunction, ObjRef};
+
+
+/// A call in progress.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    /// The closure being run, which has the upvalues.
+    pub closure: ObjRef,
+
+    /// The closure's function, to hand.
+    pub function: Rc<ObjFunction>,
+
+    /// The offset of the next instruction in the function's code.
+    pub ip: usize,
+
+    /// Where the function's stack slots start: slot 0 is the
+    /// function itself (or `this` in a method), then come the
+    /// arguments and the locals.
+    pub slots: usize,
+}
+
+impl CallFrame {
+    /// The line of the instruction that's running.
+    pub fn line(&self) -> usize {
+        self.function.chunk.line_at(self.ip.saturating_sub(1))
+    }
+
+    /// The part of the source the instruction that's running came
+    /// from, if the function knows.
+    pub fn span(&self) -> Option<Span> {
+        self.function.chunk.span_at(self.ip.saturating_sub(1))
+    }
+}
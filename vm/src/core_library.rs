@@ -0,0 +1,87 @@
+//! Contains the core library: the native functions every VM
+//! starts with.  They're the same as the tree-walker's, down to
+//! the error messages.
+//!
+//! - `clock()` -- seconds since the Unix epoch, for timing
+//! - `len(string)` -- how many characters a string has
+//! - `str(value)` -- a value as a string, the way `print` shows it
+//! - `num(value)` -- a string parsed as a number, or `nil` if it
+//!   isn't one
+//! - `type(value)` -- the name of a value's type
+//! - `input()` -- the next line of stdin, or `nil` at the end
+//! - `exit(code)` -- stop the program with an exit code
+
+use std::io::BufRead;
+
+use crate::value::Value;
+use crate::vm_error::VmError;
+use crate::vm_struct::Vm;
+
+
+/// Define the core library in `vm`.
+pub fn register(vm: &mut Vm) {
+    vm.define_native("clock", 0, clock);
+    vm.define_native("len", 1, len);
+    vm.define_native("str", 1, str);
+    vm.define_native("num", 1, num);
+    vm.define_native("type", 1, type_of);
+    vm.define_native("input", 0, input);
+    vm.define_native("exit", 1, exit);
+}
+
+
+fn clock(_vm: &mut Vm, _arguments: &[Value]) -> Result<Value, VmError> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::number(now.as_secs_f64()))
+}
+
+/// Counts characters, not bytes, so `len(«≟»)` is 1.
+fn len(vm: &mut Vm, arguments: &[Value]) -> Result<Value, VmError> {
+    match vm.heap.as_string(arguments[0]) {
+        Some(string) => Ok(Value::number(string.chars().count() as f64)),
+        None => Err(VmError::native(&format!("len() expects a string, not a {}.", vm.heap.type_name(arguments[0])))),
+    }
+}
+
+fn str(vm: &mut Vm, arguments: &[Value]) -> Result<Value, VmError> {
+    let string = vm.heap.format(arguments[0]);
+    Ok(Value::obj(vm.heap.intern_owned(string)))
+}
+
+/// Surrounding whitespace is ignored, as in the tree-walker.
+fn num(vm: &mut Vm, arguments: &[Value]) -> Result<Value, VmError> {
+    if arguments[0].as_number().is_some() {
+        return Ok(arguments[0]);
+    }
+    match vm.heap.as_string(arguments[0]) {
+        Some(string) => Ok(string.trim().parse::<f64>().map_or(Value::NIL, Value::number)),
+        None => Err(VmError::native(&format!(
+            "num() expects a string or a number, not a {}.",
+            vm.heap.type_name(arguments[0])
+        ))),
+    }
+}
+
+fn type_of(vm: &mut Vm, arguments: &[Value]) -> Result<Value, VmError> {
+    let name = vm.heap.type_name(arguments[0]);
+    Ok(Value::obj(vm.heap.intern(name)))
+}
+
+/// The line comes back without its line ending.
+fn input(vm: &mut Vm, _arguments: &[Value]) -> Result<Value, VmError> {
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Value::NIL),
+        Ok(_) => Ok(Value::obj(vm.heap.intern(line.trim_end_matches(['\n', '\r'])))),
+        Err(error) => Err(VmError::native(&format!("Couldn't read input: {}.", error))),
+    }
+}
+
+fn exit(_vm: &mut Vm, arguments: &[Value]) -> Result<Value, VmError> {
+    match arguments[0].as_number() {
+        Some(code) if code.fract() == 0.0 && (0.0..=255.0).contains(&code) => Err(VmError::exit(code as i32)),
+        _ => Err(VmError::native("exit() expects a whole number from 0 to 255.")),
+    }
+}
@@ -0,0 +1,37 @@
+//! Contains the `GcOptions` struct and the `GcMode` enum.
+
+
+/// How the garbage collector should behave.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcOptions {
+    /// How collections are split up.
+    pub mode: GcMode,
+
+    /// Collect after every allocation instead of waiting for the
+    /// heap to grow, so a value the collector forgets to keep
+    /// alive goes away straight away instead of some time later.
+    /// This is for debugging the VM (and the collector).
+    pub stress: bool,
+
+    /// Write a line to stderr for every collection, saying how
+    /// much it freed.
+    pub log: bool,
+}
+
+
+/// Whether a collection happens all at once or a bit at a time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GcMode {
+    /// Stop the program and do the whole collection in one go.
+    /// This gets through the most garbage for the time spent, but
+    /// the pause grows with the heap.
+    #[default]
+    StopTheWorld,
+
+    /// Spread each collection over many short pauses, doing a
+    /// bounded amount of marking or sweeping after each
+    /// allocation.  Between pauses, the program writing to
+    /// objects tells the collector about the values it stores,
+    /// so none are missed.
+    Incremental,
+}
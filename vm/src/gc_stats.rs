@@ -0,0 +1,35 @@
+//! Contains the `GcStats` struct.
+
+use std::time::Duration;
+
+
+/// How much time the garbage collector has taken from the program,
+/// and in how big chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// How many collections have finished.
+    pub collections: u64,
+
+    /// How many times the program was stopped for the collector.
+    /// Stop-the-world collections pause once each; incremental
+    /// ones pause many times.
+    pub pauses: u64,
+
+    /// The time spent in all those pauses together.
+    pub total_pause: Duration,
+
+    /// The longest single pause.
+    pub longest_pause: Duration,
+
+    /// Roughly how many bytes have been freed.
+    pub bytes_freed: u64,
+}
+
+impl GcStats {
+    /// Count a pause that took `duration`.
+    pub fn record_pause(&mut self, duration: Duration) {
+        self.pauses += 1;
+        self.total_pause += duration;
+        self.longest_pause = self.longest_pause.max(duration);
+    }
+}
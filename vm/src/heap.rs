@@ -0,0 +1,531 @@
+//! Contains the `Heap` struct.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::gc_options::{GcMode, GcOptions};
+use crate::gc_stats::GcStats;
+use crate::object::{LoxString, ObjRef, Object};
+use crate::value::{Value, ValueKind};
+
+
+/// How much the heap can grow to before the first collection.
+const FIRST_COLLECTION_BYTES: usize = 1024 * 1024;
+
+/// After a collection, the next one waits until the heap has grown
+/// to this many times what was left.
+const GROWTH_FACTOR: usize = 2;
+
+/// How many gray objects an incremental step blackens.
+const MARK_STEP: usize = 100;
+
+/// How many slots an incremental step sweeps.
+const SWEEP_STEP: usize = 500;
+
+
+/// Where the VM's objects live.
+///
+/// Objects go in a list of slots and are referred to by their
+/// index.  A slot that's been freed is remembered and reused by
+/// the next allocation.
+///
+/// The heap also interns strings: it keeps every string it has
+/// made in a table, and making a string with the same characters
+/// again gives back the existing one.
+///
+/// Objects are freed by a tri-color mark-and-sweep collector.
+/// The VM marks the roots gray, then the heap blackens gray
+/// objects one at a time (marking what each refers to gray) until
+/// there are none left, and frees every object still white.  The
+/// table of interned strings doesn't count as a root, so strings
+/// nothing else refers to are dropped from it.
+///
+/// In `GcMode::Incremental`, `collect_step` only does a little of
+/// that at a time, and the program runs in between.  Two things
+/// keep it from freeing something the program still uses:
+/// - Objects allocated while marking start black, with what they
+///   refer to marked, and anything stored in an object goes
+///   through `write_barrier`, which marks it.  So a black object
+///   never points at a white one.
+/// - The stack and the globals change without any barrier, so
+///   when the gray objects run out the VM marks the roots again,
+///   and marking only ends once that finds nothing new.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    free: Vec<u32>,
+    strings: HashMap<Rc<str>, ObjRef>,
+
+    /// Whether each slot's object has been reached by the
+    /// collection in progress: white objects aren't marked, gray
+    /// ones are marked and on the gray stack, black ones are
+    /// marked and off it.
+    marked: Vec<bool>,
+    gray: Vec<ObjRef>,
+
+    phase: Phase,
+
+    /// Roughly how many bytes the objects take up.
+    bytes_allocated: usize,
+
+    /// What `bytes_allocated` was when the collection in progress
+    /// started, and how much of it has been swept and kept.
+    bytes_before: usize,
+    bytes_kept: usize,
+
+    /// How big `bytes_allocated` can get before it's time to
+    /// collect.  While an incremental collection is in progress
+    /// it's zero, so every allocation does a step.
+    next_collection: usize,
+
+    /// How many bytes have been allocated since the last
+    /// `reset_total`, freed or not.
+    total_allocated: usize,
+
+    /// The caps the VM's limits put on `bytes_allocated` and
+    /// `total_allocated`, or `usize::MAX` for none.  Going over
+    /// either makes `should_collect` say yes, so the VM gets to
+    /// check.
+    max_bytes: usize,
+    max_total: usize,
+
+    options: GcOptions,
+    stats: GcStats,
+}
+
+
+/// Where the heap is in a collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// No collection in progress.
+    Idle,
+
+    /// Blackening gray objects.
+    Marking,
+
+    /// Freeing white objects, in slot order.  The slots before
+    /// this one are done.
+    Sweeping(usize),
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap {
+            objects: Vec::new(),
+            free: Vec::new(),
+            strings: HashMap::new(),
+            marked: Vec::new(),
+            gray: Vec::new(),
+            phase: Phase::Idle,
+            bytes_allocated: 0,
+            bytes_before: 0,
+            bytes_kept: 0,
+            next_collection: FIRST_COLLECTION_BYTES,
+            total_allocated: 0,
+            max_bytes: usize::MAX,
+            max_total: usize::MAX,
+            options: GcOptions::default(),
+            stats: GcStats::default(),
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        //! Create a new empty `Heap`.
+        Heap::default()
+    }
+
+    pub fn set_gc_options(&mut self, options: GcOptions) {
+        //! Change how the garbage collector behaves from the next
+        //! allocation on.
+        //!
+        //! A collection that's in progress is finished the new way.
+        self.options = options;
+        if options.stress {
+            self.next_collection = self.next_collection.min(self.bytes_allocated);
+        }
+    }
+
+    pub fn set_memory_limits(&mut self, max_bytes: Option<usize>, max_total: Option<usize>) {
+        //! Cap how many bytes the objects can take up at once, and
+        //! how many can be allocated since the last `reset_total`.
+        //!
+        //! The heap doesn't enforce these itself: it collects when
+        //! it reaches `max_bytes`, and leaves the VM to see whether
+        //! that was enough.
+        self.max_bytes = max_bytes.unwrap_or(usize::MAX);
+        self.max_total = max_total.unwrap_or(usize::MAX);
+        self.next_collection = self.next_collection.min(self.max_bytes);
+    }
+
+    /// Start counting `total_allocated` over, for a new run.
+    pub fn reset_total(&mut self) {
+        self.total_allocated = 0;
+    }
+
+    /// How much time the garbage collector has taken so far.
+    pub fn gc_stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// Count a pause the VM made to collect garbage.
+    pub fn record_pause(&mut self, duration: Duration) {
+        self.stats.record_pause(duration);
+    }
+
+    /// Put `object` on the heap.
+    ///
+    /// This never collects garbage itself, since the heap doesn't
+    /// know the roots; the VM asks `should_collect` when it's
+    /// somewhere every live value can be found.
+    pub fn allocate(&mut self, object: Object) -> ObjRef {
+        let size = object.size();
+        self.bytes_allocated += size;
+        self.total_allocated = self.total_allocated.saturating_add(size);
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = Some(object);
+                index as usize
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.marked.push(false);
+                self.objects.len() - 1
+            }
+        };
+        // A new object is live for the rest of the collection in
+        // progress: it's black if marking is still going on, and
+        // either kept by the sweep or left white for the next
+        // collection if the sweep has already gone past it.
+        match self.phase {
+            Phase::Idle => {}
+            Phase::Marking => {
+                self.marked[index] = true;
+                self.blacken(ObjRef(index as u32));
+            }
+            Phase::Sweeping(next) if index < next => self.bytes_kept += size,
+            Phase::Sweeping(_) => self.marked[index] = true,
+        }
+        ObjRef(index as u32)
+    }
+
+    /// Whether enough has been allocated since the last collection
+    /// that it's time for another, or for the next step of one, or
+    /// so much that it's over the limits.
+    #[inline]
+    pub fn should_collect(&self) -> bool {
+        self.bytes_allocated > self.next_collection || self.total_allocated > self.max_total
+    }
+
+    /// Whether the objects take up more than the limits allow.
+    pub fn over_max_bytes(&self) -> bool {
+        self.bytes_allocated > self.max_bytes
+    }
+
+    /// Whether more has been allocated since the last
+    /// `reset_total` than the limits allow.
+    pub fn over_max_total(&self) -> bool {
+        self.total_allocated > self.max_total
+    }
+
+    /// Whether a collection is in progress.
+    pub fn is_collecting(&self) -> bool {
+        self.phase != Phase::Idle
+    }
+
+    /// Whether the next `collect_step` needs the roots marked
+    /// first: at the start of a collection, or when an incremental
+    /// one has run out of gray objects.
+    pub fn needs_roots(&self) -> bool {
+        match self.phase {
+            Phase::Idle => true,
+            Phase::Marking => self.gray.is_empty(),
+            Phase::Sweeping(_) => false,
+        }
+    }
+
+    /// Mark `value` as reachable, if it's an object.
+    pub fn mark_value(&mut self, value: Value) {
+        if let Some(reference) = value.as_obj() {
+            self.mark_object(reference);
+        }
+    }
+
+    /// Mark the object `reference` refers to as reachable: white
+    /// objects turn gray.
+    pub fn mark_object(&mut self, reference: ObjRef) {
+        let marked = &mut self.marked[reference.0 as usize];
+        if !*marked {
+            *marked = true;
+            self.gray.push(reference);
+        }
+    }
+
+    /// Tell the collector that `value` has been stored in an object
+    /// on the heap.
+    ///
+    /// This only does something while an incremental collection is
+    /// marking, when the object it's stored in may already be
+    /// black.
+    #[inline]
+    pub fn write_barrier(&mut self, value: Value) {
+        if self.phase == Phase::Marking {
+            self.mark_value(value);
+        }
+    }
+
+    /// Collect garbage, or some of it in `GcMode::Incremental`.
+    ///
+    /// If `needs_roots` says so, everything the program can still
+    /// get at has to have been marked first, or it'll be freed out
+    /// from under it.
+    pub fn collect_step(&mut self) {
+        if self.phase == Phase::Idle {
+            self.phase = Phase::Marking;
+            self.bytes_before = self.bytes_allocated;
+            self.next_collection = 0;
+        }
+        let incremental = self.options.mode == GcMode::Incremental;
+        let (mark_step, sweep_step) = match (incremental, self.options.stress) {
+            (false, _) => (usize::MAX, usize::MAX),
+            // As small as it gets, to give the program as many
+            // chances as possible to get in the collector's way.
+            // The sweep has to keep ahead of the allocations,
+            // though, or it'd never reach the end.
+            (true, true) => (1, 16),
+            (true, false) => (MARK_STEP, SWEEP_STEP),
+        };
+
+        if self.phase == Phase::Marking {
+            if self.gray.is_empty() {
+                // The roots have just been marked again and there's
+                // nothing new, so everything reachable is black.
+                let marked = &self.marked;
+                self.strings.retain(|_, string| marked[string.0 as usize]);
+                self.phase = Phase::Sweeping(0);
+                self.bytes_kept = 0;
+            } else {
+                for _ in 0..mark_step {
+                    let Some(reference) = self.gray.pop() else {
+                        break;
+                    };
+                    self.blacken(reference);
+                }
+                if !incremental {
+                    self.collect_step();
+                }
+                return;
+            }
+        }
+
+        if let Phase::Sweeping(next) = self.phase {
+            let end = next.saturating_add(sweep_step).min(self.objects.len());
+            self.sweep(next..end);
+            self.phase = Phase::Sweeping(end);
+            if end == self.objects.len() {
+                self.finish();
+            }
+        }
+    }
+
+    /// Mark everything the gray object `reference` refers to,
+    /// which turns it black.
+    fn blacken(&mut self, reference: ObjRef) {
+        let Heap { objects, marked, gray, .. } = self;
+        if let Some(object) = &objects[reference.0 as usize] {
+            object.for_each_reference(|value| {
+                if let Some(reference) = value.as_obj() {
+                    if !marked[reference.0 as usize] {
+                        marked[reference.0 as usize] = true;
+                        gray.push(reference);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Free the white objects in `slots` and turn the black ones
+    /// white again for the next collection.  What's kept is
+    /// counted up afresh, which also catches tables that have
+    /// grown since they were allocated.
+    fn sweep(&mut self, slots: std::ops::Range<usize>) {
+        for index in slots {
+            let Some(object) = &self.objects[index] else {
+                continue;
+            };
+            if std::mem::take(&mut self.marked[index]) {
+                self.bytes_kept += object.size();
+            } else {
+                self.objects[index] = None;
+                self.free.push(index as u32);
+            }
+        }
+    }
+
+    /// Wrap up the collection once the sweep is done.
+    fn finish(&mut self) {
+        let before = self.bytes_allocated;
+        self.bytes_allocated = self.bytes_kept;
+        self.phase = Phase::Idle;
+        self.next_collection = if self.options.stress {
+            self.bytes_allocated
+        } else {
+            (self.bytes_allocated * GROWTH_FACTOR).max(FIRST_COLLECTION_BYTES).min(self.max_bytes)
+        };
+
+        let freed = before.saturating_sub(self.bytes_allocated);
+        self.stats.collections += 1;
+        self.stats.bytes_freed += freed as u64;
+        if self.options.log {
+            eprintln!(
+                "-- gc: freed {} bytes ({} -> {}), next at {}",
+                freed,
+                self.bytes_before,
+                self.bytes_allocated,
+                self.next_collection,
+            );
+        }
+    }
+
+    /// Roughly how many bytes the objects on the heap take up.
+    pub fn bytes_allocated(&self) -> usize {
+        self.bytes_allocated
+    }
+
+    /// The string with `chars`, made if there isn't one yet.
+    pub fn intern(&mut self, chars: &str) -> ObjRef {
+        match self.strings.get(chars) {
+            Some(string) => *string,
+            None => self.add_string(Rc::from(chars)),
+        }
+    }
+
+    /// Like `intern`, but takes ownership of the characters so they
+    /// don't need copying if the string is new.
+    pub fn intern_owned(&mut self, chars: String) -> ObjRef {
+        match self.strings.get(chars.as_str()) {
+            Some(string) => *string,
+            None => self.add_string(Rc::from(chars)),
+        }
+    }
+
+    fn add_string(&mut self, chars: Rc<str>) -> ObjRef {
+        let string = self.allocate(Object::String(LoxString { chars: Rc::clone(&chars) }));
+        self.strings.insert(chars, string);
+        string
+    }
+
+    /// The object `reference` refers to.
+    #[inline]
+    pub fn get(&self, reference: ObjRef) -> &Object {
+        self.objects[reference.0 as usize].as_ref().expect("a live value never refers to a freed object")
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, reference: ObjRef) -> &mut Object {
+        self.objects[reference.0 as usize].as_mut().expect("a live value never refers to a freed object")
+    }
+
+    /// The characters of the string `reference` refers to.
+    ///
+    /// Only for references the compiler or the VM knows are
+    /// strings, like the names of globals.
+    pub fn string(&self, reference: ObjRef) -> &str {
+        match self.get(reference) {
+            Object::String(string) => &string.chars,
+            other => unreachable!("expected a string, found a {}", other.type_name()),
+        }
+    }
+
+    /// The characters of `value`, if it's a string.
+    pub fn as_string(&self, value: Value) -> Option<&Rc<str>> {
+        match value.as_obj() {
+            Some(reference) => match self.get(reference) {
+                Object::String(string) => Some(&string.chars),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// How many objects are on the heap.
+    pub fn len(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The name of `value`'s type, for error messages.
+    pub fn type_name(&self, value: Value) -> &'static str {
+        match value.kind() {
+            ValueKind::Nil => "nil",
+            ValueKind::Bool(_) => "bool",
+            ValueKind::Number(_) => "number",
+            ValueKind::Obj(reference) => self.get(reference).type_name(),
+        }
+    }
+
+    /// How `print` shows `value`, the same as the tree-walker does.
+    pub fn format(&self, value: Value) -> String {
+        match value.kind() {
+            ValueKind::Nil => String::from("nil"),
+            ValueKind::Bool(boolean) => boolean.to_string(),
+            // `f64`'s Display already leaves off a trailing `.0`
+            ValueKind::Number(number) => number.to_string(),
+            ValueKind::Obj(reference) => match self.get(reference) {
+                Object::String(string) => string.chars.to_string(),
+                Object::Function(function) if function.name.is_empty() => String::from("<script>"),
+                Object::Function(function) => format!("<fn {}>", function.name),
+                Object::Closure(closure) => format!("<fn {}>", closure.function.name),
+                Object::Upvalue(_) => String::from("upvalue"),
+                Object::Native(native) => format!("<fn {}>", native.name),
+                Object::Class(class) => self.string(class.name).to_string(),
+                Object::Instance(instance) => match self.get(instance.class) {
+                    Object::Class(class) => format!("{} instance", self.string(class.name)),
+                    _ => unreachable!("an instance's class is always a class"),
+                },
+                Object::BoundMethod(bound) => self.format(Value::obj(bound.method)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::ObjClass;
+    use crate::table::Table;
+
+    #[test]
+    fn test_strings_are_interned() {
+        let mut heap = Heap::new();
+        let first = heap.intern("≟");
+        let second = heap.intern_owned(String::from("≟"));
+
+        assert_eq!(first, second);
+        assert_ne!(first, heap.intern("≠"));
+        assert_eq!(heap.len(), 2);
+        assert_eq!(heap.format(Value::obj(first)), "≟");
+    }
+
+    #[test]
+    fn test_collecting_frees_what_isnt_marked() {
+        let mut heap = Heap::new();
+        let kept = heap.intern("kept");
+        heap.intern("dropped");
+        let class = heap.allocate(Object::Class(ObjClass { name: kept, methods: Table::default(), epoch: 0 }));
+
+        heap.mark_object(class);
+        heap.collect_step();
+
+        assert_eq!(heap.len(), 2);
+        assert_eq!(heap.string(kept), "kept");
+        assert_eq!(heap.intern("kept"), kept);
+        heap.intern("dropped");
+        assert_eq!(heap.len(), 3, "the string was dropped from the intern table, so it's made again");
+    }
+}
@@ -0,0 +1,40 @@
+//! Contains the `InlineCache` enum.
+
+use crate::object::ObjRef;
+use crate::shape::ShapeId;
+
+
+/// What a `GetProperty`, `SetProperty` or `Invoke` instruction found
+/// the last time it ran, so that next time it can skip looking the
+/// name up if the instance has the same shape.  `GetGlobal` and
+/// `SetGlobal` remember where their variable is, which never
+/// changes.
+///
+/// Each loaded function has one of these for each of its
+/// instructions.  Only one shape is remembered at a time: an
+/// instruction that sees instances of many shapes keeps looking
+/// names up, just as without the cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InlineCache {
+    /// The instruction hasn't run yet (or isn't one that caches).
+    #[default]
+    Empty,
+
+    /// Instances of `shape` have the property as the field in
+    /// `slot`.
+    Field { shape: ShapeId, slot: u32 },
+
+    /// Instances of `shape` don't have the field being set, and
+    /// setting it gives them the shape `next`.
+    AddField { shape: ShapeId, next: ShapeId },
+
+    /// Instances of `shape` and of `class` don't have a field with
+    /// the name, so the property is the class's `method`.  That's
+    /// only sure while the class's epoch is still `epoch`, which
+    /// changes when its methods do.  Other classes changing, or
+    /// new classes being made, leave it alone.
+    Method { shape: ShapeId, class: ObjRef, method: ObjRef, epoch: u64 },
+
+    /// The global variable is in `slot`.
+    Global { slot: u32 },
+}
@@ -0,0 +1,34 @@
+//! Contains the virtual machine, which runs the bytecode that the
+//! compiler makes.
+//!
+//! - `vm_struct` has the `Vm` itself.
+//! - `value` has the `Value` enum, what goes on the VM's stack.
+//! - `object` has the objects that live on the heap, like strings,
+//!   functions and instances, and `heap` has the `Heap` they live
+//!   in.  `gc_options` has the `GcOptions` for its garbage
+//!   collector, and `gc_stats` has the `GcStats` it keeps.
+//! - `table` has the hash `Table` keyed by interned strings, for
+//!   globals and methods.
+//! - `shape` has the `Shapes` that say where each instance keeps
+//!   its fields, and `inline_cache` has the `InlineCache` that
+//!   property instructions remember them in.
+//! - `call_frame` has the `CallFrame` struct, one per call in
+//!   progress.
+//! - `vm_error` has the `VmError` struct.
+//! - `native_function` has functions written in Rust, and
+//!   `core_library` has the ones every VM starts with.
+
+
+pub mod vm_struct;
+pub mod value;
+pub mod object;
+pub mod heap;
+pub mod gc_options;
+pub mod gc_stats;
+pub mod table;
+pub mod shape;
+pub mod inline_cache;
+pub mod call_frame;
+pub mod vm_error;
+pub mod native_function;
+pub mod core_library;
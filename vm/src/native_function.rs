@@ -0,0 +1,23 @@
+//! Contains the `NativeFunction` struct.
+
+use crate::value::Value;
+use crate::vm_error::VmError;
+use crate::vm_struct::Vm;
+
+
+/// The Rust side of a native function.
+///
+/// It gets the VM (so it can make strings, say) and the arguments,
+/// which there are always as many of as the function's arity.
+/// Errors should come from `VmError::native`; the VM adds the
+/// stack trace.
+pub type NativeFn = fn(&mut Vm, &[Value]) -> Result<Value, VmError>;
+
+
+/// A function written in Rust that Lox code can call.
+#[derive(Debug, Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
@@ -0,0 +1,192 @@
+//! Contains the objects that live on the VM's heap.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use bytecode::chunk::Chunk;
+
+use crate::inline_cache::InlineCache;
+use crate::native_function::NativeFunction;
+use crate::shape::ShapeId;
+use crate::table::Table;
+use crate::value::Value;
+
+
+/// A reference to an object on the heap.
+///
+/// This is an index into the heap's slots rather than a pointer,
+/// so values stay `Copy` and the heap decides when objects go away.
+/// Once the garbage collector frees an object its slot can be
+/// reused, so a reference is only good while something the
+/// collector knows about (like the stack or a global) refers to
+/// the object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjRef(pub u32);
+
+
+/// Something on the heap.
+#[derive(Debug)]
+pub enum Object {
+    String(LoxString),
+    Function(Rc<ObjFunction>),
+    Closure(ObjClosure),
+    Upvalue(ObjUpvalue),
+    Native(NativeFunction),
+    Class(ObjClass),
+    Instance(ObjInstance),
+    BoundMethod(BoundMethod),
+}
+
+impl Object {
+    /// The name of the object's type, for error messages and for
+    /// the `type()` native.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::String(_) => "string",
+            Object::Function(_) | Object::Closure(_) | Object::Native(_) | Object::BoundMethod(_) => "function",
+            Object::Upvalue(_) => "upvalue",
+            Object::Class(_) => "class",
+            Object::Instance(_) => "instance",
+        }
+    }
+
+    /// Roughly how many bytes the object takes up, which is what
+    /// the heap counts to decide when to collect garbage.
+    pub fn size(&self) -> usize {
+        let entry = std::mem::size_of::<(ObjRef, Value)>();
+        std::mem::size_of::<Object>()
+            + match self {
+                Object::String(string) => string.chars.len(),
+                Object::Function(function) => {
+                    function.chunk.code.len()
+                        + function.constants.len() * std::mem::size_of::<Value>()
+                        + function.caches.len() * std::mem::size_of::<InlineCache>()
+                }
+                Object::Closure(closure) => closure.upvalues.len() * std::mem::size_of::<ObjRef>(),
+                Object::Class(class) => class.methods.capacity() * entry,
+                Object::Instance(instance) => instance.fields.capacity() * std::mem::size_of::<Value>(),
+                Object::Upvalue(_) | Object::Native(_) | Object::BoundMethod(_) => 0,
+            }
+    }
+
+    /// Call `visit` with every value the object refers to, which
+    /// the garbage collector has to keep alive along with it.
+    pub fn for_each_reference(&self, mut visit: impl FnMut(Value)) {
+        match self {
+            Object::String(_) | Object::Native(_) => {}
+            Object::Function(function) => function.constants.iter().copied().for_each(visit),
+            Object::Closure(closure) => {
+                visit(Value::obj(closure.function_object));
+                closure.upvalues.iter().for_each(|upvalue| visit(Value::obj(*upvalue)));
+            }
+            Object::Upvalue(ObjUpvalue::Closed(value)) => visit(*value),
+            Object::Upvalue(ObjUpvalue::Open(_)) => {}
+            Object::Class(class) => {
+                visit(Value::obj(class.name));
+                for (name, method) in &class.methods {
+                    visit(Value::obj(*name));
+                    visit(*method);
+                }
+            }
+            Object::Instance(instance) => {
+                // The field names are kept alive by the shapes.
+                visit(Value::obj(instance.class));
+                instance.fields.iter().copied().for_each(visit);
+            }
+            Object::BoundMethod(bound) => {
+                visit(bound.receiver);
+                visit(Value::obj(bound.method));
+            }
+        }
+    }
+}
+
+
+/// An interned string.
+///
+/// The characters are shared with the heap's table of interned
+/// strings, which is how it finds them again.
+#[derive(Debug)]
+pub struct LoxString {
+    pub chars: Rc<str>,
+}
+
+
+/// A function loaded into the VM.
+///
+/// This is a `bytecode::function::Function` with its constants
+/// turned into values, so running it never has to go back to the
+/// compiled form.  It's shared (`Rc`) so that a call frame can
+/// hold on to the code it's running.
+#[derive(Debug)]
+pub struct ObjFunction {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+    pub constants: Vec<Value>,
+    /// The inline cache of the instruction at each offset in the
+    /// code.  Only the ones for property instructions get used.
+    pub caches: Box<[Cell<InlineCache>]>,
+}
+
+
+/// A function together with the variables it captured when it was
+/// made.  Every function the VM calls is wrapped in one, even if it
+/// captures nothing.
+#[derive(Debug)]
+pub struct ObjClosure {
+    /// The heap object for the function, which keeps its constants
+    /// alive.
+    pub function_object: ObjRef,
+    pub function: Rc<ObjFunction>,
+    /// The `ObjUpvalue`s, one for each variable captured.
+    pub upvalues: Vec<ObjRef>,
+}
+
+
+/// A variable captured by a closure.
+///
+/// While the variable's function is still running, it lives in
+/// its stack slot and the upvalue is open, pointing at the slot.
+/// When it goes out of scope the value moves into the upvalue,
+/// which is then closed.  Closures that captured the same variable
+/// share the upvalue, so they see each other's assignments either
+/// way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjUpvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+
+/// A class and the methods declared in it (or inherited by it).
+#[derive(Debug)]
+pub struct ObjClass {
+    pub name: ObjRef,
+    pub methods: Table<Value>,
+    /// The VM's method epoch when the class was made or its methods
+    /// last changed.  No other class has the same one.
+    pub epoch: u64,
+}
+
+
+/// An object made by calling a class.
+///
+/// The fields are in the order they were first set, and the shape
+/// says which is which.
+#[derive(Debug)]
+pub struct ObjInstance {
+    pub class: ObjRef,
+    pub shape: ShapeId,
+    pub fields: Vec<Value>,
+}
+
+
+/// A method read off an instance, which remembers the instance so
+/// `this` still works when it's called later.
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
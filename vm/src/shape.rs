@@ -0,0 +1,109 @@
+//! Contains the `Shapes` struct and the `ShapeId` type.
+
+use crate::object::ObjRef;
+use crate::table::Table;
+
+
+/// Which shape an instance has: an index into `Shapes`.
+///
+/// Shapes are never freed, so an id always means the same shape,
+/// which is what lets an inline cache trust one it saw earlier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShapeId(pub u32);
+
+
+/// One layout of fields.
+#[derive(Debug)]
+struct Shape {
+    /// The field the shape added to the one it came from, or
+    /// `None` for the empty shape.
+    name: Option<ObjRef>,
+    /// Every field, with its index in the instance's fields.
+    slots: Table<usize>,
+    /// The shapes made by adding a field to this one.
+    transitions: Table<ShapeId>,
+}
+
+
+/// The shapes of the VM's instances, which say where each field
+/// is kept.
+///
+/// An instance keeps its fields in a plain list, in the order they
+/// were first set, and its shape maps names to places in the list.
+/// Instances that got the same fields in the same order share a
+/// shape: every instance starts with the empty one, and setting a
+/// new field moves it along to the shape with that field added,
+/// which is only made the first time.
+///
+/// So an instruction that keeps meeting instances of one shape
+/// can remember where the field was last time and skip looking the
+/// name up (see `InlineCache`).
+#[derive(Debug)]
+pub struct Shapes {
+    shapes: Vec<Shape>,
+}
+
+impl Default for Shapes {
+    fn default() -> Self {
+        Shapes::new()
+    }
+}
+
+impl Shapes {
+    /// The shape of an instance with no fields.
+    pub const EMPTY: ShapeId = ShapeId(0);
+
+    pub fn new() -> Self {
+        //! Create a new `Shapes` with just the empty shape.
+        Shapes {
+            shapes: vec![Shape { name: None, slots: Table::default(), transitions: Table::default() }],
+        }
+    }
+
+    /// Where instances of `shape` keep the field `name`, if they
+    /// have it.
+    pub fn slot(&self, shape: ShapeId, name: ObjRef) -> Option<usize> {
+        self.shapes[shape.0 as usize].slots.get(&name).copied()
+    }
+
+    /// The shape an instance of `shape` gets when the field `name`
+    /// is added to it.  It goes at the end of the fields.
+    pub fn add_field(&mut self, shape: ShapeId, name: ObjRef) -> ShapeId {
+        if let Some(next) = self.shapes[shape.0 as usize].transitions.get(&name) {
+            return *next;
+        }
+        let next = ShapeId(self.shapes.len() as u32);
+        let mut slots = self.shapes[shape.0 as usize].slots.clone();
+        slots.insert(name, slots.len());
+        self.shapes.push(Shape { name: Some(name), slots, transitions: Table::default() });
+        self.shapes[shape.0 as usize].transitions.insert(name, next);
+        next
+    }
+
+    /// Every field name any shape has, which the garbage collector
+    /// has to keep alive since the shapes stay.
+    pub fn names(&self) -> impl Iterator<Item = ObjRef> + '_ {
+        self.shapes.iter().filter_map(|shape| shape.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instances_with_fields_in_the_same_order_share_a_shape() {
+        let mut shapes = Shapes::new();
+        let (x, y) = (ObjRef(1), ObjRef(2));
+        let mut add_fields = |names: &[ObjRef]| names.iter().fold(Shapes::EMPTY, |shape, name| shapes.add_field(shape, *name));
+        let xy = add_fields(&[x, y]);
+        let yx = add_fields(&[y, x]);
+
+        assert_eq!(add_fields(&[x, y]), xy);
+        assert_ne!(xy, yx);
+        assert_eq!((shapes.slot(xy, x), shapes.slot(xy, y)), (Some(0), Some(1)));
+        assert_eq!((shapes.slot(yx, x), shapes.slot(yx, y)), (Some(1), Some(0)));
+        assert_eq!(shapes.slot(Shapes::EMPTY, x), None);
+        assert_eq!(shapes.names().count(), 4);
+    }
+}
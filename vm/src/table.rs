@@ -0,0 +1,39 @@
+//! Contains the `Table` type.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use crate::object::ObjRef;
+
+
+/// A hash table keyed by interned strings.
+///
+/// Since equal strings are the same object, the key is just the
+/// reference, which hashes much faster than the characters would.
+pub type Table<V> = HashMap<ObjRef, V, BuildHasherDefault<ObjRefHasher>>;
+
+
+/// Hashes an `ObjRef` with a single multiply, which spreads the
+/// small, dense numbers references are over the whole `u64`.
+#[derive(Debug, Default)]
+pub struct ObjRefHasher(u64);
+
+impl Hasher for ObjRefHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(*byte as u64);
+        }
+    }
+
+    fn write_u32(&mut self, number: u32) {
+        self.write_u64(number as u64);
+    }
+
+    fn write_u64(&mut self, number: u64) {
+        self.0 = (self.0.rotate_left(5) ^ number).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
@@ -0,0 +1,278 @@
+//! Contains the `Value` struct and the `ValueKind` enum.
+//!
+//! There are two ways a value can be stored.  Normally it's a
+//! `ValueKind`, a tagged enum.  With the `nan-boxing` feature it's
+//! a single `u64`, which is either a number's bits or a NaN with
+//! the other kinds of value packed into the bits a NaN doesn't
+//! use.  Either way, the rest of the VM makes values with the
+//! constructors below and looks inside them with `kind` and the
+//! `as_` methods, so it doesn't know which it's got.
+
+use crate::object::ObjRef;
+
+
+/// What a value is, to match on.
+///
+/// The tag takes a whole word, so that copying a value is copying
+/// two words.  With a one-byte tag the compiler copies the bytes
+/// after it in two overlapping pieces, and reading a value back
+/// soon after writing it (as pushing then popping does) stalls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u64)]
+pub enum ValueKind {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+
+/// A value on the VM's stack, in a variable or in a field.
+///
+/// Values are small and `Copy`: anything bigger than a number
+/// lives on the heap, and the value just refers to it.  Strings
+/// are interned, so two strings with the same characters are the
+/// same object and comparing them is comparing references.
+#[cfg(not(feature = "nan-boxing"))]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Value(ValueKind);
+
+#[cfg(not(feature = "nan-boxing"))]
+impl Value {
+    pub const NIL: Value = Value(ValueKind::Nil);
+
+    #[inline]
+    pub fn bool(boolean: bool) -> Value {
+        Value(ValueKind::Bool(boolean))
+    }
+
+    #[inline]
+    pub fn number(number: f64) -> Value {
+        Value(ValueKind::Number(number))
+    }
+
+    #[inline]
+    pub fn obj(reference: ObjRef) -> Value {
+        Value(ValueKind::Obj(reference))
+    }
+
+    /// What the value is.
+    #[inline]
+    pub fn kind(self) -> ValueKind {
+        self.0
+    }
+
+    /// The number, if the value is one.
+    #[inline]
+    pub fn as_number(self) -> Option<f64> {
+        match self.0 {
+            ValueKind::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// The object the value refers to, if it refers to one.
+    #[inline]
+    pub fn as_obj(self) -> Option<ObjRef> {
+        match self.0 {
+            ValueKind::Obj(reference) => Some(reference),
+            _ => None,
+        }
+    }
+
+    /// Lox's truthiness: `nil` and `false` are falsey, everything
+    /// else (including `0` and `«»`) is truthy.
+    #[inline]
+    pub fn is_truthy(self) -> bool {
+        !matches!(self.0, ValueKind::Nil | ValueKind::Bool(false))
+    }
+}
+
+
+/// The bits that make a double a quiet NaN, plus one more so that
+/// the NaN the hardware makes (which only has the quiet bit set)
+/// is still a number.
+#[cfg(feature = "nan-boxing")]
+const QUIET_NAN: u64 = 0x7ffc_0000_0000_0000;
+
+/// Set on the NaNs that are objects, with the object's index in
+/// the low 32 bits.
+#[cfg(feature = "nan-boxing")]
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+
+#[cfg(feature = "nan-boxing")]
+const TAG_NIL: u64 = 1;
+#[cfg(feature = "nan-boxing")]
+const TAG_FALSE: u64 = 2;
+#[cfg(feature = "nan-boxing")]
+const TAG_TRUE: u64 = 3;
+
+
+/// A value on the VM's stack, in a variable or in a field.
+///
+/// Values are small and `Copy`: anything bigger than a number
+/// lives on the heap, and the value just refers to it.  Strings
+/// are interned, so two strings with the same characters are the
+/// same object and comparing them is comparing references.
+///
+/// This one is NaN-boxed: a number is stored as its own bits, and
+/// `nil`, the booleans and objects are quiet NaNs no arithmetic
+/// makes.  Numbers that are NaN are all stored as the one NaN the
+/// hardware makes, so they can't be mistaken for anything else.
+#[cfg(feature = "nan-boxing")]
+#[derive(Clone, Copy)]
+pub struct Value(u64);
+
+#[cfg(feature = "nan-boxing")]
+impl Value {
+    pub const NIL: Value = Value(QUIET_NAN | TAG_NIL);
+
+    #[inline]
+    pub fn bool(boolean: bool) -> Value {
+        Value(QUIET_NAN | if boolean { TAG_TRUE } else { TAG_FALSE })
+    }
+
+    #[inline]
+    pub fn number(number: f64) -> Value {
+        if number.is_nan() {
+            return Value(f64::NAN.to_bits());
+        }
+        Value(number.to_bits())
+    }
+
+    #[inline]
+    pub fn obj(reference: ObjRef) -> Value {
+        Value(SIGN_BIT | QUIET_NAN | reference.0 as u64)
+    }
+
+    /// What the value is.
+    #[inline]
+    pub fn kind(self) -> ValueKind {
+        if let Some(number) = self.as_number() {
+            return ValueKind::Number(number);
+        }
+        if let Some(reference) = self.as_obj() {
+            return ValueKind::Obj(reference);
+        }
+        match self.0 & 0b11 {
+            TAG_NIL => ValueKind::Nil,
+            tag => ValueKind::Bool(tag == TAG_TRUE),
+        }
+    }
+
+    /// The number, if the value is one.
+    #[inline]
+    pub fn as_number(self) -> Option<f64> {
+        if self.0 & QUIET_NAN != QUIET_NAN {
+            return Some(f64::from_bits(self.0));
+        }
+        None
+    }
+
+    /// The object the value refers to, if it refers to one.
+    #[inline]
+    pub fn as_obj(self) -> Option<ObjRef> {
+        if self.0 & (SIGN_BIT | QUIET_NAN) == SIGN_BIT | QUIET_NAN {
+            return Some(ObjRef(self.0 as u32));
+        }
+        None
+    }
+
+    /// Lox's truthiness: `nil` and `false` are falsey, everything
+    /// else (including `0` and `«»`) is truthy.
+    #[inline]
+    pub fn is_truthy(self) -> bool {
+        self != Value::NIL && self != Value::bool(false)
+    }
+}
+
+/// Numbers are equal the way doubles are (so NaN isn't equal to
+/// itself); anything else is only equal to the same bits.
+#[cfg(feature = "nan-boxing")]
+impl PartialEq for Value {
+    #[inline]
+    fn eq(&self, other: &Value) -> bool {
+        match (self.as_number(), other.as_number()) {
+            (Some(left), Some(right)) => left == right,
+            _ => self.0 == other.0,
+        }
+    }
+}
+
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::NIL
+    }
+}
+
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Value::number(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(boolean: bool) -> Self {
+        Value::bool(boolean)
+    }
+}
+
+impl From<ObjRef> for Value {
+    fn from(reference: ObjRef) -> Self {
+        Value::obj(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_keep_what_they_are() {
+        let values = [
+            ValueKind::Nil,
+            ValueKind::Bool(true),
+            ValueKind::Bool(false),
+            ValueKind::Number(0.0),
+            ValueKind::Number(-2.5),
+            ValueKind::Number(f64::INFINITY),
+            ValueKind::Number(f64::MAX),
+            ValueKind::Obj(ObjRef(0)),
+            ValueKind::Obj(ObjRef(u32::MAX)),
+        ];
+        for kind in values {
+            let value = match kind {
+                ValueKind::Nil => Value::NIL,
+                ValueKind::Bool(boolean) => Value::bool(boolean),
+                ValueKind::Number(number) => Value::number(number),
+                ValueKind::Obj(reference) => Value::obj(reference),
+            };
+
+            assert_eq!(value.kind(), kind);
+            assert_eq!(value, value);
+        }
+    }
+
+    #[test]
+    fn test_numbers_compare_like_doubles() {
+        // A NaN with every payload bit set looks like an object
+        // unless it's stored as the usual NaN.
+        let nan = Value::number(f64::from_bits(0x7fff_ffff_ffff_ffff));
+
+        assert!(matches!(nan.kind(), ValueKind::Number(number) if number.is_nan()));
+        assert_ne!(nan, nan);
+        assert_eq!(Value::number(0.0), Value::number(-0.0));
+        assert_ne!(Value::number(1.0), Value::bool(true));
+        assert_ne!(Value::NIL, Value::bool(false));
+        assert!(Value::number(0.0).is_truthy());
+        assert!(!Value::NIL.is_truthy());
+        assert!(!Value::bool(false).is_truthy());
+    }
+}
@@ -0,0 +1,108 @@
+//! Contains the `VmError` struct.
+
+use limits::limits::Limit;
+use token::span::Span;
+
+
+/// Why a `VmError` stopped the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorKind {
+    /// Something went wrong, like subtracting a string.
+    #[default]
+    Error,
+
+    /// The program asked to stop by calling `exit(code)`.
+    Exit(i32),
+
+    /// The program went over one of the VM's `Limits`.
+    LimitExceeded(Limit),
+}
+
+
+/// An error that happens while the VM is running a program.
+///
+/// Keeps the Lox stack trace at the point it happened: the line
+/// each call in progress had got to, innermost first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VmError {
+    pub message: String,
+    pub trace: Vec<TraceLine>,
+    pub kind: ErrorKind,
+}
+
+
+/// One call in a stack trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceLine {
+    pub line: usize,
+    /// The part of the source the call had got to, if the code was
+    /// compiled from source rather than loaded from a file.
+    pub span: Option<Span>,
+    /// The function's name, or empty for the top-level script.
+    pub function: String,
+}
+
+impl VmError {
+    /// An error with no stack trace yet, as from a native
+    /// function.  The VM fills the trace in.
+    pub fn native(message: &str) -> Self {
+        VmError {
+            message: message.to_string(),
+            ..VmError::default()
+        }
+    }
+
+    /// Unwind because the program called `exit(code)`.
+    pub fn exit(code: i32) -> Self {
+        VmError {
+            kind: ErrorKind::Exit(code),
+            ..VmError::native(&format!("Exited with code {}.", code))
+        }
+    }
+
+    /// Stop because the program went over `limit`.
+    pub fn limit(limit: Limit, message: &str) -> Self {
+        VmError {
+            kind: ErrorKind::LimitExceeded(limit),
+            ..VmError::native(message)
+        }
+    }
+
+    /// The line the error happened on, or 0 if it has no trace.
+    pub fn line(&self) -> usize {
+        self.trace.first().map_or(0, |frame| frame.line)
+    }
+
+    /// The part of the source the error happened in, if the VM
+    /// knows it.
+    pub fn span(&self) -> Option<Span> {
+        self.trace.first().and_then(|frame| frame.span)
+    }
+}
+
+/// Shows the error the way clox does, with the trace under it.
+///
+/// ```text
+/// Runtime error: Operands must be numbers.
+/// [line 2] in half()
+/// [line 4] in script
+/// ```
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Runtime error: {}", self.message)?;
+        for frame in &self.trace {
+            write!(f, "\n{}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows one call of a trace, like `[line 2] in half()`.
+impl std::fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.function.as_str() {
+            "" => write!(f, "[line {}] in script", self.line),
+            name => write!(f, "[line {}] in {}()", self.line, name),
+        }
+    }
+}
@@ -0,0 +1,1330 @@
+//! Contains the `Vm` struct.
+
+use std::cell::Cell;
+use std::io::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+use bytecode::constant::Constant;
+use bytecode::function::Function;
+use bytecode::op_code::OpCode;
+use limits::limits::{Limit, Limits};
+
+use crate::call_frame::CallFrame;
+use crate::gc_options::GcOptions;
+use crate::gc_stats::GcStats;
+use crate::heap::Heap;
+use crate::inline_cache::InlineCache;
+use crate::native_function::{NativeFn, NativeFunction};
+use crate::object::{BoundMethod, ObjClass, ObjClosure, ObjFunction, ObjInstance, ObjRef, ObjUpvalue, Object};
+use crate::shape::Shapes;
+use crate::table::Table;
+use crate::value::Value;
+use crate::vm_error::{TraceLine, VmError};
+
+
+/// How many steps to take between looks at the clock, when
+/// there's a time limit.  Reading the clock is slow next to a
+/// step.
+const CLOCK_STEPS: u64 = 256;
+
+
+/// The virtual machine struct.
+///
+/// This runs compiled functions one instruction at a time, with a
+/// stack for the values being worked on and a stack of call frames
+/// for the calls in progress.
+///
+/// Like the tree-walker, the globals outlive a single call to
+/// `interpret`, so later programs can use what earlier ones
+/// declared.
+pub struct Vm {
+    pub heap: Heap,
+
+    /// Values being worked on, and the locals of every call in
+    /// progress.
+    stack: Vec<Value>,
+
+    /// The calls that are waiting on the one that's running.  The
+    /// running one is kept out of the list while the VM runs, so
+    /// it can be got at quickly.
+    frames: Vec<CallFrame>,
+
+    /// Where each global variable is in `global_values`, by name.
+    /// A slot is never taken away or given to another name, so the
+    /// instructions that use globals can remember theirs.
+    globals: Table<u32>,
+
+    /// The value of each global variable, by slot.
+    global_values: Vec<Value>,
+
+    /// The upvalues still pointing at stack slots, with their
+    /// slots, in order of the slots.  Capturing a variable that's
+    /// already been captured finds its upvalue here.
+    open_upvalues: Vec<(usize, ObjRef)>,
+
+    /// The string `init`, to find initializers by.
+    init_string: ObjRef,
+
+    /// Where each layout of instance fields keeps each field.
+    shapes: Shapes,
+
+    /// Moves on whenever a class is made or its methods change,
+    /// and that class takes the new value as its epoch.  An inline
+    /// cache remembers the epoch of the class it found a method in,
+    /// to tell whether a lookup would still find the same one.
+    /// Since the values are never reused, neither is an epoch, even
+    /// when a freed class's reference is.
+    method_epoch: u64,
+
+    /// How much a run is allowed to do.
+    limits: Limits,
+
+    /// How many steps the current run has taken.
+    steps: u64,
+
+    /// How many steps the run can take before the limits need
+    /// checking again, so that a step is usually one comparison.
+    next_check: u64,
+
+    /// When the current run has to stop by, if there's a timeout.
+    deadline: Option<Instant>,
+
+    /// Where `print` writes to.
+    output: Box<dyn Write>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        //! Create a new `Vm` that prints to stdout.
+        Vm::with_output(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        //! Create a new `Vm` that prints to `output`.
+        //!
+        //! The core library's native functions are already defined.
+        let mut heap = Heap::new();
+        let init_string = heap.intern("init");
+        let mut vm = Vm {
+            heap,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: Table::default(),
+            global_values: Vec::new(),
+            open_upvalues: Vec::new(),
+            init_string,
+            shapes: Shapes::new(),
+            method_epoch: 0,
+            limits: Limits::default(),
+            steps: 0,
+            next_check: u64::MAX,
+            deadline: None,
+            output,
+        };
+        crate::core_library::register(&mut vm);
+        vm
+    }
+
+    /// Define (or redefine) a native function as a global.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction { name: name.to_string(), arity, function };
+        let native = self.heap.allocate(Object::Native(native));
+        let name = self.heap.intern(name);
+        self.define_global(name, Value::obj(native));
+    }
+
+    /// Define (or redefine) the global variable `name`.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        let name = self.heap.intern(name);
+        self.define_global(name, value);
+    }
+
+    /// The value of the global variable `name`, if there is one.
+    pub fn get_global(&mut self, name: &str) -> Option<Value> {
+        let name = self.heap.intern(name);
+        let slot = *self.globals.get(&name)?;
+        Some(self.global_values[slot as usize])
+    }
+
+    /// Change how the garbage collector behaves.
+    pub fn set_gc_options(&mut self, options: GcOptions) {
+        self.heap.set_gc_options(options);
+    }
+
+    /// How much time the garbage collector has taken so far.
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.gc_stats()
+    }
+
+    /// Change how much a run is allowed to do.  This takes effect
+    /// from the next run.
+    ///
+    /// The VM keeps to the same limits as the interpreter, except
+    /// `max_stack_bytes`, since it doesn't recurse on the Rust stack.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// How much a run is allowed to do.
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Where `print` writes to, for native functions that want to
+    /// write to the same place.
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    pub fn interpret(&mut self, function: &Function) -> Result<(), VmError> {
+        //! Run a compiled script.
+        //!
+        //! This is the main method and purpose of the VM.  It stops
+        //! at the first runtime error.
+        self.reset_budget();
+        let function = self.load_function(function);
+        let script = self.new_closure(function, Vec::new());
+        let Object::Closure(closure) = self.heap.get(script) else {
+            unreachable!("a closure was just made");
+        };
+        let frame = CallFrame { closure: script, function: Rc::clone(&closure.function), ip: 0, slots: 0 };
+        self.stack.push(Value::obj(script));
+        self.run(frame)
+    }
+
+    /// Put a compiled function (and every function inside it) on
+    /// the heap, turning its constants into values.
+    pub fn load_function(&mut self, function: &Function) -> ObjRef {
+        let constants = function
+            .chunk
+            .constants()
+            .iter()
+            .map(|constant| match constant {
+                Constant::Number(number) => Value::number(*number),
+                Constant::String(string) => Value::obj(self.heap.intern(string)),
+                Constant::Function(function) => Value::obj(self.load_function(function)),
+            })
+            .collect();
+        let function = ObjFunction {
+            name: function.name.clone(),
+            arity: function.arity,
+            upvalue_count: function.upvalue_count,
+            chunk: function.chunk.clone(),
+            constants,
+            caches: (0..function.chunk.code.len()).map(|_| Cell::new(InlineCache::Empty)).collect(),
+        };
+        self.heap.allocate(Object::Function(Rc::new(function)))
+    }
+
+    /// Wrap the loaded function `function` in a closure over
+    /// `upvalues`.
+    fn new_closure(&mut self, function: ObjRef, upvalues: Vec<ObjRef>) -> ObjRef {
+        let Object::Function(function_rc) = self.heap.get(function) else {
+            unreachable!("closures are only made of functions");
+        };
+        let closure = ObjClosure { function_object: function, function: Rc::clone(function_rc), upvalues };
+        self.heap.allocate(Object::Closure(closure))
+    }
+
+    /// Start the step, allocation and time budgets over for a new
+    /// run, and give the heap its limits.
+    fn reset_budget(&mut self) {
+        self.steps = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.next_check = self.next_check();
+        self.heap.set_memory_limits(self.limits.max_heap_bytes, self.limits.max_allocated_bytes);
+        self.heap.reset_total();
+    }
+
+    /// Count one step against the step limit, and every so often
+    /// check the clock against the deadline.
+    #[inline]
+    fn step(&mut self) -> Result<(), VmError> {
+        self.steps += 1;
+        if self.steps >= self.next_check {
+            return self.check_budget();
+        }
+        Ok(())
+    }
+
+    #[cold]
+    fn check_budget(&mut self) -> Result<(), VmError> {
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(VmError::limit(Limit::Steps, &format!("Step limit of {} exceeded.", max_steps)));
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                let timeout = self.limits.timeout.unwrap_or_default();
+                return Err(VmError::limit(Limit::Time, &format!("Time limit of {:?} exceeded.", timeout)));
+            }
+        }
+        self.next_check = self.next_check();
+        Ok(())
+    }
+
+    /// The step after which the limits need checking again: the
+    /// first one over the step limit, or sooner if it's time to
+    /// look at the clock.
+    fn next_check(&self) -> u64 {
+        let over_max_steps = self.limits.max_steps.map_or(u64::MAX, |max_steps| max_steps.saturating_add(1));
+        match self.deadline {
+            Some(_) => over_max_steps.min(self.steps + CLOCK_STEPS),
+            None => over_max_steps,
+        }
+    }
+
+    /// Run `frame` and everything it calls until it returns.
+    ///
+    /// If there's an error, the stack trace is added and the VM is
+    /// left ready to run something else.
+    fn run(&mut self, mut frame: CallFrame) -> Result<(), VmError> {
+        let result = self.execute(&mut frame);
+        if let Err(mut error) = result {
+            error.trace = std::iter::once(&frame)
+                .chain(self.frames.iter().rev())
+                .map(|frame| TraceLine { line: frame.line(), span: frame.span(), function: frame.function.name.clone() })
+                .collect();
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// The instruction loop.
+    ///
+    /// The running function's code and constants are kept to hand
+    /// until a call or a return switches to another frame.
+    fn execute(&mut self, frame: &mut CallFrame) -> Result<(), VmError> {
+        // the high bytes of the next constant index, from an
+        // `OP_WIDE` just before it
+        let mut wide = 0;
+        'frame: loop {
+            let code = &frame.function.chunk.code[..];
+            let constants = &frame.function.constants[..];
+            let caches = &frame.function.caches[..];
+            loop {
+                let byte = read_byte(code, &mut frame.ip);
+                let Ok(op_code) = OpCode::try_from(byte) else {
+                    return Err(VmError::native(&format!("Unknown opcode {}.", byte)));
+                };
+
+                match op_code {
+                    OpCode::Constant => {
+                        let constant = constants[read_byte(code, &mut frame.ip) as usize];
+                        self.push(constant);
+                    }
+                    OpCode::ConstantLong => {
+                        let index = frame.function.chunk.read_u24(frame.ip);
+                        frame.ip += 3;
+                        self.push(constants[index]);
+                    }
+                    OpCode::Wide => {
+                        wide = read_u16(code, &mut frame.ip) << 8;
+                    }
+                    OpCode::Nil => self.push(Value::NIL),
+                    OpCode::True => self.push(Value::bool(true)),
+                    OpCode::False => self.push(Value::bool(false)),
+                    OpCode::Pop => {
+                        self.pop();
+                    }
+                    OpCode::PopN => {
+                        let count = read_byte(code, &mut frame.ip) as usize;
+                        self.stack.truncate(self.stack.len() - count);
+                    }
+                    OpCode::GetLocal => {
+                        let slot = read_byte(code, &mut frame.ip) as usize;
+                        self.push(self.stack[frame.slots + slot]);
+                    }
+                    OpCode::SetLocal => {
+                        let slot = read_byte(code, &mut frame.ip) as usize;
+                        self.stack[frame.slots + slot] = self.peek(0);
+                    }
+                    OpCode::GetGlobal => {
+                        let cache = &caches[frame.ip - 1];
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let slot = self.global_slot(name, cache)?;
+                        self.push(self.global_values[slot]);
+                    }
+                    OpCode::DefineGlobal => {
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let value = self.pop();
+                        self.define_global(name, value);
+                    }
+                    OpCode::SetGlobal => {
+                        let cache = &caches[frame.ip - 1];
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let slot = self.global_slot(name, cache)?;
+                        self.global_values[slot] = self.peek(0);
+                    }
+                    OpCode::Equal => {
+                        let right = self.pop();
+                        let left = self.pop();
+                        self.push(Value::bool(left == right));
+                    }
+                    OpCode::Greater => self.compare(|left, right| left > right)?,
+                    OpCode::GreaterEqual => self.compare(|left, right| left >= right)?,
+                    OpCode::Less => self.compare(|left, right| left < right)?,
+                    OpCode::LessEqual => self.compare(|left, right| left <= right)?,
+                    OpCode::Add => {
+                        self.add()?;
+                        self.collect_if_needed(frame)?;
+                    }
+                    OpCode::Subtract => self.arithmetic(|left, right| left - right)?,
+                    OpCode::Multiply => self.arithmetic(|left, right| left * right)?,
+                    OpCode::Divide => self.arithmetic(|left, right| left / right)?,
+                    OpCode::Not => {
+                        let value = self.pop();
+                        self.push(Value::bool(!value.is_truthy()));
+                    }
+                    OpCode::Negate => match self.peek(0).as_number() {
+                        Some(number) => {
+                            self.pop();
+                            self.push(Value::number(-number));
+                        }
+                        _ => return Err(VmError::native("Operand must be a number.")),
+                    },
+                    OpCode::Print => {
+                        let value = self.pop();
+                        let text = self.heap.format(value);
+                        if let Err(error) = writeln!(self.output, "{}", text) {
+                            return Err(VmError::native(&format!("Couldn't print: {}.", error)));
+                        }
+                    }
+                    OpCode::Jump => {
+                        let offset = read_u16(code, &mut frame.ip);
+                        frame.ip += offset;
+                    }
+                    OpCode::JumpIfFalse => {
+                        let offset = read_u16(code, &mut frame.ip);
+                        if !self.peek(0).is_truthy() {
+                            frame.ip += offset;
+                        }
+                    }
+                    OpCode::Loop => {
+                        let offset = read_u16(code, &mut frame.ip);
+                        frame.ip -= offset;
+                        self.step()?;
+                    }
+                    OpCode::Call => {
+                        let argument_count = read_byte(code, &mut frame.ip) as usize;
+                        let callee = self.peek(argument_count);
+                        // Calling a Lox function is by far the most
+                        // common call, so it doesn't go through the
+                        // general `call_value`.
+                        if let Some(reference) = callee.as_obj() {
+                            if let Object::Closure(closure) = self.heap.get(reference) {
+                                let function = Rc::clone(&closure.function);
+                                self.call_function(frame, reference, function, argument_count)?;
+                                continue 'frame;
+                            }
+                        }
+                        self.call_value(frame, callee, argument_count)?;
+                        self.collect_if_needed(frame)?;
+                        continue 'frame;
+                    }
+                    OpCode::Return => {
+                        let result = self.pop();
+                        self.close_upvalues(frame.slots);
+                        self.stack.truncate(frame.slots);
+                        match self.frames.pop() {
+                            Some(caller) => {
+                                *frame = caller;
+                                self.push(result);
+                                continue 'frame;
+                            }
+                            None => return Ok(()),
+                        }
+                    }
+                    OpCode::Class => {
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let epoch = self.next_method_epoch();
+                        let class = self.heap.allocate(Object::Class(ObjClass { name, methods: Table::default(), epoch }));
+                        self.push(Value::obj(class));
+                        self.collect_if_needed(frame)?;
+                    }
+                    OpCode::GetProperty => {
+                        let cache = &caches[frame.ip - 1];
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let instance = self.peek_instance(0, "Only instances have properties.")?;
+                        match self.find_property(instance, name, cache)? {
+                            Property::Field(value) => {
+                                self.pop();
+                                self.push(value);
+                            }
+                            Property::Method(method) => {
+                                self.bind(method);
+                                self.collect_if_needed(frame)?;
+                            }
+                        }
+                    }
+                    OpCode::SetProperty => {
+                        let cache = &caches[frame.ip - 1];
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let instance = self.peek_instance(1, "Only instances have fields.")?;
+                        let value = self.pop();
+                        self.set_field(instance, name, value, cache);
+                        self.heap.write_barrier(Value::obj(name));
+                        self.heap.write_barrier(value);
+                        self.pop();
+                        self.push(value);
+                    }
+                    OpCode::Method => {
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let method = self.pop();
+                        let epoch = self.next_method_epoch();
+                        if let Some(class) = self.peek(0).as_obj() {
+                            if let Object::Class(class) = self.heap.get_mut(class) {
+                                class.methods.insert(name, method);
+                                class.epoch = epoch;
+                            }
+                        }
+                        self.heap.write_barrier(Value::obj(name));
+                        self.heap.write_barrier(method);
+                    }
+                    OpCode::Invoke => {
+                        let cache = &caches[frame.ip - 1];
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let argument_count = read_byte(code, &mut frame.ip) as usize;
+                        let instance = self.peek_instance(argument_count, "Only instances have properties.")?;
+                        match self.find_property(instance, name, cache)? {
+                            // A field with the name wins, as when
+                            // reading the property, and is called
+                            // like any other value.
+                            Property::Field(field) => {
+                                let slot = self.stack.len() - argument_count - 1;
+                                self.stack[slot] = field;
+                                self.call_value(frame, field, argument_count)?;
+                                self.collect_if_needed(frame)?;
+                            }
+                            // Calling a method makes nothing, so
+                            // there's nothing to collect.
+                            Property::Method(method) => self.call_method(frame, method, argument_count)?,
+                        }
+                        continue 'frame;
+                    }
+                    OpCode::Closure => {
+                        let index = std::mem::take(&mut wide) | read_byte(code, &mut frame.ip) as usize;
+                        let Some(function) = constants[index].as_obj() else {
+                            unreachable!("closures are always made of functions");
+                        };
+                        let upvalue_count = match self.heap.get(function) {
+                            Object::Function(function) => function.upvalue_count,
+                            _ => unreachable!("closures are always made of functions"),
+                        };
+                        let mut upvalues = Vec::with_capacity(upvalue_count);
+                        for _ in 0..upvalue_count {
+                            let is_local = read_byte(code, &mut frame.ip) == 1;
+                            let index = read_byte(code, &mut frame.ip) as usize;
+                            let upvalue = if is_local {
+                                self.capture_upvalue(frame.slots + index)
+                            } else {
+                                self.upvalue(frame.closure, index)
+                            };
+                            upvalues.push(upvalue);
+                        }
+                        let closure = self.new_closure(function, upvalues);
+                        self.push(Value::obj(closure));
+                        self.collect_if_needed(frame)?;
+                    }
+                    OpCode::GetUpvalue => {
+                        let index = read_byte(code, &mut frame.ip) as usize;
+                        let value = match self.heap.get(self.upvalue(frame.closure, index)) {
+                            Object::Upvalue(ObjUpvalue::Open(slot)) => self.stack.get(*slot).copied().unwrap_or_default(),
+                            Object::Upvalue(ObjUpvalue::Closed(value)) => *value,
+                            _ => unreachable!("a closure's upvalues are always upvalues"),
+                        };
+                        self.push(value);
+                    }
+                    OpCode::SetUpvalue => {
+                        let index = read_byte(code, &mut frame.ip) as usize;
+                        let value = self.peek(0);
+                        let upvalue = self.upvalue(frame.closure, index);
+                        match self.heap.get_mut(upvalue) {
+                            Object::Upvalue(ObjUpvalue::Open(slot)) => {
+                                if let Some(slot) = self.stack.get_mut(*slot) {
+                                    *slot = value;
+                                }
+                            }
+                            Object::Upvalue(closed) => {
+                                *closed = ObjUpvalue::Closed(value);
+                                self.heap.write_barrier(value);
+                            }
+                            _ => unreachable!("a closure's upvalues are always upvalues"),
+                        }
+                    }
+                    OpCode::CloseUpvalue => {
+                        self.close_upvalues(self.stack.len() - 1);
+                        self.pop();
+                    }
+                    OpCode::GetSuper => {
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let Some(superclass) = self.pop().as_obj() else {
+                            return Err(VmError::native("Superclass must be a class."));
+                        };
+                        self.bind_method(superclass, name)?;
+                        self.collect_if_needed(frame)?;
+                    }
+                    OpCode::SuperInvoke => {
+                        let name = read_string(code, constants, &mut wide, &mut frame.ip);
+                        let argument_count = read_byte(code, &mut frame.ip) as usize;
+                        let Some(superclass) = self.pop().as_obj() else {
+                            return Err(VmError::native("Superclass must be a class."));
+                        };
+                        let Some(method) = self.find_method(superclass, name) else {
+                            return Err(self.undefined_property(name));
+                        };
+                        self.call_method(frame, method, argument_count)?;
+                        continue 'frame;
+                    }
+                    OpCode::Inherit => {
+                        let superclass = match self.peek(1).as_obj() {
+                            Some(superclass) => match self.heap.get(superclass) {
+                                Object::Class(superclass) => superclass.methods.clone(),
+                                _ => return Err(VmError::native("Superclass must be a class.")),
+                            },
+                            _ => return Err(VmError::native("Superclass must be a class.")),
+                        };
+                        for (name, method) in &superclass {
+                            self.heap.write_barrier(Value::obj(*name));
+                            self.heap.write_barrier(*method);
+                        }
+                        let epoch = self.next_method_epoch();
+                        if let Some(subclass) = self.peek(0).as_obj() {
+                            if let Object::Class(subclass) = self.heap.get_mut(subclass) {
+                                subclass.methods.extend(superclass);
+                                subclass.epoch = epoch;
+                            }
+                        }
+                        self.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    // ---- globals ----
+
+    /// Give the global variable `name` the value `value`, making
+    /// it a slot if it hasn't got one.
+    fn define_global(&mut self, name: ObjRef, value: Value) {
+        match self.globals.get(&name) {
+            Some(&slot) => self.global_values[slot as usize] = value,
+            None => {
+                self.globals.insert(name, self.global_values.len() as u32);
+                self.global_values.push(value);
+            }
+        }
+    }
+
+    /// The slot of the global variable `name`, for an instruction
+    /// that remembers it in `cache`.
+    #[inline(always)]
+    fn global_slot(&self, name: ObjRef, cache: &Cell<InlineCache>) -> Result<usize, VmError> {
+        if let InlineCache::Global { slot } = cache.get() {
+            return Ok(slot as usize);
+        }
+        self.find_global_slot(name, cache)
+    }
+
+    /// Look up the slot of the global variable `name`, and
+    /// remember it in `cache`.
+    #[cold]
+    fn find_global_slot(&self, name: ObjRef, cache: &Cell<InlineCache>) -> Result<usize, VmError> {
+        match self.globals.get(&name) {
+            Some(&slot) => {
+                cache.set(InlineCache::Global { slot });
+                Ok(slot as usize)
+            }
+            None => Err(self.undefined_variable(name)),
+        }
+    }
+
+    // ---- calls ----
+
+    /// Call `callee`, which is on the stack below its arguments.
+    ///
+    /// A Lox function gets a new frame, which becomes `frame`;
+    /// anything else runs straight away and leaves its result on
+    /// the stack in place of the callee and the arguments.
+    fn call_value(&mut self, frame: &mut CallFrame, callee: Value, argument_count: usize) -> Result<(), VmError> {
+        let Some(callee) = callee.as_obj() else {
+            return Err(VmError::native("Can only call functions and classes."));
+        };
+        match self.heap.get(callee) {
+            Object::Closure(closure) => {
+                let function = Rc::clone(&closure.function);
+                self.call_function(frame, callee, function, argument_count)
+            }
+            Object::Native(native) => {
+                let (arity, function) = (native.arity, native.function);
+                check_arity(arity, argument_count)?;
+                let start = self.stack.len() - argument_count;
+                let arguments: Vec<Value> = self.stack[start..].to_vec();
+                let result = function(self, &arguments)?;
+                self.stack.truncate(start - 1);
+                self.push(result);
+                Ok(())
+            }
+            Object::Class(class) => {
+                let initializer = class.methods.get(&self.init_string).copied();
+                let instance = ObjInstance { class: callee, shape: Shapes::EMPTY, fields: Vec::new() };
+                let instance = self.heap.allocate(Object::Instance(instance));
+                let receiver = self.stack.len() - argument_count - 1;
+                self.stack[receiver] = Value::obj(instance);
+                match initializer.and_then(Value::as_obj) {
+                    Some(initializer) => self.call_method(frame, initializer, argument_count),
+                    _ => check_arity(0, argument_count),
+                }
+            }
+            Object::BoundMethod(bound) => {
+                let (receiver, method) = (bound.receiver, bound.method);
+                let slot = self.stack.len() - argument_count - 1;
+                self.stack[slot] = receiver;
+                self.call_method(frame, method, argument_count)
+            }
+            _ => Err(VmError::native("Can only call functions and classes.")),
+        }
+    }
+
+    /// Call the method `method`, whose instance is already on the
+    /// stack below the arguments.
+    fn call_method(&mut self, frame: &mut CallFrame, method: ObjRef, argument_count: usize) -> Result<(), VmError> {
+        // The compiler only makes closures into methods, but a
+        // loaded file could make anything one.
+        let Object::Closure(closure) = self.heap.get(method) else {
+            return Err(VmError::native("Can only call functions and classes."));
+        };
+        let function = Rc::clone(&closure.function);
+        self.call_function(frame, method, function, argument_count)
+    }
+
+    /// Start running the closure `closure`, whose function is
+    /// `function`, in a new frame.
+    #[inline(always)]
+    fn call_function(
+        &mut self,
+        frame: &mut CallFrame,
+        closure: ObjRef,
+        function: Rc<ObjFunction>,
+        argument_count: usize,
+    ) -> Result<(), VmError> {
+        check_arity(function.arity, argument_count)?;
+        self.step()?;
+        // the running frame is the script's or a call's, and the
+        // script's is at the bottom, so this is how many calls are
+        // in progress
+        if let Some(max_call_depth) = self.limits.max_call_depth {
+            if self.frames.len() >= max_call_depth {
+                return Err(VmError::limit(
+                    Limit::CallDepth,
+                    &format!("Call depth limit of {} exceeded.", max_call_depth),
+                ));
+            }
+        }
+        let slots = self.stack.len() - argument_count - 1;
+        let callee = CallFrame { closure, function, ip: 0, slots };
+        self.frames.push(std::mem::replace(frame, callee));
+        Ok(())
+    }
+
+    /// Replace the instance on top of the stack with its method
+    /// `name`, bound to it.
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), VmError> {
+        let Some(method) = self.find_method(class, name) else {
+            return Err(self.undefined_property(name));
+        };
+        self.bind(method);
+        Ok(())
+    }
+
+    /// Replace the instance on top of the stack with `method`, bound
+    /// to it.
+    fn bind(&mut self, method: ObjRef) {
+        let receiver = self.peek(0);
+        let bound = self.heap.allocate(Object::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::obj(bound));
+    }
+
+    /// A new method epoch, for a class that's just been made or
+    /// whose methods have just changed.
+    fn next_method_epoch(&mut self) -> u64 {
+        self.method_epoch = self.method_epoch.wrapping_add(1);
+        self.method_epoch
+    }
+
+    fn class_epoch(&self, class: ObjRef) -> u64 {
+        match self.heap.get(class) {
+            Object::Class(class) => class.epoch,
+            _ => unreachable!("instances always have a class"),
+        }
+    }
+
+    fn find_method(&self, class: ObjRef, name: ObjRef) -> Option<ObjRef> {
+        match self.heap.get(class) {
+            Object::Class(class) => class.methods.get(&name).and_then(|method| method.as_obj()),
+            _ => None,
+        }
+    }
+
+    // ---- properties ----
+
+    /// The property `name` of `instance`: its field if it has one,
+    /// and otherwise its class's method.
+    ///
+    /// `cache` is the inline cache of the instruction asking, which
+    /// is tried first and updated after a lookup.
+    #[inline(always)]
+    fn find_property(&self, instance: ObjRef, name: ObjRef, cache: &Cell<InlineCache>) -> Result<Property, VmError> {
+        let Object::Instance(found) = self.heap.get(instance) else {
+            unreachable!("peek_instance only gives back instances");
+        };
+        match cache.get() {
+            InlineCache::Field { shape, slot } if shape == found.shape => Ok(Property::Field(found.fields[slot as usize])),
+            InlineCache::Method { shape, class, method, epoch }
+                if shape == found.shape && class == found.class && epoch == self.class_epoch(class) =>
+            {
+                Ok(Property::Method(method))
+            }
+            _ => self.look_up_property(instance, name, cache),
+        }
+    }
+
+    /// The property `name` of `instance`, when `cache` didn't have
+    /// it, which is remembered in `cache` for next time.
+    #[cold]
+    fn look_up_property(&self, instance: ObjRef, name: ObjRef, cache: &Cell<InlineCache>) -> Result<Property, VmError> {
+        let Object::Instance(instance) = self.heap.get(instance) else {
+            unreachable!("peek_instance only gives back instances");
+        };
+        if let Some(slot) = self.shapes.slot(instance.shape, name) {
+            cache.set(InlineCache::Field { shape: instance.shape, slot: slot as u32 });
+            return Ok(Property::Field(instance.fields[slot]));
+        }
+        let Some(method) = self.find_method(instance.class, name) else {
+            return Err(self.undefined_property(name));
+        };
+        let epoch = self.class_epoch(instance.class);
+        cache.set(InlineCache::Method { shape: instance.shape, class: instance.class, method, epoch });
+        Ok(Property::Method(method))
+    }
+
+    /// Set the field `name` of `instance` to `value`, adding the
+    /// field if it doesn't have it yet.
+    ///
+    /// `cache` is the inline cache of the instruction setting it.
+    fn set_field(&mut self, instance: ObjRef, name: ObjRef, value: Value, cache: &Cell<InlineCache>) {
+        let Object::Instance(ObjInstance { shape, .. }) = self.heap.get(instance) else {
+            unreachable!("peek_instance only gives back instances");
+        };
+        let shape = *shape;
+        let found = match cache.get() {
+            found @ (InlineCache::Field { shape: cached, .. } | InlineCache::AddField { shape: cached, .. })
+                if cached == shape =>
+            {
+                found
+            }
+            _ => {
+                let found = match self.shapes.slot(shape, name) {
+                    Some(slot) => InlineCache::Field { shape, slot: slot as u32 },
+                    None => InlineCache::AddField { shape, next: self.shapes.add_field(shape, name) },
+                };
+                cache.set(found);
+                found
+            }
+        };
+        let Object::Instance(instance) = self.heap.get_mut(instance) else {
+            unreachable!("peek_instance only gives back instances");
+        };
+        match found {
+            InlineCache::Field { slot, .. } => instance.fields[slot as usize] = value,
+            InlineCache::AddField { next, .. } => {
+                instance.fields.push(value);
+                instance.shape = next;
+            }
+            _ => unreachable!("only field caches are used for setting"),
+        }
+    }
+
+    // ---- upvalues ----
+
+    /// The `index`th upvalue of the closure `closure`.
+    fn upvalue(&self, closure: ObjRef, index: usize) -> ObjRef {
+        match self.heap.get(closure) {
+            Object::Closure(closure) => closure.upvalues[index],
+            _ => unreachable!("frames only ever run closures"),
+        }
+    }
+
+    /// The open upvalue for the local in stack slot `slot`, made if
+    /// nothing has captured it yet.
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        match self.open_upvalues.binary_search_by_key(&slot, |(open, _)| *open) {
+            Ok(found) => self.open_upvalues[found].1,
+            Err(position) => {
+                let upvalue = self.heap.allocate(Object::Upvalue(ObjUpvalue::Open(slot)));
+                self.open_upvalues.insert(position, (slot, upvalue));
+                upvalue
+            }
+        }
+    }
+
+    /// Close every upvalue for a stack slot at or above `last`,
+    /// moving the value out of the slot and into the upvalue.
+    ///
+    /// The compiler always closes a captured local before popping
+    /// it, but a loaded file's code might not, which leaves an open
+    /// upvalue past the top of the stack.  Reading one gives `nil`
+    /// (here and in `GetUpvalue`) rather than a panic.
+    #[inline(always)]
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(&(slot, upvalue)) = self.open_upvalues.last() {
+            if slot < last {
+                break;
+            }
+            let value = self.stack.get(slot).copied().unwrap_or_default();
+            *self.heap.get_mut(upvalue) = Object::Upvalue(ObjUpvalue::Closed(value));
+            self.heap.write_barrier(value);
+            self.open_upvalues.pop();
+        }
+    }
+
+    // ---- garbage collection ----
+
+    /// Collect garbage if enough has been allocated since last
+    /// time, and stop the program if it's over the memory limits.
+    ///
+    /// Only the instructions that allocate call this, once they're
+    /// done: by then every live value is on the stack or reachable
+    /// from a root.  (Calling a Lox function or a method doesn't
+    /// allocate, but calling a class or a native function can.)
+    #[inline]
+    fn collect_if_needed(&mut self, running: &CallFrame) -> Result<(), VmError> {
+        if self.heap.should_collect() {
+            return self.collect_garbage(running);
+        }
+        Ok(())
+    }
+
+    /// Free everything the roots can't reach (or take the next
+    /// step towards it), then check the memory limits.
+    fn collect_garbage(&mut self, running: &CallFrame) -> Result<(), VmError> {
+        let start = Instant::now();
+        let started_now = !self.heap.is_collecting();
+        self.collect_step(running);
+        if self.heap.over_max_bytes() {
+            // Only a collection that starts now can free all the
+            // garbage there is, so finish the one in progress and,
+            // if it started earlier, do another.
+            self.finish_collection(running);
+            if !started_now && self.heap.over_max_bytes() {
+                self.collect_step(running);
+                self.finish_collection(running);
+            }
+        }
+        self.heap.record_pause(start.elapsed());
+
+        if self.heap.over_max_bytes() {
+            let max_heap_bytes = self.limits.max_heap_bytes.unwrap_or_default();
+            return Err(VmError::limit(Limit::Heap, &format!("Heap limit of {} bytes exceeded.", max_heap_bytes)));
+        }
+        if self.heap.over_max_total() {
+            let max_allocated_bytes = self.limits.max_allocated_bytes.unwrap_or_default();
+            return Err(VmError::limit(
+                Limit::Allocation,
+                &format!("Allocation limit of {} bytes exceeded.", max_allocated_bytes),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Mark the roots if the heap needs them, then take the next
+    /// step of collecting.
+    fn collect_step(&mut self, running: &CallFrame) {
+        if self.heap.needs_roots() {
+            self.mark_roots(running);
+        }
+        self.heap.collect_step();
+    }
+
+    /// Take steps until the collection in progress is done.
+    fn finish_collection(&mut self, running: &CallFrame) {
+        while self.heap.is_collecting() {
+            self.collect_step(running);
+        }
+    }
+
+    /// Mark everything the program can get at directly: the stack,
+    /// the closures of the calls in progress (`running` and the
+    /// ones waiting on it), the globals, the open upvalues, the
+    /// `init` string and the field names in the shapes.
+    fn mark_roots(&mut self, running: &CallFrame) {
+        for value in &self.stack {
+            self.heap.mark_value(*value);
+        }
+        for frame in std::iter::once(running).chain(&self.frames) {
+            self.heap.mark_object(frame.closure);
+        }
+        for name in self.globals.keys() {
+            self.heap.mark_object(*name);
+        }
+        for value in &self.global_values {
+            self.heap.mark_value(*value);
+        }
+        for (_, upvalue) in &self.open_upvalues {
+            self.heap.mark_object(*upvalue);
+        }
+        self.heap.mark_object(self.init_string);
+        for name in self.shapes.names() {
+            self.heap.mark_object(name);
+        }
+    }
+
+    // ---- operators ----
+
+    /// `+` adds numbers or concatenates strings.
+    fn add(&mut self) -> Result<(), VmError> {
+        match (self.peek(1).as_number(), self.peek(0).as_number()) {
+            (Some(left), Some(right)) => {
+                self.pop();
+                self.pop();
+                self.push(Value::number(left + right));
+            }
+            _ => {
+                let (Some(left), Some(right)) = (self.heap.as_string(self.peek(1)), self.heap.as_string(self.peek(0))) else {
+                    return Err(VmError::native("Operands must be two numbers or two strings."));
+                };
+                let concatenated = format!("{}{}", left, right);
+                let string = self.heap.intern_owned(concatenated);
+                self.pop();
+                self.pop();
+                self.push(Value::obj(string));
+            }
+        }
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, operator: impl Fn(f64, f64) -> f64) -> Result<(), VmError> {
+        self.binary(|left, right| Value::number(operator(left, right)))
+    }
+
+    fn compare(&mut self, operator: impl Fn(f64, f64) -> bool) -> Result<(), VmError> {
+        self.binary(|left, right| Value::bool(operator(left, right)))
+    }
+
+    /// Replace the two numbers on top of the stack with what
+    /// `operator` makes of them.
+    fn binary(&mut self, operator: impl Fn(f64, f64) -> Value) -> Result<(), VmError> {
+        let top = self.stack.len() - 1;
+        match (self.stack[top - 1].as_number(), self.stack[top].as_number()) {
+            (Some(left), Some(right)) => {
+                self.stack[top - 1] = operator(left, right);
+                self.stack.truncate(top);
+                Ok(())
+            }
+            _ => Err(VmError::native("Operands must be numbers.")),
+        }
+    }
+
+    // ---- the stack ----
+
+    #[inline(always)]
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    #[inline(always)]
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the compiler keeps the stack balanced")
+    }
+
+    /// The value `distance` down from the top of the stack.
+    #[inline(always)]
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    /// The instance `distance` down from the top of the stack, or
+    /// `message` as an error if it isn't one.
+    fn peek_instance(&self, distance: usize, message: &str) -> Result<ObjRef, VmError> {
+        match self.peek(distance).as_obj() {
+            Some(reference) if matches!(self.heap.get(reference), Object::Instance(_)) => Ok(reference),
+            _ => Err(VmError::native(message)),
+        }
+    }
+
+    fn undefined_variable(&self, name: ObjRef) -> VmError {
+        VmError::native(&format!("Undefined variable '{}'.", self.heap.string(name)))
+    }
+
+    fn undefined_property(&self, name: ObjRef) -> VmError {
+        VmError::native(&format!("Undefined property '{}'.", self.heap.string(name)))
+    }
+}
+
+
+/// What a property of an instance turned out to be.
+enum Property {
+    Field(Value),
+    Method(ObjRef),
+}
+
+
+/// Make sure a call has as many arguments as the callee takes.
+///
+/// Every call checks, so the error is made out of line to keep
+/// the check itself small.
+#[inline]
+fn check_arity(arity: usize, count: usize) -> Result<(), VmError> {
+    if count != arity {
+        return Err(arity_error(arity, count));
+    }
+    Ok(())
+}
+
+#[cold]
+fn arity_error(arity: usize, count: usize) -> VmError {
+    VmError::native(&format!("Expected {} arguments but got {}.", arity, count))
+}
+
+
+fn read_byte(code: &[u8], ip: &mut usize) -> u8 {
+    let byte = code[*ip];
+    *ip += 1;
+    byte
+}
+
+fn read_u16(code: &[u8], ip: &mut usize) -> usize {
+    let value = u16::from_be_bytes([code[*ip], code[*ip + 1]]);
+    *ip += 2;
+    value as usize
+}
+
+/// Read a constant the compiler only ever makes a string, like
+/// the name of a global, and use up the high bytes of its index
+/// in `wide`.
+fn read_string(code: &[u8], constants: &[Value], wide: &mut usize, ip: &mut usize) -> ObjRef {
+    let index = std::mem::take(wide) | read_byte(code, ip) as usize;
+    match constants[index].as_obj() {
+        Some(string) => string,
+        _ => unreachable!("names are always strings"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gc_options::GcMode;
+    use std::cell::RefCell;
+
+    /// Somewhere to print to that the test can read back.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(source: &str) -> Result<String, VmError> {
+        run_with_gc(source, GcOptions::default())
+    }
+
+    fn run_with_gc(source: &str, options: GcOptions) -> Result<String, VmError> {
+        let function = compiler::compiler_struct::compile(source).unwrap();
+        let output = SharedOutput::default();
+        let mut vm = Vm::with_output(Box::new(output.clone()));
+        vm.set_gc_options(options);
+        vm.interpret(&function)?;
+        let printed = output.0.borrow().clone();
+        Ok(String::from_utf8(printed).unwrap())
+    }
+
+    #[test]
+    fn test_runs_functions_and_loops() {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); }
+                      for (var i ← 0; i < 8; i ← i + 1) print fib(i);";
+
+        assert_eq!(run(source).unwrap(), "0\n1\n1\n2\n3\n5\n8\n13\n");
+    }
+
+    #[test]
+    fn test_runs_classes() {
+        let source = "class A { init(n) { this.n ← n; } get() { return this.n; } }
+                      class B < A { twice() { return this.get() * 2; } }
+                      var b ← B(21); print b.twice(); print b; print B;";
+
+        assert_eq!(run(source).unwrap(), "42\nB instance\nB\n");
+    }
+
+    #[test]
+    fn test_inline_caches_notice_other_shapes_and_classes() {
+        let source = "class A { init() { this.x ← 1; } get() { return «A»; } }
+                      class B { init() { this.y ← 0; this.x ← 2; } get() { return «B»; } }
+                      fun show(o) { print o.x; print o.get(); }
+                      show(A()); show(B()); show(A());
+                      var a ← A(); fun field() { return «field»; } a.get ← field; show(a);
+                      class A { init() { this.x ← 3; } get() { return «new A»; } }
+                      show(A());";
+
+        assert_eq!(run(source).unwrap(), "1\nA\n2\nB\n1\nA\n1\nfield\n3\nnew A\n");
+    }
+
+    #[test]
+    fn test_classes_made_while_running_keep_their_own_methods() {
+        let source = "class A { get() { return «A»; } }
+                      fun show(o) { print o.get(); }
+                      var a ← A();
+                      for (var i ← 0; i < 2; i ← i + 1) {
+                        show(a);
+                        class Local < A { get() { return «Local » + str(i); } }
+                        show(Local());
+                        class Inherits < Local {}
+                        show(Inherits());
+                      }";
+
+        assert_eq!(run(source).unwrap(), "A\nLocal 0\nLocal 0\nA\nLocal 1\nLocal 1\n");
+    }
+
+    #[test]
+    fn test_counters_keep_their_own_count() {
+        let source = "fun make_counter() { var count ← 0; fun counter() { count ← count + 1; return count; } return counter; }
+                      var a ← make_counter(); var b ← make_counter();
+                      print a(); print a(); print b(); print a();";
+
+        assert_eq!(run(source).unwrap(), "1\n2\n1\n3\n");
+    }
+
+    #[test]
+    fn test_closures_share_the_variables_they_capture() {
+        let source = "var get; var set;
+                      { var x ← «before»; fun g() { return x; } fun s(value) { x ← value; } get ← g; set ← s;
+                        print get(); x ← «inside»; print get(); }
+                      set(«after»); print get();";
+
+        assert_eq!(run(source).unwrap(), "before\ninside\nafter\n");
+    }
+
+    #[test]
+    fn test_each_loop_pass_gets_its_own_variable() {
+        let source = "var first; var second;
+                      for (var i ← 0; i < 2; i ← i + 1) { var j ← i; fun f() { return j; } if (i ≟ 0) first ← f; else second ← f; }
+                      print first(); print second();";
+
+        assert_eq!(run(source).unwrap(), "0\n1\n");
+    }
+
+    #[test]
+    fn test_super_calls_the_superclass_method() {
+        let source = "class A { name() { return «A»; } }
+                      class B < A { name() { var up ← super.name; return super.name() + up() + «B»; } }
+                      print B().name();";
+
+        assert_eq!(run(source).unwrap(), "AAB\n");
+    }
+
+    #[test]
+    fn test_collecting_all_the_time_keeps_what_is_still_used() {
+        let source = "fun make_counter() { var count ← 0; fun counter() { count ← count + 1; return count; } return counter; }
+                      class Pair { init(first, second) { this.first ← first; this.second ← second; } sum() { return this.first + this.second; } }
+                      class Named < Pair { sum() { return «sum: » + str(super.sum()); } }
+                      var counter ← make_counter(); var pairs ← nil; var text ← «»;
+                      for (var i ← 0; i < 20; i ← i + 1) {
+                        var pair ← Named(«a» + str(i), «b»); pair.next ← pairs; pairs ← pair;
+                        text ← text + str(counter());
+                      }
+                      var sum ← pairs.sum; print sum(); print pairs.next.next.first; print text; print counter();
+                      fun chain(n) { var head ← nil; for (var i ← 0; i < n; i ← i + 1) { var node ← Pair(i, 0); node.next ← head; head ← node; } return head; }
+                      var total ← 0; var node ← chain(50); while (node ≠ nil) { total ← total + node.sum(); node ← node.next; } print total;";
+        let stress = GcOptions { stress: true, ..GcOptions::default() };
+        let incremental = GcOptions { mode: GcMode::Incremental, stress: true, log: false };
+
+        assert_eq!(run_with_gc(source, stress).unwrap(), run(source).unwrap());
+        assert_eq!(run_with_gc(source, incremental).unwrap(), run(source).unwrap());
+        assert_eq!(run(source).unwrap(), "sum: a19b\na17\n1234567891011121314151617181920\n21\n1225\n");
+    }
+
+    #[test]
+    fn test_garbage_is_freed() {
+        let source = "class Node {} for (var i ← 0; i < 1000; i ← i + 1) { var node ← Node(); node.name ← «node » + str(i); }";
+        let function = compiler::compiler_struct::compile(source).unwrap();
+        for mode in [GcMode::StopTheWorld, GcMode::Incremental] {
+            let mut vm = Vm::new();
+            vm.set_gc_options(GcOptions { mode, stress: true, log: false });
+            let objects_before = vm.heap.len();
+            vm.interpret(&function).unwrap();
+
+            assert!(vm.heap.len() < objects_before + 50, "{} objects are left in {:?} mode", vm.heap.len(), mode);
+        }
+    }
+
+    #[test]
+    fn test_incremental_collection_sees_values_moved_between_objects() {
+        // Moving the tail of a list out of `from` before the
+        // collector has looked at `from` leaves it only in `to`,
+        // which the collector thinks it's finished with -- unless
+        // the write barrier tells it otherwise.  Varying the delay
+        // makes sure some move lands in the middle of marking.
+        let source = "class Pair { init(first, second) { this.first ← first; this.second ← second; } }
+                      fun chain(n) { var head ← nil; for (var i ← 0; i < n; i ← i + 1) { var node ← Pair(i, 0); node.next ← head; head ← node; } return head; }
+                      fun moved(delay) {
+                        var from ← chain(5);
+                        for (var i ← 0; i < delay; i ← i + 1) str(i);
+                        var to ← Pair(0, 0); to.next ← from.next; from.next ← nil;
+                        for (var i ← 0; i < 20; i ← i + 1) str(i);
+                        var total ← 0; var node ← to.next; while (node ≠ nil) { total ← total + node.first; node ← node.next; }
+                        return total;
+                      }
+                      var sum ← 0; for (var delay ← 0; delay < 40; delay ← delay + 1) sum ← sum + moved(delay); print sum;";
+        let incremental = GcOptions { mode: GcMode::Incremental, stress: true, log: false };
+
+        assert_eq!(run_with_gc(source, incremental).unwrap(), "240\n");
+    }
+
+    #[test]
+    fn test_incremental_collections_pause_more_often() {
+        let source = "var keep ← nil; for (var i ← 0; i < 20000; i ← i + 1) { var node ← «node » + str(i); if (i ≟ 7) keep ← node; } print keep;";
+        let function = compiler::compiler_struct::compile(source).unwrap();
+        let mut stats = Vec::new();
+        for mode in [GcMode::StopTheWorld, GcMode::Incremental] {
+            let mut vm = Vm::with_output(Box::new(SharedOutput::default()));
+            vm.set_gc_options(GcOptions { mode, ..GcOptions::default() });
+            vm.interpret(&function).unwrap();
+            stats.push(vm.gc_stats());
+        }
+        let (stop_the_world, incremental) = (stats[0], stats[1]);
+
+        assert!(stop_the_world.collections > 0);
+        assert_eq!(stop_the_world.pauses, stop_the_world.collections);
+        assert!(incremental.collections > 0);
+        assert!(incremental.pauses > 10 * incremental.collections);
+        assert!(incremental.bytes_freed > 0);
+    }
+
+    #[test]
+    fn test_errors_have_a_stack_trace() {
+        let error = run("fun inner() { return 1 + nil; }\nfun outer() {\n  inner();\n}\nouter();").unwrap_err();
+
+        assert_eq!(error.message, "Operands must be two numbers or two strings.");
+        assert_eq!(
+            error.to_string(),
+            "Runtime error: Operands must be two numbers or two strings.\n[line 1] in inner()\n[line 3] in outer()\n[line 5] in script"
+        );
+    }
+}